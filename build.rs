@@ -0,0 +1,37 @@
+extern crate cbindgen;
+
+use std::env;
+use std::path::Path;
+
+use cbindgen::{Builder, Config, EnumConfig, Language, RenameRule};
+
+/// Generates `include/chip8.h`, the header of the C API in `src/ffi.rs`.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let root = Path::new(&root);
+
+    let config = Config {
+        language: Language::C,
+        cpp_compat: true,
+        usize_is_size_t: true,
+        documentation: true,
+        include_guard: Some("CHIP8_H".into()),
+        autogen_warning: Some("/* Generated from src/ffi.rs by build.rs. Do not edit. */".into()),
+        enumeration: EnumConfig {
+            rename_variants: RenameRule::ScreamingSnakeCase,
+            prefix_with_name: true,
+            ..EnumConfig::default()
+        },
+        ..Config::default()
+    };
+
+    Builder::new()
+        .with_config(config)
+        .with_src(root.join("src/ffi.rs"))
+        .generate()
+        .expect("cannot generate the C header")
+        .write_to_file(root.join("include/chip8.h"));
+}
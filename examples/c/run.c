@@ -0,0 +1,92 @@
+/* Runs a CHIP-8 ROM through the C API and prints the display as text.
+ *
+ *     cargo build --release
+ *     cc -Iinclude examples/c/run.c -Ltarget/release -lchip8 -o run
+ *     LD_LIBRARY_PATH=target/release ./run game.ch8 120 [config.toml]
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "chip8.h"
+
+static int fail(Chip8Machine *machine, Chip8Status status) {
+    fprintf(stderr, "run: %s: %s\n", chip8_status_message(status), chip8_last_error(machine));
+    chip8_destroy(machine);
+    return 1;
+}
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: run <rom> [frames] [config.toml]\n");
+        return 2;
+    }
+    long frames = argc > 2 ? strtol(argv[2], NULL, 10) : 60;
+
+    static uint8_t rom[64 * 1024];
+    FILE *file = fopen(argv[1], "rb");
+    if (file == NULL) {
+        perror(argv[1]);
+        return 1;
+    }
+    size_t size = fread(rom, 1, sizeof rom, file);
+    fclose(file);
+
+    /* Without a config only the built-in defaults apply. */
+    static char config[64 * 1024];
+    if (argc > 3) {
+        file = fopen(argv[3], "rb");
+        if (file == NULL) {
+            perror(argv[3]);
+            return 1;
+        }
+        config[fread(config, 1, sizeof config - 1, file)] = '\0';
+        fclose(file);
+    }
+
+    Chip8Machine *machine = chip8_create();
+    Chip8Status status = chip8_load_rom_with_config(machine, rom, size, argc > 3 ? config : NULL);
+    if (status != CHIP8_STATUS_OK) {
+        return fail(machine, status);
+    }
+
+    long beeping = 0;
+    for (long frame = 0; frame < frames; frame++) {
+        bool sound;
+        status = chip8_run_frame(machine, &sound);
+        if (status != CHIP8_STATUS_OK) {
+            return fail(machine, status);
+        }
+        beeping += sound;
+    }
+
+    /* Save and restore the state, sizing the buffer with a first call. */
+    size_t length;
+    chip8_save_state(machine, NULL, 0, &length);
+    uint8_t *state = malloc(length);
+    status = chip8_save_state(machine, state, length, &length);
+    if (status == CHIP8_STATUS_OK) {
+        status = chip8_load_state(machine, state, length);
+    }
+    free(state);
+    if (status != CHIP8_STATUS_OK) {
+        return fail(machine, status);
+    }
+
+    uint8_t pixels[128 * 64];
+    size_t width, height;
+    status = chip8_framebuffer(machine, pixels, sizeof pixels, &width, &height);
+    if (status != CHIP8_STATUS_OK) {
+        return fail(machine, status);
+    }
+    for (size_t y = 0; y < height; y++) {
+        for (size_t x = 0; x < width; x++) {
+            putchar(pixels[y * width + x] ? '#' : '.');
+        }
+        putchar('\n');
+    }
+    printf("%ld frames, %ld with the buzzer on\n", frames, beeping);
+
+    chip8_destroy(machine);
+    return 0;
+}
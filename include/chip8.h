@@ -0,0 +1,222 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated from src/ffi.rs by build.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * What a call of the C API came to.
+ */
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK = 0,
+  /**
+   * A required pointer was NULL.
+   */
+  CHIP8_STATUS_NULL_POINTER = 1,
+  /**
+   * No ROM is loaded.
+   */
+  CHIP8_STATUS_NO_ROM = 2,
+  /**
+   * The ROM is unreadable or does not fit in memory.
+   */
+  CHIP8_STATUS_INVALID_ROM = 3,
+  /**
+   * The configuration for the ROM is invalid.
+   */
+  CHIP8_STATUS_INVALID_CONFIG = 4,
+  /**
+   * The buffer is too small; the length needed was stored.
+   */
+  CHIP8_STATUS_BUFFER_TOO_SMALL = 5,
+  /**
+   * The state was not saved from a machine like this one.
+   */
+  CHIP8_STATUS_INVALID_STATE = 6,
+  /**
+   * The program ran an instruction no platform defines.
+   */
+  CHIP8_STATUS_UNKNOWN_INSTRUCTION = 7,
+  /**
+   * The program accessed memory its policies forbid, or that does not
+   * exist.
+   */
+  CHIP8_STATUS_MEMORY_FAULT = 8,
+  /**
+   * The program called with all 16 stack levels in use.
+   */
+  CHIP8_STATUS_STACK_OVERFLOW = 9,
+  /**
+   * The program returned with nothing on the stack.
+   */
+  CHIP8_STATUS_STACK_UNDERFLOW = 10,
+  /**
+   * A bug in the emulator, caught before it crossed into C.
+   */
+  CHIP8_STATUS_INTERNAL = 11,
+} Chip8Status;
+
+/**
+ * A CHIP-8 machine for C programs, made by `chip8_create`.
+ *
+ * It runs a frame at a time like `Machine`. After a fault the program
+ * counter stays on the faulting instruction, so every further frame
+ * faults the same way until a reset or a state is loaded.
+ */
+typedef struct Chip8Machine Chip8Machine;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Makes a machine with no ROM loaded. Free it with `chip8_destroy`.
+ */
+struct Chip8Machine *chip8_create(void);
+
+/**
+ * Frees a machine. Does nothing given NULL.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create` and not have been destroyed.
+ */
+void chip8_destroy(struct Chip8Machine *machine);
+
+/**
+ * Loads a ROM image, replacing any program loaded before. The platform,
+ * quirks and speed come from the bundled ROM database or detection, on
+ * top of the built-in defaults; no config file is read.
+ *
+ * # Safety
+ *
+ * `data` must point to `size` readable bytes.
+ */
+enum Chip8Status chip8_load_rom(struct Chip8Machine *machine, const uint8_t *data, size_t size);
+
+/**
+ * Loads a ROM image like `chip8_load_rom`, then applies `config`, a
+ * NUL-terminated config file as TOML text, on top. A NULL `config` is
+ * the same as `chip8_load_rom`.
+ *
+ * # Safety
+ *
+ * `data` must point to `size` readable bytes, and `config` be NULL or a
+ * NUL-terminated string.
+ */
+enum Chip8Status chip8_load_rom_with_config(struct Chip8Machine *machine,
+                                            const uint8_t *data,
+                                            size_t size,
+                                            const char *config);
+
+/**
+ * Restarts the program from its pristine image.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`.
+ */
+enum Chip8Status chip8_reset(struct Chip8Machine *machine);
+
+/**
+ * Runs one frame, a sixtieth of a second of emulated time. Stores
+ * whether the buzzer sounded in `sound`, unless it is NULL.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`, and `sound` be NULL or
+ * writable.
+ */
+enum Chip8Status chip8_run_frame(struct Chip8Machine *machine, bool *sound);
+
+/**
+ * Sets the pressed keys, bit N for key N.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`.
+ */
+enum Chip8Status chip8_set_keys(struct Chip8Machine *machine, uint16_t keys);
+
+/**
+ * Copies the display to `pixels`, one color index per pixel, row by row.
+ * Stores its size in `width` and `height`, which may be NULL: 64x32, or
+ * 128x64 in SUPER-CHIP's high resolution. A buffer of 128 * 64 bytes
+ * always fits.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`, `pixels` be NULL or point to
+ * `size` writable bytes, and `width` and `height` be NULL or writable.
+ */
+enum Chip8Status chip8_framebuffer(struct Chip8Machine *machine,
+                                   uint8_t *pixels,
+                                   size_t size,
+                                   size_t *width,
+                                   size_t *height);
+
+/**
+ * Copies all of RAM to `buffer`, storing its length in `length`, which
+ * may be NULL.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`, `buffer` be NULL or point to
+ * `size` writable bytes, and `length` be NULL or writable.
+ */
+enum Chip8Status chip8_memory(struct Chip8Machine *machine,
+                              uint8_t *buffer,
+                              size_t size,
+                              size_t *length);
+
+/**
+ * Saves the whole machine state to `buffer`, storing its length in
+ * `length`, which may be NULL. Call with a NULL buffer to learn the
+ * length.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`, `buffer` be NULL or point to
+ * `size` writable bytes, and `length` be NULL or writable.
+ */
+enum Chip8Status chip8_save_state(struct Chip8Machine *machine,
+                                  uint8_t *buffer,
+                                  size_t size,
+                                  size_t *length);
+
+/**
+ * Restores a state from `chip8_save_state` of a machine with the same
+ * memory size.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`, and `data` point to `size`
+ * readable bytes.
+ */
+enum Chip8Status chip8_load_state(struct Chip8Machine *machine, const uint8_t *data, size_t size);
+
+/**
+ * Details of the machine's last error, valid until the next call with
+ * the machine. Empty before any error.
+ *
+ * # Safety
+ *
+ * `machine` must come from `chip8_create`.
+ */
+const char *chip8_last_error(const struct Chip8Machine *machine);
+
+/**
+ * A description of a status code, as a static string.
+ */
+const char *chip8_status_message(int status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,154 @@
+use std::io::{Cursor, Read};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use rom::Error;
+
+/// File extensions ROMs are usually distributed with.
+const ROM_EXTENSIONS: [&str; 6] = ["ch8", "c8", "sc8", "xo8", "c8x", "rom"];
+
+/// Unpacks `bytes` if they are a `.zip` or `.gz` archive.
+///
+/// Returns `None` for anything else. A zip holding more than one ROM needs
+/// `entry` to pick one, otherwise the candidates are reported in
+/// `Error::Ambiguous`.
+pub fn extract(bytes: &[u8], entry: Option<&str>) -> Result<Option<Vec<u8>>, Error> {
+    if bytes.starts_with(b"PK\x03\x04") {
+        extract_zip(bytes, entry).map(Some)
+    } else if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut data = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut data)
+            .map_err(|err| Error::Archive(format!("corrupt gzip stream: {}", err)))?;
+        Ok(Some(data))
+    } else {
+        Ok(None)
+    }
+}
+
+fn extract_zip(bytes: &[u8], entry: Option<&str>) -> Result<Vec<u8>, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))
+        .map_err(|err| Error::Archive(format!("corrupt zip archive: {}", err)))?;
+
+    let files: Vec<String> = archive.file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(String::from)
+        .collect();
+
+    let name = match entry {
+        Some(entry) => match files.iter().find(|name| *name == entry) {
+            Some(name) => name.clone(),
+            None => return Err(Error::MissingEntry(entry.to_string())),
+        },
+        None => {
+            let mut candidates: Vec<String> = files.iter().filter(|name| is_rom_name(name)).cloned().collect();
+            if candidates.is_empty() {
+                candidates = files;
+            }
+            candidates.sort();
+
+            match candidates.len() {
+                0 => return Err(Error::Archive("zip archive is empty".into())),
+                1 => candidates.remove(0),
+                _ => return Err(Error::Ambiguous(candidates)),
+            }
+        }
+    };
+
+    let mut file = archive.by_name(&name)
+        .map_err(|err| Error::Archive(format!("cannot read {}: {}", name, err)))?;
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|err| Error::Archive(format!("cannot read {}: {}", name, err)))?;
+
+    Ok(data)
+}
+
+fn is_rom_name(name: &str) -> bool {
+    match name.rsplit('.').next() {
+        Some(extension) if extension != name => {
+            ROM_EXTENSIONS.iter().any(|rom| extension.eq_ignore_ascii_case(rom))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::{FileOptions, ZipWriter};
+
+    use super::*;
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, data) in files {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn passes_plain_roms_through() {
+        assert!(extract(&[0x12, 0x00], None).unwrap().is_none());
+    }
+
+    #[test]
+    fn unpacks_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0x12, 0x00]).unwrap();
+
+        assert_eq!(extract(&encoder.finish().unwrap(), None).unwrap(), Some(vec![0x12, 0x00]));
+    }
+
+    #[test]
+    fn picks_the_only_rom_in_a_zip() {
+        let archive = zip(&[("README.txt", b"read me"), ("games/PONG.CH8", &[0x12, 0x00])]);
+
+        assert_eq!(extract(&archive, None).unwrap(), Some(vec![0x12, 0x00]));
+    }
+
+    #[test]
+    fn takes_the_only_file_of_a_zip_whatever_its_name() {
+        assert_eq!(extract(&zip(&[("pong", &[0x12, 0x00])]), None).unwrap(), Some(vec![0x12, 0x00]));
+    }
+
+    #[test]
+    fn asks_which_of_several_roms() {
+        let archive = zip(&[("b.ch8", &[0x12, 0x02]), ("a.sc8", &[0x12, 0x00]), ("notes.txt", b"")]);
+
+        match extract(&archive, None) {
+            Err(Error::Ambiguous(names)) => assert_eq!(names, ["a.sc8", "b.ch8"]),
+            other => panic!("expected the ROMs to choose from, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(extract(&archive, Some("b.ch8")).unwrap(), Some(vec![0x12, 0x02]));
+        assert_eq!(extract(&archive, Some("notes.txt")).unwrap(), Some(vec![]));
+
+        match extract(&archive, Some("c.ch8")) {
+            Err(Error::MissingEntry(name)) => assert_eq!(name, "c.ch8"),
+            other => panic!("expected a missing entry, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reports_corrupt_archives() {
+        match extract(b"PK\x03\x04 not really", None) {
+            Err(Error::Archive(message)) => assert!(message.starts_with("corrupt zip archive"), "{}", message),
+            other => panic!("expected a corrupt archive, got {:?}", other.map(|_| ())),
+        }
+        assert!(extract(&[0x1f, 0x8b, 0x00], None).is_err());
+    }
+
+    #[test]
+    fn recognizes_rom_extensions() {
+        assert!(is_rom_name("Pong.ch8"));
+        assert!(is_rom_name("dir/game.XO8"));
+        assert!(!is_rom_name("ch8"));
+        assert!(!is_rom_name("readme.md"));
+    }
+}
@@ -0,0 +1,49 @@
+use config::AudioConfig;
+
+/// The buzzer's tone: a square wave, or an XO-CHIP audio pattern once the
+/// program loads one.
+pub struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+    sample_rate: f32,
+    pattern: Option<[u8; 16]>,
+    pattern_inc: f32,
+}
+
+impl SquareWave {
+    pub fn new(audio: &AudioConfig, sample_rate: u32) -> SquareWave {
+        SquareWave {
+            phase_inc: audio.tone / sample_rate as f32,
+            phase: 0.0,
+            volume: audio.volume,
+            sample_rate: sample_rate as f32,
+            pattern: None,
+            pattern_inc: 0.0,
+        }
+    }
+
+    /// Plays the 128 bits of `pattern` in a loop at XO-CHIP's `pitch`,
+    /// 4000 bits a second at 64 and an octave higher every 48 steps, or
+    /// goes back to the square wave for `None`.
+    pub fn set_pattern(&mut self, pattern: Option<&[u8; 16]>, pitch: u8) {
+        let rate = 4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0);
+
+        self.pattern = pattern.copied();
+        self.pattern_inc = rate / 128.0 / self.sample_rate;
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let high = match self.pattern {
+            Some(ref pattern) => {
+                let bit = (self.phase * 128.0) as usize % 128;
+                pattern[bit / 8] & 0x80 >> (bit % 8) != 0
+            }
+            None => self.phase <= 0.5,
+        };
+        let inc = if self.pattern.is_some() { self.pattern_inc } else { self.phase_inc };
+        self.phase = (self.phase + inc) % 1.0;
+
+        if high { self.volume } else { -self.volume }
+    }
+}
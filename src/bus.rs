@@ -1,13 +1,19 @@
-use rom::Rom;
+use core::fmt;
+
+use cpu::Fault;
+use memory::{Layout, Memory, Ram, Storage};
+#[cfg(feature = "std")]
+use rom::{Error, Rom};
 
 mod map {
-    pub struct Range(u16, u16);
+    #[derive(Clone, Copy, Debug)]
+    pub struct Range(pub u16, pub u16);
 
     impl Range {
         pub fn contains(self, addr: u16) -> Option<u16> {
             let Range(start, end) = self;
 
-            if addr >= start && addr < start + (end - start + 0x1) {
+            if addr >= start && addr <= end {
                 Some(addr - start)
             } else {
                 None
@@ -15,33 +21,355 @@ mod map {
         }
     }
 
-    pub const ROM: Range = Range(0x000, 0xFFF);
+    /// Interpreter work area, stack and display buffer of the COSMAC VIP.
+    pub const VIP_STACK: Range = Range(0xEA0, 0xEFF);
+    pub const VIP_DISPLAY: Range = Range(0xF00, 0xFFF);
+}
+
+/// What a region of memory allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "kebab-case"))]
+pub enum Access {
+  /// Reads and writes go to RAM.
+  ReadWrite,
+  /// Writes are dropped, as if the region were ROM.
+  ReadOnly,
+  /// Any access is a fault.
+  None,
+}
+
+/// Access policy of each memory region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(default, deny_unknown_fields))]
+pub struct Policies {
+  /// Below the load address: fonts and, on real hardware, the interpreter.
+  pub interpreter: Access,
+  /// From the load address up to the VIP areas, or the end of memory.
+  pub program: Access,
+  /// 0xEA0-0xEFF on 4 KiB machines: the VIP's work area and stack.
+  pub stack: Access,
+  /// 0xF00-0xFFF on 4 KiB machines: the VIP's display buffer.
+  pub display: Access,
+}
+
+impl Default for Policies {
+  fn default() -> Policies {
+    Policies {
+      interpreter: Access::ReadWrite,
+      program: Access::ReadWrite,
+      stack: Access::ReadWrite,
+      display: Access::ReadWrite,
+    }
+  }
 }
 
-pub struct Bus {
-  pub rom: Rom,
+/// How many addresses can be frozen at once.
+pub const MAX_FROZEN: usize = 64;
+
+/// Why an address cannot be frozen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FreezeError {
+  /// The address is past the end of memory.
+  Unmapped(u16),
+  /// `MAX_FROZEN` addresses are frozen already.
+  Full,
 }
 
+impl fmt::Display for FreezeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      FreezeError::Unmapped(address) => write!(f, "{:#05x} is past the end of memory", address),
+      FreezeError::Full => write!(f, "{} addresses are frozen already", MAX_FROZEN),
+    }
+  }
+}
+
+impl core::error::Error for FreezeError {}
+
+#[derive(Clone, Copy)]
+struct Region {
+  name: &'static str,
+  range: map::Range,
+  access: Access,
+}
+
+/// The program image and layout RAM is restored from on reset.
+#[cfg(feature = "std")]
+struct Image {
+  rom: Rom,
+  layout: Layout,
+}
+
+pub struct Bus<S = Ram> {
+  #[cfg(feature = "std")]
+  image: Option<Image>,
+  font_address: u16,
+  large_font_address: u16,
+  pub memory: Memory<S>,
+  /// At most interpreter, program, stack and display.
+  regions: [Region; 4],
+  region_count: usize,
+  /// Addresses held at a value, as cheats do, and how many are in use.
+  frozen: [(u16, u8); MAX_FROZEN],
+  frozen_count: usize,
+}
+
+#[cfg(feature = "std")]
 impl Bus {
-  pub fn new(rom: Rom) -> Bus {
+  pub fn new(rom: Rom, layout: Layout, policies: Policies) -> Result<Bus, Error> {
+    let memory = Memory::new(&rom, &layout)?;
+
+    let mut bus = Bus::with_memory(memory, &layout, policies);
+    bus.image = Some(Image { rom, layout });
+
+    Ok(bus)
+  }
+
+  /// Restores RAM to the pristine program image, in place so pointers
+  /// handed out to it stay valid.
+  pub fn reset(&mut self) {
+    if let Some(ref image) = self.image {
+      self.memory.install(&image.layout, image.rom.program()).expect("layout was checked when the bus was built");
+    }
+    self.refreeze();
+  }
+
+  /// The program image the memory was loaded from, unless the bus was
+  /// built around memory loaded by the caller.
+  pub fn rom(&self) -> Option<&Rom> {
+    self.image.as_ref().map(|image| &image.rom)
+  }
+}
+
+impl<S: Storage> Bus<S> {
+  /// A bus around memory already holding the fonts and program where
+  /// `layout` says, such as from `Memory::with_storage`. Needs no heap.
+  pub fn with_memory<F>(memory: Memory<S>, layout: &Layout<F>, policies: Policies) -> Bus<S> {
+    let last = (layout.memory_size - 1) as u16;
+    let load_address = layout.load_address;
+
+    let unused = Region { name: "", range: map::Range(1, 0), access: Access::None };
+    let mut regions = [unused; 4];
+    let mut region_count = 0;
+    let mut push = |region| {
+      regions[region_count] = region;
+      region_count += 1;
+    };
+
+    if load_address > 0 {
+      push(Region { name: "interpreter", range: map::Range(0, load_address - 1), access: policies.interpreter });
+    }
+
+    // Only the VIP's 4 KiB memory map reserves its top for the interpreter.
+    if last == map::VIP_DISPLAY.1 && load_address < map::VIP_STACK.0 {
+      push(Region { name: "program", range: map::Range(load_address, map::VIP_STACK.0 - 1), access: policies.program });
+      push(Region { name: "stack", range: map::VIP_STACK, access: policies.stack });
+      push(Region { name: "display", range: map::VIP_DISPLAY, access: policies.display });
+    } else {
+      push(Region { name: "program", range: map::Range(load_address, last), access: policies.program });
+    }
+
     Bus {
-      rom,
+      #[cfg(feature = "std")]
+      image: None,
+      font_address: layout.font_address,
+      large_font_address: layout.large_font_address,
+      memory,
+      regions,
+      region_count,
+      frozen: [(0, 0); MAX_FROZEN],
+      frozen_count: 0,
+    }
+  }
+
+  /// Where the 4x5 glyphs for FX29 start.
+  pub fn font_address(&self) -> u16 {
+    self.font_address
+  }
+
+  /// Where the 8x10 glyphs for FX30 start.
+  pub fn large_font_address(&self) -> u16 {
+    self.large_font_address
+  }
+
+  fn region(&self, addr: u16) -> Option<&Region> {
+    self.regions[..self.region_count].iter().find(|region| region.range.contains(addr).is_some())
+  }
+
+  /// Holds `addr` at `value`: RAM gets it at once, whatever the policy of
+  /// its region, and stores by the program leave it there. Freezing a
+  /// frozen address changes its value.
+  pub fn freeze(&mut self, addr: u16, value: u8) -> Result<(), FreezeError> {
+    if addr as usize >= self.memory.size() {
+      return Err(FreezeError::Unmapped(addr));
+    }
+
+    let index = self.frozen().position(|(address, _)| address == addr);
+    match index {
+      Some(index) => self.frozen[index].1 = value,
+      None if self.frozen_count == MAX_FROZEN => return Err(FreezeError::Full),
+      None => {
+        self.frozen[self.frozen_count] = (addr, value);
+        self.frozen_count += 1;
+      }
     }
+    self.memory.store(addr, value);
+
+    Ok(())
   }
 
-  pub fn load(&self, addr: u16) -> u8 {
-      if let Some(offset) = map::ROM.contains(addr) {
-          return self.rom.load(offset);
+  /// Lets the program change `addr` again, returning whether it was
+  /// frozen. RAM keeps the frozen value until then.
+  pub fn unfreeze(&mut self, addr: u16) -> bool {
+    let index = self.frozen().position(|(address, _)| address == addr);
+    match index {
+      Some(index) => {
+        self.frozen.copy_within(index + 1..self.frozen_count, index);
+        self.frozen_count -= 1;
+        true
       }
-      
-      panic!("Unhandled load 8bit address {:#08x}", addr);
+      None => false,
+    }
   }
 
-  pub fn store(&mut self, addr: u16, value: u8) {
-      if let Some(offset) = map::ROM.contains(addr) {
-          return self.rom.store(offset, value);
+  pub fn unfreeze_all(&mut self) {
+    self.frozen_count = 0;
+  }
+
+  /// The frozen addresses and their values, in the order they were frozen.
+  pub fn frozen(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+    self.frozen[..self.frozen_count].iter().cloned()
+  }
+
+  /// Writes the frozen values back, after RAM was changed behind the
+  /// bus's back, such as by restoring a snapshot.
+  pub fn refreeze(&mut self) {
+    for &(address, value) in &self.frozen[..self.frozen_count] {
+      self.memory.store(address, value);
+    }
+  }
+
+  pub fn load(&self, addr: u16) -> Result<u8, Fault> {
+      match self.region(addr) {
+          Some(region) if region.access != Access::None => Ok(self.memory.load(addr)),
+          Some(region) => Err(Fault::Inaccessible(region.name, addr)),
+          None => Err(Fault::Unmapped(addr)),
+      }
+  }
+
+  pub fn store(&mut self, addr: u16, value: u8) -> Result<(), Fault> {
+      match self.region(addr).map(|region| (region.name, region.access)) {
+          Some((_, Access::ReadWrite)) if self.frozen().any(|(address, _)| address == addr) => {}
+          Some((_, Access::ReadWrite)) => self.memory.store(addr, value),
+          Some((_, Access::ReadOnly)) => {}
+          Some((name, Access::None)) => return Err(Fault::Inaccessible(name, addr)),
+          None => return Err(Fault::Unmapped(addr)),
       }
 
-      panic!("Unhandled store 8bit address {:#x}", addr);
+      Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn bus(memory_size: usize, policies: Policies) -> Bus<Vec<u8>> {
+    let layout: Layout<&[u8]> = Layout {
+      memory_size,
+      load_address: 0x200,
+      font: &[0xF0; 80],
+      font_address: 0x000,
+      large_font: &[0xFF; 160],
+      large_font_address: 0x050,
+    };
+    let memory = Memory::with_storage(vec![0; memory_size], &layout, &[0x12, 0x00]).unwrap();
+
+    Bus::with_memory(memory, &layout, policies)
+  }
+
+  #[test]
+  fn reads_and_writes_everywhere_by_default() {
+    let mut bus = bus(0x1000, Policies::default());
+
+    for &addr in &[0x000, 0x200, 0xEA0, 0xFFF] {
+      bus.store(addr, 0x42).unwrap();
+      assert_eq!(bus.load(addr), Ok(0x42));
+    }
+    assert_eq!(bus.load(0x1000), Err(Fault::Unmapped(0x1000)));
+    assert_eq!(bus.store(0x1000, 0), Err(Fault::Unmapped(0x1000)));
+  }
+
+  #[test]
+  fn drops_stores_to_read_only_regions() {
+    let mut bus = bus(0x1000, Policies { interpreter: Access::ReadOnly, program: Access::ReadOnly, ..Policies::default() });
+
+    assert_eq!(bus.store(0x000, 0x00), Ok(()));
+    assert_eq!(bus.load(0x000), Ok(0xF0));
+    assert_eq!(bus.store(0x200, 0x00), Ok(()));
+    assert_eq!(bus.load(0x200), Ok(0x12));
+
+    // The VIP's areas keep their own policy.
+    bus.store(0xF00, 0x01).unwrap();
+    assert_eq!(bus.load(0xF00), Ok(0x01));
+  }
+
+  #[test]
+  fn faults_on_inaccessible_regions_by_name() {
+    let mut bus = bus(0x1000, Policies { interpreter: Access::None, stack: Access::None, ..Policies::default() });
+
+    assert_eq!(bus.load(0x050), Err(Fault::Inaccessible("interpreter", 0x050)));
+    assert_eq!(bus.store(0xEA0, 0), Err(Fault::Inaccessible("stack", 0xEA0)));
+    assert_eq!(bus.load(0xE9F), Ok(0));
+    assert_eq!(bus.load(0xF00), Ok(0));
+  }
+
+  #[test]
+  fn reserves_the_vip_areas_only_in_4_kib() {
+    let policies = Policies { stack: Access::None, display: Access::None, ..Policies::default() };
+    let mut bus = bus(0x10000, policies);
+
+    assert_eq!(bus.load(0xEA0), Ok(0));
+    bus.store(0xFFF, 1).unwrap();
+    assert_eq!(bus.load(0xFFFF), Ok(0));
+  }
+
+  #[test]
+  fn holds_frozen_addresses_whatever_the_policy() {
+    let mut bus = bus(0x1000, Policies { program: Access::ReadOnly, ..Policies::default() });
+
+    bus.freeze(0x300, 9).unwrap();
+    bus.freeze(0x000, 7).unwrap();
+    assert_eq!(bus.load(0x300), Ok(9));
+
+    bus.store(0x000, 1).unwrap();
+    assert_eq!(bus.load(0x000), Ok(7));
+    assert_eq!(bus.frozen().collect::<Vec<_>>(), [(0x300, 9), (0x000, 7)]);
+
+    assert!(bus.unfreeze(0x000));
+    assert!(!bus.unfreeze(0x000));
+    bus.store(0x000, 1).unwrap();
+    assert_eq!(bus.load(0x000), Ok(1));
+
+    // RAM changed behind the bus's back gets the frozen values again.
+    bus.memory.store(0x300, 0);
+    bus.refreeze();
+    assert_eq!(bus.load(0x300), Ok(9));
+  }
+
+  #[test]
+  fn limits_what_can_be_frozen() {
+    let mut bus = bus(0x1000, Policies::default());
+
+    assert_eq!(bus.freeze(0x1000, 0), Err(FreezeError::Unmapped(0x1000)));
+    for addr in 0..MAX_FROZEN as u16 {
+      bus.freeze(0x300 + addr, 0).unwrap();
+    }
+    assert_eq!(bus.freeze(0x400, 0), Err(FreezeError::Full));
+    // Refreezing changes the value in place.
+    assert_eq!(bus.freeze(0x300, 5), Ok(()));
+    assert_eq!(bus.load(0x300), Ok(5));
   }
 }
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use flate2::Crc;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+
+use config::Rgb;
+use video::Image;
+
+/// Writes `image` as an 8-bit RGB PNG.
+pub fn write_png<W: Write>(mut out: W, image: &Image) -> io::Result<()> {
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // 8 bits per channel, truecolor, deflate, no filtering, no interlace.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header)?;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in image.pixels.chunks(image.width.max(1)) {
+        encoder.write_all(&[0])?;
+        for &Rgb(r, g, b) in row {
+            encoder.write_all(&[r, g, b])?;
+        }
+    }
+    write_chunk(&mut out, b"IDAT", &encoder.finish()?)?;
+
+    write_chunk(&mut out, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc.sum().to_be_bytes())
+}
+
+/// Writes an animated GIF one frame at a time.
+///
+/// Each frame carries its own 256-color table, so palettes and fading
+/// colors change freely between frames; a frame with more colors than
+/// that is mapped onto a fixed color cube.
+pub struct GifEncoder<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Starts a looping animation of `width` by `height` frames.
+    pub fn new(mut out: W, width: usize, height: usize) -> io::Result<GifEncoder<W>> {
+        out.write_all(b"GIF89a")?;
+        out.write_all(&(width as u16).to_le_bytes())?;
+        out.write_all(&(height as u16).to_le_bytes())?;
+        // No global color table, background 0, square pixels.
+        out.write_all(&[0, 0, 0])?;
+
+        // Loop forever.
+        out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        Ok(GifEncoder { out, width, height })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Adds `image`, shown for `delay` hundredths of a second.
+    pub fn frame(&mut self, image: &Image, delay: u16) -> io::Result<()> {
+        if image.width != self.width || image.height != self.height {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame size differs from the animation's"));
+        }
+
+        let (table, indices) = index_colors(&image.pixels);
+
+        // Graphic control extension: the delay, no transparency.
+        self.out.write_all(&[0x21, 0xf9, 0x04, 0x00])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor covering the whole screen, with a local table of
+        // 256 colors.
+        self.out.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.out.write_all(&(self.width as u16).to_le_bytes())?;
+        self.out.write_all(&(self.height as u16).to_le_bytes())?;
+        self.out.write_all(&[0x87])?;
+        for index in 0..256 {
+            let Rgb(r, g, b) = table.get(index).cloned().unwrap_or(Rgb(0, 0, 0));
+            self.out.write_all(&[r, g, b])?;
+        }
+
+        self.out.write_all(&[8])?;
+        for block in lzw(&indices).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+
+    /// Ends the animation, returning the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// A color table of at most 256 entries and each pixel's index into it.
+fn index_colors(pixels: &[Rgb]) -> (Vec<Rgb>, Vec<u8>) {
+    let mut table = Vec::new();
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::with_capacity(pixels.len());
+
+    for &pixel in pixels {
+        let next = table.len();
+        let index = *lookup.entry(pixel).or_insert(next);
+        if index == next {
+            if next == 256 {
+                return color_cube(pixels);
+            }
+            table.push(pixel);
+        }
+        indices.push(index as u8);
+    }
+
+    (table, indices)
+}
+
+/// Maps `pixels` onto 6 levels of red and blue and 7 of green.
+fn color_cube(pixels: &[Rgb]) -> (Vec<Rgb>, Vec<u8>) {
+    let level = |value: u8, levels: u32| (value as u32 * (levels - 1) + 127) / 255;
+    let value = |level: u32, levels: u32| (level * 255 / (levels - 1)) as u8;
+
+    let mut table = Vec::with_capacity(252);
+    for r in 0..6 {
+        for g in 0..7 {
+            for b in 0..6 {
+                table.push(Rgb(value(r, 6), value(g, 7), value(b, 6)));
+            }
+        }
+    }
+
+    let indices = pixels.iter()
+        .map(|&Rgb(r, g, b)| (level(r, 6) * 42 + level(g, 7) * 6 + level(b, 6)) as u8)
+        .collect();
+
+    (table, indices)
+}
+
+/// GIF's variable-width LZW over 8-bit indices, packed least significant
+/// bit first.
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+    const MAX_CODES: u16 = 4096;
+
+    let mut output = Vec::new();
+    let mut bits: u32 = 0;
+    let mut pending = 0;
+
+    let mut emit = |code: u16, width: u32, output: &mut Vec<u8>| {
+        bits |= (code as u32) << pending;
+        pending += width;
+        while pending >= 8 {
+            output.push(bits as u8);
+            bits >>= 8;
+            pending -= 8;
+        }
+    };
+
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = END + 1;
+    let mut width = 9;
+
+    emit(CLEAR, width, &mut output);
+
+    let mut prefix = match indices.first() {
+        Some(&first) => first as u16,
+        None => {
+            emit(END, width, &mut output);
+            emit(0, 7, &mut output);
+            return output;
+        }
+    };
+
+    for &index in &indices[1..] {
+        if let Some(&code) = dictionary.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        emit(prefix, width, &mut output);
+
+        if next < MAX_CODES {
+            dictionary.insert((prefix, index), next);
+            next += 1;
+            // The decoder widens its codes one step behind the encoder.
+            if next > 1 << width && width < 12 {
+                width += 1;
+            }
+        } else {
+            emit(CLEAR, width, &mut output);
+            dictionary.clear();
+            next = END + 1;
+            width = 9;
+        }
+
+        prefix = index as u16;
+    }
+
+    emit(prefix, width, &mut output);
+    emit(END, width, &mut output);
+    // Flush the last partial byte.
+    emit(0, 7, &mut output);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::*;
+
+    /// Decodes GIF LZW as a viewer would, independently of `lzw`.
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let mut table: Vec<Vec<u8>> = (0..=255).map(|index| vec![index]).collect();
+        table.extend([vec![], vec![]]);
+        let mut width = 9;
+        let mut at = 0;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+
+        loop {
+            let code = (0..width).fold(0, |code, bit| {
+                let set = data[(at + bit) / 8] >> ((at + bit) % 8) & 1;
+                code | (set as usize) << bit
+            });
+            at += width;
+
+            match code {
+                256 => {
+                    table.truncate(258);
+                    width = 9;
+                    previous = None;
+                    continue;
+                }
+                257 => return output,
+                _ => {}
+            }
+
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [&previous[..], &previous[..1]].concat(),
+                (None, None) => panic!("code {} before any other", code),
+            };
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    table.push([&previous[..], &entry[..1]].concat());
+                }
+            }
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips() {
+        let mut noise = Vec::new();
+        let mut state: u32 = 1;
+        for _ in 0..50_000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            noise.push((state >> 16) as u8);
+        }
+        let repetitive: Vec<u8> = (0..20_000).map(|index| (index / 7 % 3) as u8).collect();
+
+        for indices in [vec![], vec![5], vec![1, 1, 1, 1, 1, 1, 1], repetitive, noise] {
+            assert_eq!(unlzw(&lzw(&indices)), indices);
+        }
+    }
+
+    #[test]
+    fn lzw_compresses_runs() {
+        assert!(lzw(&[0; 64 * 32]).len() < 100);
+    }
+
+    #[test]
+    fn maps_many_colors_onto_the_cube() {
+        let few = [Rgb(1, 2, 3), Rgb(4, 5, 6), Rgb(1, 2, 3)];
+        assert_eq!(index_colors(&few), (vec![Rgb(1, 2, 3), Rgb(4, 5, 6)], vec![0, 1, 0]));
+
+        let many: Vec<Rgb> = (0..300).map(|index| Rgb(index as u8, (index >> 8) as u8, 0)).collect();
+        let (table, indices) = index_colors(&many);
+        assert_eq!(table.len(), 252);
+        assert_eq!(table[indices[0] as usize], Rgb(0, 0, 0));
+        assert_eq!(table[indices[255] as usize], Rgb(255, 0, 0));
+    }
+
+    #[test]
+    fn writes_png_chunks_with_their_crc() {
+        let mut image = Image::new(2, 1);
+        image.pixels = vec![Rgb(255, 0, 0), Rgb(0, 0, 255)];
+
+        let mut png = Vec::new();
+        write_png(&mut png, &image).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[8..16], b"\x00\x00\x00\x0dIHDR");
+        assert_eq!(&png[16..29], &[0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        // The CRC of IEND, the same in every PNG.
+        assert_eq!(&png[png.len() - 12..], b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+
+        // Every chunk's CRC covers its type and data.
+        let mut at = 8;
+        while at < png.len() {
+            let length = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let mut crc = Crc::new();
+            crc.update(&png[at + 4..at + 8 + length]);
+            assert_eq!(png[at + 8 + length..at + 12 + length], crc.sum().to_be_bytes());
+
+            if &png[at + 4..at + 8] == b"IDAT" {
+                let mut pixels = Vec::new();
+                ZlibDecoder::new(&png[at + 8..at + 8 + length]).read_to_end(&mut pixels).unwrap();
+                assert_eq!(pixels, [0, 255, 0, 0, 0, 0, 255]);
+            }
+            at += 12 + length;
+        }
+    }
+
+    #[test]
+    fn writes_gif_frames_of_the_animation_size() {
+        let mut gif = GifEncoder::new(Vec::new(), 2, 1).unwrap();
+        gif.frame(&Image::new(2, 1), 2).unwrap();
+        assert!(gif.frame(&Image::new(1, 1), 2).is_err());
+
+        let bytes = gif.finish().unwrap();
+        assert_eq!(&bytes[..10], b"GIF89a\x02\x00\x01\x00");
+        assert_eq!(bytes.last(), Some(&0x3b));
+    }
+}
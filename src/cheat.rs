@@ -0,0 +1,206 @@
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use config::config_dir;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The cheat file is not valid TOML, or holds an invalid code.
+    Parse(PathBuf, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Parse(ref path, ref message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+/// An address frozen at a value, written `"AAA:VV"` in hex: `"2F0:09"`
+/// holds 0x2F0 at 9.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Cheat, String> {
+        let invalid = || format!("invalid cheat `{}`, expected \"address:value\" in hex", code);
+
+        let (address, value) = code.trim().split_once(':').ok_or_else(invalid)?;
+        Ok(Cheat {
+            address: u16::from_str_radix(address.trim(), 16).map_err(|_| invalid())?,
+            value: u8::from_str_radix(value.trim(), 16).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl TryFrom<String> for Cheat {
+    type Error = String;
+
+    fn try_from(code: String) -> Result<Cheat, String> {
+        code.parse()
+    }
+}
+
+impl From<Cheat> for String {
+    fn from(cheat: Cheat) -> String {
+        cheat.to_string()
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X}:{:02X}", self.address, self.value)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheatFile {
+    #[serde(default)]
+    codes: Vec<Cheat>,
+}
+
+/// `cheats` next to the default config file, where the codes of each ROM
+/// are kept.
+pub fn dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("cheats"))
+}
+
+/// `<sha1>.toml` in `dir()`, where the codes of the ROM with that hash are
+/// kept.
+pub fn path(hash: &str) -> Option<PathBuf> {
+    dir().map(|dir| path_in(&dir, hash))
+}
+
+fn path_in(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(format!("{}.toml", hash))
+}
+
+/// The codes saved for the ROM with `hash`, none if there is no file.
+pub fn load(hash: &str) -> Result<Vec<Cheat>, Error> {
+    match dir() {
+        Some(dir) => load_from(&dir, hash),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The codes saved in `dir` for the ROM with `hash`.
+pub fn load_from(dir: &Path, hash: &str) -> Result<Vec<Cheat>, Error> {
+    let path = path_in(dir, hash);
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(Error::Io(err)),
+    };
+    let file: CheatFile = toml::from_str(&text).map_err(|err| Error::Parse(path, err.to_string()))?;
+
+    Ok(file.codes)
+}
+
+/// Saves `cheats` as the codes of the ROM with `hash`, returning the file
+/// written.
+pub fn save(hash: &str, cheats: &[Cheat]) -> Result<PathBuf, Error> {
+    let dir = dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    save_to(&dir, hash, cheats)
+}
+
+/// Saves `cheats` in `dir` as the codes of the ROM with `hash`.
+pub fn save_to(dir: &Path, hash: &str, cheats: &[Cheat]) -> Result<PathBuf, Error> {
+    fs::create_dir_all(dir)?;
+    let path = path_in(dir, hash);
+
+    let file = CheatFile { codes: cheats.to_vec() };
+    fs::write(&path, toml::to_string(&file).expect("cheats serialize to TOML"))?;
+
+    Ok(path)
+}
+
+/// How a byte must have changed since the last step of a search to stay a
+/// candidate. Written `"equal"`, `"changed"`, `"increased"`,
+/// `"decreased"`, or `{ "value" = n }` for a byte that is now `n`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Comparison {
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    Value(u8),
+}
+
+impl Comparison {
+    fn matches(self, before: u8, now: u8) -> bool {
+        match self {
+            Comparison::Equal => now == before,
+            Comparison::Changed => now != before,
+            Comparison::Increased => now > before,
+            Comparison::Decreased => now < before,
+            Comparison::Value(value) => now == value,
+        }
+    }
+}
+
+/// Narrows down where a program keeps a value, such as lives or a score,
+/// by comparing RAM between snapshots.
+///
+/// Every address starts as a candidate. Each step keeps those whose byte
+/// changed the way asked since the step before, then remembers RAM for
+/// the next: lose a life, keep `decreased`; wait, keep `equal`; and so on
+/// until a few addresses are left to freeze.
+#[derive(Clone, Debug)]
+pub struct Search {
+    previous: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl Search {
+    /// Starts from a snapshot of `memory`, with every address a candidate.
+    pub fn new(memory: &[u8]) -> Search {
+        Search {
+            previous: memory.to_vec(),
+            candidates: (0..memory.len()).map(|address| address as u16).collect(),
+        }
+    }
+
+    /// Keeps the candidates whose byte in `memory` compares with the last
+    /// snapshot as asked, returning how many are left.
+    pub fn narrow(&mut self, memory: &[u8], comparison: Comparison) -> usize {
+        let previous = &self.previous;
+        self.candidates.retain(|&address| {
+            let address = address as usize;
+            comparison.matches(previous[address], memory[address])
+        });
+        self.previous.copy_from_slice(memory);
+
+        self.candidates.len()
+    }
+
+    /// The addresses still in the running, lowest first.
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+}
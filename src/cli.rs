@@ -0,0 +1,183 @@
+use std::path::PathBuf;
+
+use chip8::config::Sources;
+use chip8::remote::Address;
+
+pub const USAGE: &str = "\
+usage: chip8 [options] <rom>
+       chip8 config dump [options] [rom]
+       chip8 serve [options] <address> <rom>
+       chip8 debug [options] <rom>
+       chip8 graph dot|json [options] <rom>
+       chip8 decompile pseudo|asm [options] <rom>
+
+<rom> is a ROM file, a .zip or .gz archive holding one, or - for stdin.
+
+`serve` runs without a window, remote-controlled over JSON-RPC on
+<address>: a loopback host:port, or unix:<path> for a Unix socket.
+
+`debug` runs without a window, driven by commands read from stdin:
+stepping, registers, memory, and cheat searches and codes. `help` at
+its prompt lists them.
+
+`graph` prints the basic blocks and subroutines of the program, found
+without running it, as Graphviz DOT or JSON.
+
+`decompile` prints the program as structured pseudo-code, or as
+assembler source with labels that assembles back to the same bytes.
+
+options:
+    --entry <name>      ROM to load from an archive holding several
+    --watch             reload the ROM whenever the file changes
+    --config <file>     read settings from <file> instead of the default
+    --set <key=value>   override a setting, e.g. --set quirks.shift=true
+    --speed <n>         instructions per second (cpu.speed)
+    --scale <n>         window pixels per CHIP-8 pixel (video.scale)
+    --load-address <a>  address the program is copied to (cpu.load_address)
+    --pc <a>            address execution starts at (cpu.entry)
+    --mute              disable the buzzer (audio.enabled=false)
+    -h, --help          print this message";
+
+pub enum Command {
+    Run(String),
+    ConfigDump(Option<String>),
+    Serve(Address, String),
+    Debug(String),
+    Graph(GraphFormat, String),
+    Decompile(DecompileFormat, String),
+    Help,
+}
+
+#[derive(Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    Json,
+}
+
+#[derive(Clone, Copy)]
+pub enum DecompileFormat {
+    Pseudo,
+    Asm,
+}
+
+pub struct Args {
+    pub command: Command,
+    pub entry: Option<String>,
+    pub watch: bool,
+    pub sources: Sources,
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+    let mut sources = Sources::default();
+    let mut entry = None;
+    let mut watch = false;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} expects a value", name));
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(Args { command: Command::Help, entry, watch, sources });
+            }
+            "--entry" => entry = Some(value("--entry")?),
+            "--watch" => watch = true,
+            "--config" => sources.file = Some(PathBuf::from(value("--config")?)),
+            "--set" => sources.overrides.push(value("--set")?),
+            "--speed" => sources.overrides.push(format!("cpu.speed={}", value("--speed")?)),
+            "--scale" => sources.overrides.push(format!("video.scale={}", value("--scale")?)),
+            "--load-address" => sources.overrides.push(format!("cpu.load_address={}", value("--load-address")?)),
+            "--pc" => sources.overrides.push(format!("cpu.entry={}", value("--pc")?)),
+            "--mute" => sources.overrides.push("audio.enabled=false".into()),
+            // A lone `-` is stdin, not an option.
+            "-" => positional.push(arg),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = if positional.first().map(String::as_str) == Some("config") {
+        match positional.get(1).map(String::as_str) {
+            Some("dump") if positional.len() <= 3 => Command::ConfigDump(positional.get(2).cloned()),
+            Some("dump") => return Err("config dump takes at most one ROM".into()),
+            _ => return Err("expected `config dump`".into()),
+        }
+    } else if positional.first().map(String::as_str) == Some("serve") {
+        match positional.len() {
+            3 => Command::Serve(positional[1].parse()?, positional.remove(2)),
+            _ => return Err("serve takes an address and a ROM".into()),
+        }
+    } else if positional.first().map(String::as_str) == Some("debug") {
+        match positional.len() {
+            2 => Command::Debug(positional.remove(1)),
+            _ => return Err("debug takes a single ROM".into()),
+        }
+    } else if positional.first().map(String::as_str) == Some("graph") {
+        let format = match positional.get(1).map(String::as_str) {
+            Some("dot") => GraphFormat::Dot,
+            Some("json") => GraphFormat::Json,
+            _ => return Err("expected `graph dot` or `graph json`".into()),
+        };
+        match positional.len() {
+            3 => Command::Graph(format, positional.remove(2)),
+            _ => return Err("graph takes a single ROM".into()),
+        }
+    } else if positional.first().map(String::as_str) == Some("decompile") {
+        let format = match positional.get(1).map(String::as_str) {
+            Some("pseudo") => DecompileFormat::Pseudo,
+            Some("asm") => DecompileFormat::Asm,
+            _ => return Err("expected `decompile pseudo` or `decompile asm`".into()),
+        };
+        match positional.len() {
+            3 => Command::Decompile(format, positional.remove(2)),
+            _ => return Err("decompile takes a single ROM".into()),
+        }
+    } else {
+        match positional.len() {
+            1 => Command::Run(positional.remove(0)),
+            0 => return Err("no ROM given".into()),
+            _ => return Err("expected a single ROM".into()),
+        }
+    };
+
+    if let Command::Run(ref rom) = command {
+        if watch && rom == "-" {
+            return Err("--watch needs a ROM file, not stdin".into());
+        }
+    }
+    if let Command::Debug(ref rom) = command {
+        if rom == "-" {
+            return Err("debug reads commands from stdin, so it needs a ROM file".into());
+        }
+    }
+
+    Ok(Args { command, entry, watch, sources })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Args, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn reads_a_rom_from_stdin() {
+        match parse_args(&["-"]).unwrap().command {
+            Command::Run(rom) => assert_eq!(rom, "-"),
+            _ => panic!("expected a run"),
+        }
+        match parse_args(&["config", "dump", "-"]).unwrap().command {
+            Command::ConfigDump(rom) => assert_eq!(rom.as_deref(), Some("-")),
+            _ => panic!("expected a config dump"),
+        }
+    }
+
+    #[test]
+    fn still_rejects_unknown_options() {
+        assert_eq!(parse_args(&["--bogus", "game.ch8"]).err().unwrap(), "unknown option `--bogus`");
+        assert_eq!(parse_args(&["--watch", "-"]).err().unwrap(), "--watch needs a ROM file, not stdin");
+        assert_eq!(parse_args(&["debug", "-"]).err().unwrap(), "debug reads commands from stdin, so it needs a ROM file");
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use chip8::config::PlaybackConfig;
+use chip8::cpu::{Cpu, Fault};
+use chip8::rewind::Rewind;
+
+/// Emulated time between two ticks of the delay and sound timers.
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Longest stretch of real time made up for at once, so a stall of the
+/// host does not turn into a burst of emulation.
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+/// Real time spent per call when fast-forwarding uncapped.
+const UNCAPPED_SLICE: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Normal,
+    Paused,
+    /// Running at this multiplier, or as fast as possible when `None`.
+    FastForward(Option<f64>),
+    SlowMotion(f64),
+    Rewinding,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mode::Normal => Ok(()),
+            Mode::Paused => write!(f, "paused"),
+            Mode::FastForward(Some(rate)) => write!(f, "fast-forward {}x", rate),
+            Mode::FastForward(None) => write!(f, "fast-forward uncapped"),
+            Mode::SlowMotion(rate) => write!(f, "slow motion {}x", rate),
+            Mode::Rewinding => write!(f, "rewinding"),
+        }
+    }
+}
+
+/// Paces emulation against real time, in whichever mode the hotkeys
+/// selected.
+///
+/// The CPU runs `speed` instructions per emulated second and its timers
+/// tick every emulated 1/60 s, so every mode keeps the program's own
+/// timing consistent, only faster or slower than the wall clock.
+pub struct Clock {
+    playback: PlaybackConfig,
+    instruction: Duration,
+    /// Emulated time not yet run.
+    owed: Duration,
+    /// Emulated time since the last timer tick.
+    frame_elapsed: Duration,
+    last: Instant,
+    /// Instructions run since the clock started.
+    instructions: u64,
+    /// Instructions run in the current and the last complete frame.
+    frame_instructions: u32,
+    last_frame_instructions: u32,
+
+    pub paused: bool,
+    pub fast_forward: bool,
+    pub slow_motion: bool,
+    pub rewinding: bool,
+    frames_to_advance: u32,
+
+    /// History to rewind through, when enabled.
+    pub rewind: Option<Rewind>,
+}
+
+impl Clock {
+    pub fn new(speed: u32, playback: &PlaybackConfig, rewind: Option<Rewind>, now: Instant) -> Clock {
+        Clock {
+            playback: playback.clone(),
+            instruction: Duration::from_secs(1) / speed,
+            owed: Duration::from_secs(0),
+            frame_elapsed: Duration::from_secs(0),
+            last: now,
+            instructions: 0,
+            frame_instructions: 0,
+            last_frame_instructions: 0,
+            paused: false,
+            fast_forward: false,
+            slow_motion: false,
+            rewinding: false,
+            frames_to_advance: 0,
+            rewind,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        if self.rewinding && self.rewind.is_some() {
+            Mode::Rewinding
+        } else if self.paused {
+            Mode::Paused
+        } else if self.fast_forward {
+            let rate = self.playback.fast_forward;
+            Mode::FastForward(if rate == 0.0 { None } else { Some(rate) })
+        } else if self.slow_motion {
+            Mode::SlowMotion(self.playback.slow_motion)
+        } else {
+            Mode::Normal
+        }
+    }
+
+    /// Instructions run since the clock started, rewinding aside.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Instructions run in the last complete frame.
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.last_frame_instructions
+    }
+
+    /// Forgets the rewind history, when the program was restarted or
+    /// replaced.
+    pub fn clear_history(&mut self) {
+        if let Some(ref mut rewind) = self.rewind {
+            rewind.clear();
+        }
+    }
+
+    /// Queues one frame to run while paused.
+    pub fn advance_frame(&mut self) {
+        if self.paused {
+            self.frames_to_advance += 1;
+        }
+    }
+
+    /// Runs the CPU for the real time passed since the last call, calling
+    /// `on_frame` after each timer tick. A fault pauses the clock.
+    pub fn run<F: FnMut(&Cpu)>(&mut self, cpu: &mut Cpu, now: Instant, mut on_frame: F) -> Result<(), Fault> {
+        let result = self.advance(cpu, now, &mut on_frame);
+        if result.is_err() {
+            self.paused = true;
+            self.owed = Duration::from_secs(0);
+            self.frames_to_advance = 0;
+        }
+
+        result
+    }
+
+    fn advance<F: FnMut(&Cpu)>(&mut self, cpu: &mut Cpu, now: Instant, mut on_frame: F) -> Result<(), Fault> {
+        let real = (now - self.last).min(MAX_CATCH_UP);
+        self.last = now;
+
+        match self.mode() {
+            Mode::Paused => {
+                self.owed = Duration::from_secs(0);
+                while self.frames_to_advance > 0 {
+                    self.frames_to_advance -= 1;
+                    while !self.step(cpu, &mut on_frame)? {}
+                }
+                return Ok(());
+            }
+            Mode::FastForward(None) => {
+                while now.elapsed() < UNCAPPED_SLICE {
+                    while !self.step(cpu, &mut on_frame)? {}
+                }
+                return Ok(());
+            }
+            Mode::Rewinding => {
+                // One frame back per frame of real time.
+                self.owed += real;
+                while self.owed >= FRAME {
+                    self.owed -= FRAME;
+                    let rewind = self.rewind.as_mut().unwrap();
+                    if !rewind.step_back(cpu) {
+                        self.owed = Duration::from_secs(0);
+                    }
+                }
+                return Ok(());
+            }
+            Mode::FastForward(Some(rate)) => self.owed += real.mul_f64(rate),
+            Mode::SlowMotion(rate) => self.owed += real.mul_f64(rate),
+            Mode::Normal => self.owed += real,
+        }
+
+        while self.owed >= self.instruction {
+            self.owed -= self.instruction;
+            self.step(cpu, &mut on_frame)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs one instruction, returning whether it completed a frame.
+    fn step<F: FnMut(&Cpu)>(&mut self, cpu: &mut Cpu, on_frame: &mut F) -> Result<bool, Fault> {
+        if let Some(ref mut rewind) = self.rewind {
+            rewind.record_instruction(cpu);
+        }
+        if let Err(fault) = cpu.run_next_instruction() {
+            if let Some(ref mut rewind) = self.rewind {
+                rewind.discard_instruction();
+            }
+            return Err(fault);
+        }
+        self.instructions += 1;
+        self.frame_instructions += 1;
+
+        self.frame_elapsed += self.instruction;
+        if self.frame_elapsed < FRAME {
+            return Ok(false);
+        }
+        self.frame_elapsed -= FRAME;
+        self.last_frame_instructions = self.frame_instructions;
+        self.frame_instructions = 0;
+
+        cpu.tick_timers();
+        if let Some(ref mut rewind) = self.rewind {
+            rewind.end_frame(cpu);
+        }
+        on_frame(cpu);
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,887 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml::Value;
+use toml::value::Table;
+
+use bus::Policies;
+use filter::Filter;
+use font;
+use palette;
+use platform::Platform;
+use quirks::Quirks;
+use memory::Layout;
+use rom::Rom;
+
+/// Every user-tunable setting of the emulator.
+///
+/// Settings are layered: the built-in defaults below, then the defaults of
+/// the selected platform, then the ROM database entry for the loaded ROM,
+/// then the user config file, then the `[rom.<sha1>]` section of that file
+/// matching the ROM, then `--set key=value` overrides from the command line.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub cpu: CpuConfig,
+    pub quirks: Quirks,
+    pub font: FontConfig,
+    pub memory: Policies,
+    pub video: VideoConfig,
+    pub palette: Palette,
+    pub audio: AudioConfig,
+    pub playback: PlaybackConfig,
+    pub rewind: RewindConfig,
+    pub keymap: Keymap,
+    pub hotkeys: Hotkeys,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CpuConfig {
+    /// Interpreter to emulate. Selects the default quirks, speed and load
+    /// address.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    /// Instructions executed per second.
+    pub speed: u32,
+    /// Address the program is copied to.
+    pub load_address: u16,
+    /// Initial PC. Defaults to the load address.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<u16>,
+}
+
+impl Default for CpuConfig {
+    fn default() -> CpuConfig {
+        CpuConfig {
+            platform: None,
+            speed: 500,
+            load_address: 0x200,
+            entry: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FontConfig {
+    /// Built-in 4x5 font: "vip", "dream6800", "eti660" or "schip".
+    pub small: String,
+    /// Built-in 8x10 font: "schip".
+    pub large: String,
+    /// File of 80 bytes of 4x5 glyphs, used instead of `small`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub small_file: Option<PathBuf>,
+    /// File of 100 or 160 bytes of 8x10 glyphs, used instead of `large`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub large_file: Option<PathBuf>,
+    /// Address of the 4x5 font, used by FX29.
+    pub address: u16,
+    /// Address of the 8x10 font, used by FX30.
+    pub large_address: u16,
+}
+
+impl Default for FontConfig {
+    fn default() -> FontConfig {
+        // Until a platform is known, SUPER-CHIP's, which has both sizes.
+        let platform = Platform::Schip;
+
+        FontConfig {
+            small: platform.font().into(),
+            large: "schip".into(),
+            small_file: None,
+            large_file: None,
+            address: platform.font_address(),
+            large_address: platform.large_font_address(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoConfig {
+    /// Size of one CHIP-8 pixel in window pixels.
+    pub scale: u32,
+    /// Screen redraws per second.
+    pub refresh_rate: u32,
+    /// Show the statistics overlay at startup.
+    pub hud: bool,
+    /// How pixels that were just turned off are shown, to reduce flicker.
+    pub persistence: Persistence,
+    /// Redraws a pixel takes to fade out with `persistence = "phosphor"`.
+    pub decay: u8,
+    /// Software upscaler for the window, screenshots and recordings.
+    pub filter: Filter,
+    /// How the display is fitted into the window.
+    pub scaling: Scaling,
+    /// Start in fullscreen.
+    pub fullscreen: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaling {
+    /// The largest whole multiple of the display size that fits, with
+    /// borders around it.
+    Integer,
+    /// As large as fits while keeping the aspect ratio.
+    Fit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Persistence {
+    /// Pixels go dark as soon as they are turned off.
+    None,
+    /// Turned-off pixels fade out over `video.decay` redraws.
+    Phosphor,
+    /// Each redraw shows the last two frames ORed together.
+    Merge,
+}
+
+impl Default for VideoConfig {
+    fn default() -> VideoConfig {
+        VideoConfig {
+            scale: 10,
+            refresh_rate: 100,
+            hud: false,
+            persistence: Persistence::None,
+            decay: 4,
+            filter: Filter::None,
+            scaling: Scaling::Integer,
+            fullscreen: false,
+        }
+    }
+}
+
+/// Display colors: a built-in palette, any of whose colors can be
+/// overridden.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Palette {
+    /// One of `palette::NAMES`.
+    pub preset: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<Rgb>,
+    /// Pixels lit in the first plane, the only one before XO-CHIP.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<Rgb>,
+    /// Pixels lit in the second XO-CHIP plane only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plane2: Option<Rgb>,
+    /// Pixels lit in both XO-CHIP planes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlap: Option<Rgb>,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            preset: "mono".into(),
+            background: None,
+            foreground: None,
+            plane2: None,
+            overlap: None,
+        }
+    }
+}
+
+impl Palette {
+    /// The color of each pixel value, from the preset and the overrides.
+    pub fn colors(&self) -> [Rgb; 4] {
+        let mut colors = palette::preset(&self.preset).expect("palette preset was validated");
+        let overrides = [self.background, self.foreground, self.plane2, self.overlap];
+
+        for (color, custom) in colors.iter_mut().zip(overrides.iter()) {
+            if let Some(custom) = *custom {
+                *color = custom;
+            }
+        }
+
+        colors
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    pub enabled: bool,
+    /// Buzzer frequency in Hz.
+    pub tone: f32,
+    /// Square wave amplitude, from 0.0 to 1.0.
+    pub volume: f32,
+    /// Minimum length of a beep in milliseconds.
+    pub duration: u64,
+    pub sample_rate: i32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            enabled: true,
+            tone: 440.0,
+            volume: 0.25,
+            duration: 120,
+            sample_rate: 24100,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlaybackConfig {
+    /// Speed multiplier while fast-forward is held, or 0 for as fast as
+    /// the host can go.
+    pub fast_forward: f64,
+    /// Speed multiplier while slow motion is held, from 0.0 to 1.0.
+    pub slow_motion: f64,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> PlaybackConfig {
+        PlaybackConfig {
+            fast_forward: 4.0,
+            slow_motion: 0.25,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewindConfig {
+    pub enabled: bool,
+    /// Frames between two snapshots. Rewinding re-runs up to this many
+    /// frames per step, while longer intervals keep more history.
+    pub interval: u32,
+    /// Memory given to the history, in KiB.
+    pub budget: usize,
+}
+
+impl Default for RewindConfig {
+    fn default() -> RewindConfig {
+        RewindConfig {
+            enabled: true,
+            interval: 10,
+            budget: 16 * 1024,
+        }
+    }
+}
+
+/// An RGB color, written as `"#rrggbb"` in the config file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Rgb, String> {
+        let hex = value.trim_start_matches('#');
+
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid color `{}`, expected \"#rrggbb\"", value));
+        }
+
+        let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).unwrap();
+
+        Ok(Rgb(channel(0), channel(2), channel(4)))
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(color: Rgb) -> String {
+        format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+    }
+}
+
+/// Host key names for the sixteen CHIP-8 keys, indexed by key value.
+///
+/// Written as a table from hex digit to SDL key name, e.g. `"A" = "Z"`.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "BTreeMap<String, String>", into = "BTreeMap<String, String>")]
+pub struct Keymap {
+    pub keys: [String; 16],
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let names = [
+            "X", "1", "2", "3",
+            "Q", "W", "E", "A",
+            "S", "D", "Z", "C",
+            "4", "R", "F", "V",
+        ];
+
+        Keymap {
+            keys: names.map(String::from),
+        }
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for Keymap {
+    type Error = String;
+
+    fn try_from(table: BTreeMap<String, String>) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+
+        for (key, name) in table {
+            let index = match u8::from_str_radix(&key, 16) {
+                Ok(index) if key.len() == 1 => index,
+                _ => return Err(format!("invalid CHIP-8 key `{}`, expected 0-F", key)),
+            };
+            keymap.keys[index as usize] = name;
+        }
+
+        Ok(keymap)
+    }
+}
+
+impl From<Keymap> for BTreeMap<String, String> {
+    fn from(keymap: Keymap) -> BTreeMap<String, String> {
+        keymap.keys.iter().enumerate()
+            .map(|(index, name)| (format!("{:X}", index), name.clone()))
+            .collect()
+    }
+}
+
+/// Host key names of the emulator's own controls.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hotkeys {
+    /// Restart the ROM from its pristine image.
+    pub reset: String,
+    /// Pause or resume emulation.
+    pub pause: String,
+    /// Run exactly one frame while paused.
+    pub frame_advance: String,
+    /// Run at `playback.fast_forward` while held.
+    pub fast_forward: String,
+    /// Run at `playback.slow_motion` while held.
+    pub slow_motion: String,
+    /// Run backwards through the rewind history while held.
+    pub rewind: String,
+    /// Show or hide the statistics overlay.
+    pub hud: String,
+    /// Switch to the next built-in palette.
+    pub palette: String,
+    /// Switch to the next upscaling filter.
+    pub filter: String,
+    /// Save the display as a PNG in the current directory.
+    pub screenshot: String,
+    /// Start or stop recording the display as a GIF in the current
+    /// directory.
+    pub record: String,
+    /// Switch between the window and fullscreen.
+    pub fullscreen: String,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Hotkeys {
+        Hotkeys {
+            reset: "F5".into(),
+            pause: "P".into(),
+            frame_advance: "N".into(),
+            fast_forward: "Tab".into(),
+            slow_motion: "Backspace".into(),
+            rewind: "Left Ctrl".into(),
+            hud: "F1".into(),
+            palette: "F2".into(),
+            filter: "F3".into(),
+            screenshot: "F12".into(),
+            record: "F9".into(),
+            fullscreen: "F11".into(),
+        }
+    }
+}
+
+/// Where the non-default layers come from.
+#[derive(Debug, Default)]
+pub struct Sources {
+    /// Config file given on the command line. When absent the default
+    /// location is used, and a missing file there is not an error.
+    pub file: Option<PathBuf>,
+    /// Config as TOML text, read in place of any file.
+    pub text: Option<String>,
+    /// Leave out the file at the default location, so that only the
+    /// built-in defaults apply unless a file or text is given.
+    pub ignore_default_file: bool,
+    /// Raw `key=value` overrides, applied last.
+    pub overrides: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(String, String),
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref path, ref err) => write!(f, "cannot read config {}: {}", path.display(), err),
+            Error::Parse(ref source, ref message) => write!(f, "invalid config in {}: {}", source, message.trim_end()),
+            Error::Invalid(ref message) => write!(f, "invalid config: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl Config {
+    /// Resolves the effective configuration, for a ROM if one is loaded.
+    pub fn load(sources: &Sources, rom: Option<&Rom>) -> Result<Config, Error> {
+        let config = Config::load_layers(sources, rom)?;
+
+        // Detection assumed a load address, and the layers may move it.
+        match rom {
+            Some(rom) if rom.detection().is_some_and(|detection| detection.load_address != config.cpu.load_address) => {
+                Config::load_layers(sources, Some(&rom.with_load_address(config.cpu.load_address)))
+            }
+            _ => Ok(config),
+        }
+    }
+
+    fn load_layers(sources: &Sources, rom: Option<&Rom>) -> Result<Config, Error> {
+        let layers = layers(sources, rom)?;
+
+        // The platform can be picked by any layer, and its defaults sit
+        // below all of them.
+        let mut config = resolve(None, &layers)?;
+        if let Some(platform) = config.cpu.platform {
+            config = resolve(Some(platform), &layers)?;
+        }
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// The effective settings as a TOML document, for `chip8 config dump`.
+    pub fn dump(&self) -> String {
+        toml::to_string_pretty(self).expect("config serializes to TOML")
+    }
+
+    /// Bytes of memory of the selected platform.
+    pub fn memory_size(&self) -> usize {
+        self.cpu.platform.map_or(0x1000, Platform::memory_size)
+    }
+
+    /// Address execution starts at.
+    pub fn entry_point(&self) -> u16 {
+        self.cpu.entry.unwrap_or(self.cpu.load_address)
+    }
+
+    /// The memory layout, with any custom font files read in.
+    pub fn layout(&self) -> Result<Layout, Error> {
+        let font = match self.font.small_file {
+            Some(ref path) => read_font(path, &[font::SMALL_GLYPH * 16])?,
+            None => font::small(&self.font.small).unwrap().to_vec(),
+        };
+        let large_font = match self.font.large_file {
+            Some(ref path) => read_font(path, &[font::LARGE_GLYPH * 10, font::LARGE_GLYPH * 16])?,
+            None => font::large(&self.font.large).unwrap().to_vec(),
+        };
+
+        Ok(Layout {
+            memory_size: self.memory_size(),
+            load_address: self.cpu.load_address,
+            font,
+            font_address: self.font.address,
+            large_font,
+            large_font_address: self.font.large_address,
+        })
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.cpu.speed == 0 {
+            return Err(Error::Invalid("cpu.speed must be greater than zero".into()));
+        }
+        if self.cpu.load_address as usize >= self.memory_size() {
+            return Err(Error::Invalid(format!("cpu.load_address {:#x} is outside memory", self.cpu.load_address)));
+        }
+        if self.entry_point() as usize + 1 >= self.memory_size() {
+            return Err(Error::Invalid(format!("cpu.entry {:#x} is outside memory", self.entry_point())));
+        }
+        if font::small(&self.font.small).is_none() {
+            return Err(Error::Invalid(format!(
+                "font.small \"{}\" is not one of {}", self.font.small, font::SMALL_FONTS.join(", "),
+            )));
+        }
+        if font::large(&self.font.large).is_none() {
+            return Err(Error::Invalid(format!(
+                "font.large \"{}\" is not one of {}", self.font.large, font::LARGE_FONTS.join(", "),
+            )));
+        }
+        if palette::preset(&self.palette.preset).is_none() {
+            return Err(Error::Invalid(format!(
+                "palette.preset \"{}\" is not one of {}", self.palette.preset, palette::NAMES.join(", "),
+            )));
+        }
+        if self.video.scale == 0 {
+            return Err(Error::Invalid("video.scale must be greater than zero".into()));
+        }
+        if self.video.decay == 0 {
+            return Err(Error::Invalid("video.decay must be greater than zero".into()));
+        }
+        if self.video.refresh_rate == 0 {
+            return Err(Error::Invalid("video.refresh_rate must be greater than zero".into()));
+        }
+        if !(0.0..=1.0).contains(&self.audio.volume) {
+            return Err(Error::Invalid("audio.volume must be between 0.0 and 1.0".into()));
+        }
+        if self.audio.tone <= 0.0 || self.audio.sample_rate <= 0 {
+            return Err(Error::Invalid("audio.tone and audio.sample_rate must be positive".into()));
+        }
+        if !(self.playback.fast_forward == 0.0 || self.playback.fast_forward >= 1.0) {
+            return Err(Error::Invalid("playback.fast_forward must be at least 1.0, or 0 for uncapped".into()));
+        }
+        if self.rewind.interval == 0 {
+            return Err(Error::Invalid("rewind.interval must be greater than zero".into()));
+        }
+        if !(self.playback.slow_motion > 0.0 && self.playback.slow_motion <= 1.0) {
+            return Err(Error::Invalid("playback.slow_motion must be above 0.0 and at most 1.0".into()));
+        }
+        Ok(())
+    }
+}
+
+/// `$CHIP8_CONFIG`, or `chip8/config.toml` under the XDG config directory.
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CHIP8_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// `chip8` under `$XDG_CONFIG_HOME`, falling back to `~/.config`.
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("chip8"))
+}
+
+/// Size and position of the window, restored on the next run.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowState {
+    /// `window.toml` next to the default config file.
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("window.toml"))
+    }
+
+    /// The saved state, if there is a readable one.
+    pub fn load() -> Option<WindowState> {
+        let text = fs::read_to_string(WindowState::path()?).ok()?;
+        toml::from_str(&text).ok()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = WindowState::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, toml::to_string(self).expect("window state serializes to TOML"))
+    }
+}
+
+fn layers(sources: &Sources, rom: Option<&Rom>) -> Result<Vec<(String, Table)>, Error> {
+    let mut layers = Vec::new();
+
+    if let Some(rom) = rom {
+        if let Some(info) = rom.info() {
+            layers.push((format!("{} [{}]", info.source, rom.hash()), info.settings()));
+        }
+    }
+
+    if let Some((source, mut file)) = config_file(sources)? {
+        let roms = match file.remove("rom") {
+            // Hashes are matched like in the ROM database, whatever their case.
+            Some(Value::Table(roms)) => roms.into_iter().map(|(hash, section)| (hash.to_lowercase(), section)).collect(),
+            Some(_) => return Err(Error::Parse(source, "`rom` must be a table of [rom.<sha1>] sections".into())),
+            None => Table::new(),
+        };
+
+        layers.push((source.clone(), file));
+
+        if let Some(rom) = rom {
+            match roms.get(rom.hash()) {
+                Some(Value::Table(section)) => {
+                    layers.push((format!("{} [rom.{}]", source, rom.hash()), section.clone()));
+                }
+                Some(_) => return Err(Error::Parse(source, "[rom.<sha1>] entries must be tables".into())),
+                None => {}
+            }
+        }
+    }
+
+    for assignment in &sources.overrides {
+        let mut layer = Table::new();
+        apply_override(&mut layer, assignment)?;
+        layers.push((format!("--set {}", assignment), layer));
+    }
+
+    Ok(layers)
+}
+
+/// The config file or text of `sources`, and where it came from.
+fn config_file(sources: &Sources) -> Result<Option<(String, Table)>, Error> {
+    if let Some(ref text) = sources.text {
+        let source = "config text".to_string();
+        return match text.parse::<Table>() {
+            Ok(table) => Ok(Some((source, table))),
+            Err(err) => Err(Error::Parse(source, err.to_string())),
+        };
+    }
+
+    let path = match sources.file {
+        Some(ref path) => path.clone(),
+        None if sources.ignore_default_file => return Ok(None),
+        None => match default_path() {
+            Some(path) => path,
+            None => return Ok(None),
+        },
+    };
+
+    match read_table(&path) {
+        Ok(table) => Ok(Some((path.display().to_string(), table))),
+        Err(Error::Io(_, ref err)) if err.kind() == io::ErrorKind::NotFound && sources.file.is_none() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn resolve(platform: Option<Platform>, layers: &[(String, Table)]) -> Result<Config, Error> {
+    let mut defaults = Config::default();
+    if let Some(platform) = platform {
+        defaults.quirks = platform.quirks();
+        defaults.cpu.speed = platform.speed();
+        defaults.cpu.load_address = platform.load_address();
+        defaults.font.small = platform.font().into();
+        defaults.font.address = platform.font_address();
+        defaults.font.large_address = platform.large_font_address();
+    }
+
+    let mut table = match Value::try_from(defaults) {
+        Ok(Value::Table(table)) => table,
+        _ => unreachable!("default config is a table"),
+    };
+
+    for (source, layer) in layers {
+        merge(&mut table, layer.clone());
+        check(&table, source)?;
+    }
+
+    check(&table, "defaults")
+}
+
+fn read_font(path: &Path, sizes: &[usize]) -> Result<Vec<u8>, Error> {
+    let glyphs = fs::read(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+
+    if !sizes.contains(&glyphs.len()) {
+        let sizes: Vec<String> = sizes.iter().map(usize::to_string).collect();
+        return Err(Error::Invalid(format!(
+            "font {} is {} bytes, expected {}", path.display(), glyphs.len(), sizes.join(" or "),
+        )));
+    }
+
+    Ok(glyphs)
+}
+
+fn read_table(path: &Path) -> Result<Table, Error> {
+    let text = fs::read_to_string(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+
+    text.parse::<Table>().map_err(|err| Error::Parse(path.display().to_string(), err.to_string()))
+}
+
+fn check(table: &Table, source: &str) -> Result<Config, Error> {
+    Config::deserialize(Value::Table(table.clone()))
+        .map_err(|err| Error::Parse(source.to_string(), err.to_string()))
+}
+
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(&mut Value::Table(ref mut inner)), Value::Table(value)) => merge(inner, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn apply_override(table: &mut Table, assignment: &str) -> Result<(), Error> {
+    let source = format!("--set {}", assignment);
+
+    let (path, raw) = match assignment.find('=') {
+        Some(at) => (assignment[..at].trim(), assignment[at + 1..].trim()),
+        None => return Err(Error::Parse(source, "expected key=value".into())),
+    };
+
+    // Bare words such as `#ff0000` or `Space` are taken as strings.
+    let value = format!("value = {}", raw).parse::<Table>().ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()));
+
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop().unwrap();
+
+    let mut target = table;
+    for key in keys {
+        let entry = target.entry(key.to_string()).or_insert_with(|| Value::Table(Table::new()));
+        target = match *entry {
+            Value::Table(ref mut inner) => inner,
+            _ => return Err(Error::Parse(source, format!("`{}` is not a section", key))),
+        };
+    }
+    target.insert(last.to_string(), value);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(text: &str, rom: Option<&Rom>) -> Result<Config, Error> {
+        load_with(text, &[], rom)
+    }
+
+    fn load_with(text: &str, overrides: &[&str], rom: Option<&Rom>) -> Result<Config, Error> {
+        let sources = Sources {
+            text: Some(text.into()),
+            ignore_default_file: true,
+            overrides: overrides.iter().map(|assignment| assignment.to_string()).collect(),
+            ..Sources::default()
+        };
+        Config::load(&sources, rom)
+    }
+
+    #[test]
+    fn starts_from_the_built_in_defaults() {
+        let sources = Sources { ignore_default_file: true, ..Sources::default() };
+
+        assert_eq!(Config::load(&sources, None).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn puts_the_platform_defaults_under_every_layer() {
+        let config = load("[cpu]\nplatform = \"schip\"\n", None).unwrap();
+        assert_eq!(config.cpu.speed, Platform::Schip.speed());
+        assert_eq!(config.quirks, Platform::Schip.quirks());
+        assert_eq!(config.font.small, "schip");
+
+        // The platform is picked last of all, yet the speed set before it
+        // still wins.
+        let config = load_with("[cpu]\nspeed = 42\n", &["cpu.platform=vip"], None).unwrap();
+        assert_eq!(config.cpu.speed, 42);
+        assert_eq!(config.quirks, Platform::Vip.quirks());
+    }
+
+    #[test]
+    fn layers_the_rom_the_file_its_rom_section_and_overrides() {
+        // Only SUPER-CHIP has DXY0, so detection picks it for the ROM layer.
+        let rom = Rom::from_bytes(&[0x00, 0xFF, 0xD0, 0x10], None).unwrap();
+        assert_eq!(load("", Some(&rom)).unwrap().cpu.platform, Some(Platform::Schip));
+        assert_eq!(load("", Some(&rom)).unwrap().cpu.speed, 1800);
+
+        let file = "[cpu]\nspeed = 900\n";
+        assert_eq!(load(file, Some(&rom)).unwrap().cpu.speed, 900);
+
+        let file = format!("{}[rom.{}.cpu]\nspeed = 1000\n", file, rom.hash());
+        assert_eq!(load(&file, Some(&rom)).unwrap().cpu.speed, 1000);
+        // Sections for other ROMs are left out.
+        assert_eq!(load(&file, None).unwrap().cpu.speed, 900);
+
+        let config = load_with(&file, &["cpu.speed=1100", "cpu.speed = 1200"], Some(&rom)).unwrap();
+        assert_eq!(config.cpu.speed, 1200);
+        assert_eq!(config.cpu.platform, Some(Platform::Schip));
+    }
+
+    #[test]
+    fn parses_override_values_as_toml_or_bare_strings() {
+        let config = load_with("", &["quirks.shift=true", "palette.foreground=#ff0000", "keymap.5=Space"], None).unwrap();
+
+        assert!(config.quirks.shift);
+        assert_eq!(config.palette.foreground, Some(Rgb(0xff, 0x00, 0x00)));
+        assert_eq!(config.keymap.keys[5], "Space");
+    }
+
+    #[test]
+    fn names_the_layer_that_broke() {
+        let err = load_with("", &["cpu.speed"], None).unwrap_err();
+        assert_eq!(err.to_string(), "invalid config in --set cpu.speed: expected key=value");
+
+        let err = load_with("", &["cpu.speed.max=1"], None).unwrap_err();
+        assert_eq!(err.to_string(), "invalid config in --set cpu.speed.max=1: invalid type: map, expected u32\nin `cpu.speed`");
+
+        let err = load_with("", &["cpu.speed=fast"], None).unwrap_err();
+        assert!(err.to_string().starts_with("invalid config in --set cpu.speed=fast: "), "{}", err);
+
+        let err = load("[cpu]\nsped = 1\n", None).unwrap_err();
+        assert!(err.to_string().starts_with("invalid config in config text: unknown field `sped`"), "{}", err);
+    }
+
+    #[test]
+    fn requires_only_a_config_file_given_by_name() {
+        let missing = env::temp_dir().join(format!("chip8-missing-{}.toml", std::process::id()));
+        let sources = Sources { file: Some(missing.clone()), ..Sources::default() };
+
+        match Config::load(&sources, None) {
+            Err(Error::Io(path, _)) => assert_eq!(path, missing),
+            other => panic!("expected an I/O error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn detects_again_at_the_configured_load_address() {
+        // 0x600: LD I, 0x700; 0x602: LD V2, [I]; 0x604: JP 0x602
+        let rom = Rom::from_bytes(&[0xA7, 0x00, 0xF2, 0x65, 0x16, 0x02], None).unwrap();
+        // SUPER-CHIP leaves I alone, unless the program relies on it.
+        assert!(!load("[cpu]\nplatform = \"schip\"\n", Some(&rom)).unwrap().quirks.load_store);
+
+        let config = load("[cpu]\nplatform = \"schip\"\nload_address = 0x600\n", Some(&rom)).unwrap();
+        assert!(config.quirks.load_store);
+        assert!(rom.with_load_address(0x600).detection().unwrap().load_store);
+    }
+
+    #[test]
+    fn matches_rom_sections_whatever_the_case_of_the_hash() {
+        let rom = Rom::from_bytes(&[0x12, 0x00], None).unwrap();
+        let text = format!("[rom.{}.cpu]\nspeed = 123\n", rom.hash().to_uppercase());
+
+        assert_eq!(load(&text, Some(&rom)).unwrap().cpu.speed, 123);
+    }
+
+    #[test]
+    fn takes_font_addresses_from_the_platform() {
+        let config = load("[cpu]\nplatform = \"vip\"\n", None).unwrap();
+        assert_eq!((config.font.address, config.font.large_address), (0x050, 0x0a0));
+
+        let config = load("[cpu]\nplatform = \"eti660\"\n", None).unwrap();
+        assert_eq!((config.font.address, config.font.large_address), (0x050, 0x0a0));
+
+        let config = load("[cpu]\nplatform = \"schip\"\n", None).unwrap();
+        assert_eq!((config.font.address, config.font.large_address), (0x000, 0x050));
+
+        let config = load("[cpu]\nplatform = \"eti660\"\n[font]\naddress = 0x100\n", None).unwrap();
+        assert_eq!((config.font.address, config.font.large_address), (0x100, 0x0a0));
+    }
+}
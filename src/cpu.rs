@@ -1,12 +1,58 @@
-use rand::Rng;
-use rand::thread_rng;
-
 use bus::Bus;
+use display::Framebuffer;
+use font;
+use instruction::Instruction;
+use memory::{Ram, Storage};
+use quirks::Quirks;
+use random::{Random, Rng};
+#[cfg(feature = "std")]
+use random;
+#[cfg(feature = "std")]
+use snapshot::{self, Snapshot};
+
+use core::error;
+use core::fmt;
+use core::mem;
+
+/// Why the CPU stopped: something the program did that no platform
+/// defines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+  UnknownInstruction(u16),
+  /// A load or store to a region whose access policy is `none`.
+  Inaccessible(&'static str, u16),
+  /// A load or store past the end of memory.
+  Unmapped(u16),
+  /// A call with all 16 stack levels in use.
+  StackOverflow,
+  /// A return with nothing on the stack.
+  StackUnderflow,
+}
+
+impl fmt::Display for Fault {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Fault::UnknownInstruction(instruction) => write!(f, "unknown instruction {:04X}", instruction),
+      Fault::Inaccessible(region, address) => write!(f, "access to the inaccessible {} area at {:#05x}", region, address),
+      Fault::Unmapped(address) => write!(f, "access past the end of memory at {:#05x}", address),
+      Fault::StackOverflow => write!(f, "stack overflow"),
+      Fault::StackUnderflow => write!(f, "return with an empty stack"),
+    }
+  }
+}
 
-use std::time::{Instant, Duration};
+impl error::Error for Fault {}
 
-pub struct Cpu {
-  bus: Bus,
+/// XO-CHIP's pitch until FX3A sets one.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// The interpreter, over a bus with RAM in `S` and CXNN numbers from `R`.
+pub struct Cpu<S = Ram, R = Random> {
+  bus: Bus<S>,
+
+  quirks: Quirks,
+
+  entry: u16,
 
   pc: u16,
   sp: u8,
@@ -17,7 +63,10 @@ pub struct Cpu {
 
   v: [u8; 16],
 
-  pub video: [[u8; 64]; 32],
+  display: Framebuffer,
+
+  /// Set whenever the display changes, until the frontend takes it.
+  display_dirty: bool,
 
   key: [bool; 16],
 
@@ -25,17 +74,98 @@ pub struct Cpu {
   
   pub sound_timer: u8,
 
-  delay_duration: Instant,
-
   pub make_sound: bool,
+
+  /// XO-CHIP's 128-bit audio pattern, played instead of the plain tone
+  /// once F002 has loaded one.
+  pattern: Option<[u8; 16]>,
+
+  /// XO-CHIP's playback rate of `pattern`, 64 for 4000 bits a second.
+  pitch: u8,
+
+  rng: R,
 }
 
+#[cfg(feature = "std")]
 impl Cpu {
-  pub fn new(bus: Bus) -> Cpu {
+  pub fn new(bus: Bus, quirks: Quirks, entry: u16) -> Cpu {
+    Cpu::with_rng(bus, quirks, entry, Random::new(random::entropy()))
+  }
+
+  /// Restarts the program from its pristine image, as on power-up.
+  pub fn reset(&mut self) {
+    self.bus.reset();
+    self.restart();
+  }
+}
+
+impl<S: Storage> Cpu<S, Random> {
+  /// Reseeds the CXNN random number generator, for reproducible runs.
+  pub fn seed(&mut self, seed: u64) {
+    self.rng = Random::new(seed);
+  }
+}
+
+#[cfg(feature = "std")]
+impl<S: Storage> Cpu<S, Random> {
+  pub fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      pc: self.pc,
+      sp: self.sp,
+      stack: self.stack,
+      i: self.i as u32,
+      v: self.v,
+      delay_timer: self.delay_timer,
+      sound_timer: self.sound_timer,
+      keys: self.keys(),
+      pattern: self.pattern,
+      pitch: self.pitch,
+      rng: self.rng.state(),
+      display: self.display.clone(),
+      memory: self.bus.memory.as_slice().to_vec(),
+    }
+  }
+
+  /// Puts the machine back in the state of `snapshot`.
+  pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), snapshot::Error> {
+    if snapshot.memory.len() != self.bus.memory.size() {
+      return Err(snapshot::Error::MemorySize { expected: self.bus.memory.size(), found: snapshot.memory.len() });
+    }
+
+    self.bus.memory.copy_from(&snapshot.memory);
+    self.bus.refreeze();
+
+    self.pc = snapshot.pc;
+    self.sp = snapshot.sp;
+    self.stack = snapshot.stack;
+    self.i = snapshot.i as usize;
+    self.v = snapshot.v;
+    self.delay_timer = snapshot.delay_timer;
+    self.sound_timer = snapshot.sound_timer;
+    self.set_keys(snapshot.keys);
+    self.pattern = snapshot.pattern;
+    self.pitch = snapshot.pitch;
+    self.rng = Random::new(snapshot.rng);
+    self.display = snapshot.display.clone();
+    self.display_dirty = true;
+    self.make_sound = false;
+
+    Ok(())
+  }
+}
+
+impl<S: Storage, R: Rng> Cpu<S, R> {
+  /// A CPU drawing CXNN numbers from `rng`. Needs no heap, so with an
+  /// array for RAM it runs without `std`.
+  pub fn with_rng(bus: Bus<S>, quirks: Quirks, entry: u16, rng: R) -> Cpu<S, R> {
     Cpu {
       bus,
 
-      pc: 0x200,
+      quirks,
+
+      entry,
+
+      pc: entry,
       sp: 0,
 
       stack: [0; 16],
@@ -44,7 +174,9 @@ impl Cpu {
 
       v: [0; 16],
 
-      video: [[0; 64]; 32],
+      display: Framebuffer::new(),
+
+      display_dirty: true,
 
       key: [false; 16],
 
@@ -52,327 +184,636 @@ impl Cpu {
       
       sound_timer: 0,
 
-      delay_duration: Instant::now(),
-
       make_sound: false,
+
+      pattern: None,
+
+      pitch: DEFAULT_PITCH,
+
+      rng,
     }
   }
 
-  pub fn run_next_instruction(&mut self) {
-    let lhs = self.bus.load(self.pc) as u16;
-    let rhs = self.bus.load(self.pc + 1) as u16;
+  pub fn set_quirks(&mut self, quirks: Quirks) {
+    self.quirks = quirks;
+  }
 
-    let instruction = ((lhs << 8) | rhs) as u16;
+  pub fn bus(&self) -> &Bus<S> {
+    &self.bus
+  }
 
+  pub fn bus_mut(&mut self) -> &mut Bus<S> {
+    &mut self.bus
+  }
 
-    self.pc = self.pc.wrapping_add(2);
+  pub fn display(&self) -> &Framebuffer {
+    &self.display
+  }
 
-    self.decode(instruction); 
-    
+  /// Whether the display changed since the last call.
+  pub fn take_display_dirty(&mut self) -> bool {
+    mem::replace(&mut self.display_dirty, false)
   }
 
-  fn set_v(&mut self, addr: u8, value: u8) {
-    self.v[addr as usize] = value;
+  pub fn pc(&self) -> u16 {
+    self.pc
   }
 
-  fn get_v(&self, addr: u8) -> u8 {
-    self.v[addr as usize]
+  pub fn i(&self) -> usize {
+    self.i
   }
 
-  pub fn decrease_timers(&mut self, now: Instant) {
-      if now - self.delay_duration > Duration::from_millis(16) {
-          if self.delay_timer > 0 {
-              self.delay_timer -= 1;
-          }
+  pub fn sp(&self) -> u8 {
+    self.sp
+  }
 
-          self.make_sound = false;
-          if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                self.make_sound = true;
-            }
-              self.sound_timer -= 1;
-          }
-          self.delay_duration = Instant::now();
-      }
+  pub fn stack(&self) -> &[u16; 16] {
+    &self.stack
   }
 
-  fn decode(&mut self, instruction: u16) {
-    let opcode = instruction >> 12;
+  pub fn v(&self) -> &[u8; 16] {
+    &self.v
+  }
+
+  pub fn delay_timer(&self) -> u8 {
+    self.delay_timer
+  }
+
+  /// The audio pattern F002 loaded, if any, for the buzzer to play at
+  /// `pitch` while the sound timer runs.
+  pub fn audio_pattern(&self) -> Option<&[u8; 16]> {
+    self.pattern.as_ref()
+  }
+
+  pub fn pitch(&self) -> u8 {
+    self.pitch
+  }
+
+  /// Pressed keys, one bit per key.
+  pub fn keys(&self) -> u16 {
+    self.key.iter().enumerate().fold(0, |mask, (index, &pressed)| mask | (pressed as u16) << index)
+  }
+
+  pub fn set_keys(&mut self, mask: u16) {
+    for (index, pressed) in self.key.iter_mut().enumerate() {
+      *pressed = mask & 1 << index != 0;
+    }
+  }
 
-    let nnn = instruction & 0x0fff;
-    
-    let nn = (instruction & 0x00ff) as u8;
+  /// Puts the registers, display and timers back as on power-up,
+  /// leaving memory as it is.
+  pub fn restart(&mut self) {
+    self.pc = self.entry;
+    self.sp = 0;
+    self.stack = [0; 16];
+    self.i = 0;
+    self.v = [0; 16];
+    self.display = Framebuffer::new();
+    self.display_dirty = true;
+    self.key = [false; 16];
+    self.delay_timer = 0;
+    self.sound_timer = 0;
+    self.make_sound = false;
+    self.pattern = None;
+    self.pitch = DEFAULT_PITCH;
+  }
+
+  /// Runs one instruction. On a fault the program counter stays on the
+  /// faulting instruction, though it may have changed memory or registers
+  /// before faulting.
+  pub fn run_next_instruction(&mut self) -> Result<(), Fault> {
+    let pc = self.pc;
+
+    let result = self.fetch().and_then(|instruction| {
+      self.pc = self.pc.wrapping_add(2);
+      self.execute(instruction)
+    });
+    if result.is_err() {
+      self.pc = pc;
+    }
 
-    let n = (instruction & 0x000f) as u8;
+    result
+  }
+
+  fn fetch(&self) -> Result<u16, Fault> {
+    let lhs = self.bus.load(self.pc)? as u16;
+    let rhs = self.bus.load(self.pc.wrapping_add(1))? as u16;
 
-    let x = ((instruction & 0x0f00) >> 8) as u8;
+    Ok((lhs << 8) | rhs)
+  }
 
-    let y = ((instruction & 0x00f0) >> 4) as u8;
+  /// Steps over the next instruction, all four bytes of it for F000 NNNN.
+  fn skip(&mut self) {
+    let width = if self.fetch() == Ok(0xf000) { 4 } else { 2 };
+    self.pc = self.pc.wrapping_add(width);
+  }
 
-    //println!("{:#06x}", instruction);
+  fn set_v(&mut self, addr: u8, value: u8) {
+    self.v[addr as usize] = value;
+  }
 
-    match opcode {
-      0x0 => {
-        match nn {
-          0xe0 => {
-            self.video = [[0; 64]; 32];
-          }
-          0xee => {
-            self.pc = self.stack[self.sp as usize];
-            self.sp = self.sp.wrapping_sub(1);
-          }
-          _ => panic!("Unknown instruction {:#06x}", instruction),
+  fn get_v(&self, addr: u8) -> u8 {
+    self.v[addr as usize]
+  }
+
+  fn reset_vf(&mut self) {
+    if self.quirks.vf_reset {
+      self.set_v(0xf, 0);
+    }
+  }
+
+  /// Counts the delay and sound timers down by one 60 Hz tick. The
+  /// caller paces the ticks, from its frame loop or a hardware timer.
+  pub fn tick_timers(&mut self) {
+      if self.delay_timer > 0 {
+          self.delay_timer -= 1;
+      }
+
+      self.make_sound = false;
+      if self.sound_timer > 0 {
+        if self.sound_timer == 1 {
+            self.make_sound = true;
+        }
+          self.sound_timer -= 1;
+      }
+  }
+
+  fn execute(&mut self, opcode: u16) -> Result<(), Fault> {
+    let x = ((opcode & 0x0f00) >> 8) as u8;
+    let y = ((opcode & 0x00f0) >> 4) as u8;
+
+    let instruction = match Instruction::decode(opcode) {
+      Some(instruction) => instruction,
+      // The low nibble of 5XYN and 9XYN is not looked at, as on the VIP.
+      None if opcode & 0xf000 == 0x5000 => Instruction::SkipEqualRegister(x, y),
+      None if opcode & 0xf000 == 0x9000 => Instruction::SkipNotEqualRegister(x, y),
+      None => return Err(Fault::UnknownInstruction(opcode)),
+    };
+
+    match instruction {
+      Instruction::Clear => {
+        self.display.clear();
+        self.display_dirty = true;
+      }
+      Instruction::Return => {
+        if self.sp == 0 {
+          return Err(Fault::StackUnderflow);
         }
+        self.pc = self.stack[self.sp as usize];
+        self.sp -= 1;
       }
-      0x1 => {
+      Instruction::ScrollDown(n) => {
+        self.display.scroll_down(n as usize);
+        self.display_dirty = true;
+      }
+      Instruction::ScrollUp(n) => {
+        self.display.scroll_up(n as usize);
+        self.display_dirty = true;
+      }
+      Instruction::ScrollRight => {
+        self.display.scroll_right();
+        self.display_dirty = true;
+      }
+      Instruction::ScrollLeft => {
+        self.display.scroll_left();
+        self.display_dirty = true;
+      }
+      Instruction::LowRes => {
+        self.display.set_hires(false);
+        self.display_dirty = true;
+      }
+      Instruction::HighRes => {
+        self.display.set_hires(true);
+        self.display_dirty = true;
+      }
+      Instruction::Jump(nnn) => {
         self.pc = nnn;
       }
-      0x2 => {
-        self.sp = self.sp.wrapping_add(1);
+      Instruction::Call(nnn) => {
+        if self.sp as usize + 1 >= self.stack.len() {
+          return Err(Fault::StackOverflow);
+        }
+        self.sp += 1;
         self.stack[self.sp as usize] = self.pc;
         self.pc = nnn;
       }
-      0x3 => {
+      Instruction::SkipEqual(x, nn) => {
         let vx = self.get_v(x);
         if vx == nn {
-          self.pc = self.pc.wrapping_add(2);
+          self.skip();
         }
       }
-      0x4 => {
+      Instruction::SkipNotEqual(x, nn) => {
         let vx = self.get_v(x);
         if vx != nn {
-          self.pc = self.pc.wrapping_add(2);
+          self.skip();
         }
       }
-      0x5 => {
+      Instruction::SkipEqualRegister(x, y) => {
         let vx = self.get_v(x);
         let vy = self.get_v(y);
         if vx == vy {
-          self.pc = self.pc.wrapping_add(2);
+          self.skip();
         }
       }
-      0x6 => {
+      Instruction::Set(x, nn) => {
         self.set_v(x, nn);
       }
-      0x7 => {
+      Instruction::Add(x, nn) => {
         let old_v = self.get_v(x);
         self.set_v(x, old_v.wrapping_add(nn));
       }
-      0x8 => {
-        match n {
-          0x0 => {
-            let vy = self.get_v(y);
-            self.set_v(x, vy);
-          }
-          0x1 => {
-            let vx = self.get_v(x);
-            let vy = self.get_v(y);
-            self.set_v(x, vx | vy);
-          }
-          0x2 => {
-            let vx = self.get_v(x);
-            let vy = self.get_v(y);
-            self.set_v(x, vx & vy);
-          }
-          0x3 => {
-            let vx = self.get_v(x);
-            let vy = self.get_v(y);
-            self.set_v(x, vx ^ vy);
-          } 
-          0x4 => {
-            let vx = self.get_v(x);
-            let vy = self.get_v(y);
+      Instruction::Move(x, y) => {
+        let vy = self.get_v(y);
+        self.set_v(x, vy);
+      }
+      Instruction::Or(x, y) => {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(x, vx | vy);
+        self.reset_vf();
+      }
+      Instruction::And(x, y) => {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(x, vx & vy);
+        self.reset_vf();
+      }
+      Instruction::Xor(x, y) => {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(x, vx ^ vy);
+        self.reset_vf();
+      }
+      Instruction::AddRegister(x, y) => {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
 
-            let old_vx = vx;
+        let old_vx = vx;
 
-            self.set_v(x, vx.wrapping_add(vy));
+        self.set_v(x, vx.wrapping_add(vy));
 
-            let new_vx = self.get_v(x);
+        let new_vx = self.get_v(x);
 
-            if new_vx < old_vx {
-              self.set_v(0xf, 1);
-            } else {
-              self.set_v(0xf, 0);
-            }
-          }
+        if new_vx < old_vx {
+          self.set_v(0xf, 1);
+        } else {
+          self.set_v(0xf, 0);
+        }
+      }
 
-          0x5 => {
-            let vx = self.get_v(x);
-            let vy = self.get_v(y);
+      Instruction::Sub(x, y) => {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
 
-            let old_vx = vx;
+        let old_vx = vx;
 
-            self.set_v(x, vx.wrapping_sub(vy));
+        self.set_v(x, vx.wrapping_sub(vy));
 
-            let new_vx = self.get_v(x);
+        let new_vx = self.get_v(x);
 
-            if new_vx > old_vx {
-              self.set_v(0xf, 1);
-            } else {
-              self.set_v(0xf, 0);
-            }
-          }
+        if new_vx > old_vx {
+          self.set_v(0xf, 1);
+        } else {
+          self.set_v(0xf, 0);
+        }
+      }
 
-          0x6 => {
-            let vy = self.get_v(y);
+      Instruction::ShiftRight(x, y) => {
+        let value = if self.quirks.shift {
+          self.get_v(x)
+        } else {
+          let vy = self.get_v(y);
+          self.set_v(y, vy >> 1);
+          vy
+        };
 
-            self.set_v(y, vy >> 1);
-            self.set_v(x, vy >> 1);
+        self.set_v(x, value >> 1);
 
-            self.set_v(0xf, vy & 1);
-          }
+        self.set_v(0xf, value & 1);
+      }
 
-          0x7 => {
-            let vx = self.get_v(x);
-            let vy = self.get_v(y);
+      Instruction::SubReverse(x, y) => {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
 
-            let old_vy = vy;
+        let old_vy = vy;
 
-            self.set_v(x, vy.wrapping_sub(vx));
+        self.set_v(x, vy.wrapping_sub(vx));
 
-            let new_vy = self.get_v(y);
+        let new_vy = self.get_v(y);
 
-            if new_vy > old_vy {
-              self.set_v(0xf, 1);
-            } else {
-              self.set_v(0xf, 0);
-            }
-          }
+        if new_vy > old_vy {
+          self.set_v(0xf, 1);
+        } else {
+          self.set_v(0xf, 0);
+        }
+      }
 
-          0xe => {
-            let vy = self.get_v(y);
+      Instruction::ShiftLeft(x, y) => {
+        let value = if self.quirks.shift {
+          self.get_v(x)
+        } else {
+          let vy = self.get_v(y);
+          self.set_v(y, vy << 1);
+          vy
+        };
 
-            self.set_v(y, vy << 1);
-            self.set_v(x, vy << 1);
+        self.set_v(x, value << 1);
 
-            self.set_v(0xf, vy >> 7);
-          }
-          _ => panic!("Unknown instruction {:#06x}", instruction),
-        }
+        self.set_v(0xf, value >> 7);
       }
-      0x9 => {
+      Instruction::SkipNotEqualRegister(x, y) => {
         let vx = self.get_v(x);
         let vy = self.get_v(y);
         if vx != vy {
-          self.pc = self.pc.wrapping_add(2);
+          self.skip();
         }
       }
-      0xa => {
+      Instruction::SetI(nnn) => {
         self.i = nnn as usize;
       }
-      0xb => {
-        let value = self.get_v(0);
+      Instruction::JumpOffset(nnn) => {
+        let x = (nnn >> 8) as u8;
+        let value = if self.quirks.jump { self.get_v(x) } else { self.get_v(0) };
         self.pc = nnn.wrapping_add(value as u16);
       }
-      0xc => {
-        let rand = thread_rng().gen::<u32>();
-        self.set_v(x, (rand & nn as u32) as u8);
-      }
-      0xd => {
-        self.draw(x, y, n);
-      }
-      0xe => {
-        match nn {
-          0x9e => {
-            let key = self.key[self.get_v(x) as usize];
-            
-            if key {
-              self.pc = self.pc.wrapping_add(2);
-            }
-          }
-          0xa1 => {
-            let key = self.key[self.get_v(x) as usize];
-            
-            if !key {
-              self.pc = self.pc.wrapping_add(2);
-            }
-          }
-          _ => panic!("Unknown instruction {:#06x}", instruction),
+      Instruction::Random(x, nn) => {
+        let rand = self.rng.next_u8();
+        self.set_v(x, rand & nn);
+      }
+      Instruction::Draw(x, y, n) => {
+        self.draw(x, y, n)?;
+      }
+      Instruction::SkipKey(x) => {
+        // Only the low nibble selects the key, as on the VIP.
+        let key = self.key[(self.get_v(x) & 0xf) as usize];
+        
+        if key {
+          self.skip();
+        }
+      }
+      Instruction::SkipNotKey(x) => {
+        let key = self.key[(self.get_v(x) & 0xf) as usize];
+        
+        if !key {
+          self.skip();
+        }
+      }
+      Instruction::Planes(n) => {
+        self.display.select(n);
+      }
+      Instruction::GetDelay(x) => {
+        let value = self.delay_timer;
+        self.set_v(x, value);
+      }
+      Instruction::WaitKey(x) => {
+        let mut pressed = false;
+
+        for index in 0x0..0xf {
+          if self.key[index] {
+            self.set_v(x, index as u8);
+            pressed = true;
+          } 
+        }
+
+        if !pressed {
+          // Blocking Operation. All instruction halted until next key event.
+          self.pc = self.pc.wrapping_sub(2);
+        }
+      }
+      Instruction::SetDelay(x) => {
+        let value = self.get_v(x);
+        self.delay_timer = value;
+      }
+      Instruction::SetSound(x) => {
+        let value = self.get_v(x);
+        self.sound_timer = value;
+      }
+      Instruction::AddI(x) => {
+        let vx = self.get_v(x);
+        self.i = self.i.wrapping_add(vx as usize);
+      }
+      Instruction::Font(x) => {
+        let value = self.get_v(x) & 0xf;
+        let font = self.bus.font_address() as usize;
+        self.i = font + value as usize * font::SMALL_GLYPH;
+      }
+      Instruction::LargeFont(x) => {
+        let value = self.get_v(x) & 0xf;
+        let font = self.bus.large_font_address() as usize;
+        self.i = font + value as usize * font::LARGE_GLYPH;
+      }
+      Instruction::Bcd(x) => {
+        let value = self.get_v(x);
+        self.bus.store(self.i as u16, value / 100)?;
+        self.bus.store((self.i + 1) as u16, (value % 100) / 10)?;
+        self.bus.store((self.i + 2) as u16, value % 10)?;
+        
+      }
+      Instruction::Store(x) => {
+        for index in 0..=x {
+          let value = self.get_v(index);
+          self.bus.store((self.i + index as usize) as u16, value)?;
+        }
+
+        if self.quirks.load_store {
+          self.i += x as usize + 1;
+        }
+      }
+      Instruction::Load(x) => {
+        for index in 0..=x {
+          let value = self.bus.load((self.i + index as usize) as u16)?;
+          self.set_v(index, value);
+        }
+
+        if self.quirks.load_store {
+          self.i += x as usize + 1;
+        }
+      }
+      Instruction::SaveRange(x, y) => {
+        for (offset, index) in range(x, y).enumerate() {
+          let value = self.get_v(index);
+          self.bus.store((self.i + offset) as u16, value)?;
+        }
+      }
+      Instruction::LoadRange(x, y) => {
+        for (offset, index) in range(x, y).enumerate() {
+          let value = self.bus.load((self.i + offset) as u16)?;
+          self.set_v(index, value);
         }
       }
-      0xf => {
-        match nn {
-          0x07 => {
-            let value = self.delay_timer;
-            self.set_v(x, value);
-          }
-          0x0a => {
-            let mut pressed = false;
-
-            for index in 0x0..0xf {
-              if self.key[index] {
-                self.set_v(x, index as u8);
-                pressed = true;
-              } 
-            }
-
-            if !pressed {
-              // Blocking Operation. All instruction halted until next key event.
-              self.pc = self.pc.wrapping_sub(2);
-            }
-          }
-          0x15 => {
-            let value = self.get_v(x);
-            self.delay_timer = value;
-          }
-          0x18 => {
-            let value = self.get_v(x);
-            self.sound_timer = value;
-          }
-          0x1e => {
-            let vx = self.get_v(x);
-            self.i = self.i.wrapping_add(vx as usize);
-          }
-          0x29 => {
-            let value = self.get_v(x);
-            self.i = value as usize * 5; // Font 4x5.
-          }
-          0x33 => {
-            let value = self.get_v(x);
-            self.bus.store(self.i as u16, value / 100);
-            self.bus.store((self.i + 1) as u16, (value % 100) / 10);
-            self.bus.store((self.i + 2) as u16, value % 10);
-            
-          }
-          0x55 => {
-            for index in 0..x {
-              let value = self.get_v(index);
-              self.bus.store((self.i as u8 + index) as u16, value);
-            }
-          }
-          0x65 => {
-            for index in 0..x {
-              let value = self.bus.load((self.i as u8 + index) as u16);
-              self.set_v(index, value);
-            }
-          }
-         _ => panic!("Unknown instruction {:#06x}", instruction),
+      Instruction::SetLongI => {
+        self.i = self.fetch()? as usize;
+        self.pc = self.pc.wrapping_add(2);
+      }
+      Instruction::Audio => {
+        let mut pattern = [0; 16];
+        for (offset, byte) in pattern.iter_mut().enumerate() {
+          *byte = self.bus.load((self.i + offset) as u16)?;
         }
+        self.pattern = Some(pattern);
+      }
+      Instruction::Pitch(x) => {
+        self.pitch = self.get_v(x);
+      }
+      // SUPER-CHIP's extras this interpreter leaves out.
+      Instruction::Exit | Instruction::SaveFlags(_) | Instruction::LoadFlags(_) => {
+        return Err(Fault::UnknownInstruction(opcode));
       }
-      _ => panic!("Unknown instruction {:#06x}", instruction),
     }
+
+    Ok(())
   }
 
-  fn draw(&mut self, x: u8, y: u8, n: u8) {
-    let col = self.get_v(x) % 64;
-    let row = self.get_v(y) % 32;
-
-    self.set_v(0xf, 0);
-    
-    for offset in 0..n {
-      let pixel = self.bus.rom.load(self.i.wrapping_add(offset as usize) as u16);
-      for coll_offset in 0..8 {
-        if (pixel & 0x80 >> coll_offset) > 0 {
-          if self.video[((row + offset) % 32) as usize][((col + coll_offset) % 64) as usize] == 1 {
-            self.set_v(0xf, 1);
-          }
-          self.video[((row + offset) % 32) as usize][((col + coll_offset) % 64) as usize] ^= 1;
-        }
+  fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), Fault> {
+    // DXY0 draws a 16x16 sprite on SUPER-CHIP and XO-CHIP, and nothing on
+    // the VIP.
+    let (rows, wide) = match n {
+      0 if self.quirks.large_sprites => (16, true),
+      0 => {
+        self.set_v(0xf, 0);
+        return Ok(());
       }
+      _ => (n as usize, false),
+    };
+    let planes = self.display.selected().count_ones() as usize;
+    let length = rows * if wide { 2 } else { 1 } * planes;
+
+    let mut sprite = [0; 64];
+    for (offset, byte) in sprite[..length].iter_mut().enumerate() {
+      *byte = self.bus.load(self.i.wrapping_add(offset) as u16)?;
     }
+
+    let col = self.get_v(x);
+    let row = self.get_v(y);
+
+    let collision = self.display.draw(col, row, &sprite[..length], wide, self.quirks.clipping);
+    self.display_dirty = true;
+    self.set_v(0xf, collision as u8);
+
+    Ok(())
   }
 
   pub fn read_keys(&mut self, key_code: usize, status: bool) {
     self.key[key_code] = status;
   }
 }
+
+/// VX to VY for XO-CHIP's 5XY2 and 5XY3, backwards when Y is below X.
+fn range(x: u8, y: u8) -> impl Iterator<Item = u8> {
+  let count = x.abs_diff(y);
+  (0..=count).map(move |offset| if x <= y { x + offset } else { x - offset })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bus::Policies;
+  use memory::{Layout, Memory};
+
+  struct Fixed;
+
+  impl Rng for Fixed {
+    fn next_u8(&mut self) -> u8 {
+      0
+    }
+  }
+
+  fn cpu(program: &[u8]) -> Cpu<Vec<u8>, Fixed> {
+    let layout: Layout<&[u8]> = Layout {
+      memory_size: 0x1000,
+      load_address: 0x200,
+      font: &[0xF0; 80],
+      font_address: 0x000,
+      large_font: &[0xFF; 160],
+      large_font_address: 0x050,
+    };
+    let memory = Memory::with_storage(vec![0; 0x1000], &layout, program).unwrap();
+
+    Cpu::with_rng(Bus::with_memory(memory, &layout, Policies::default()), Quirks::default(), 0x200, Fixed)
+  }
+
+  #[test]
+  fn skips_on_5xyn_and_9xyn_whatever_n() {
+    // SE V1, V2 and SNE V1, V2, both with N = 1.
+    let mut cpu = cpu(&[0x51, 0x21, 0x00, 0xE0, 0x61, 0x01, 0x91, 0x21, 0x00, 0xE0]);
+
+    cpu.run_next_instruction().unwrap();
+    assert_eq!(cpu.pc(), 0x204);
+
+    cpu.run_next_instruction().unwrap();
+    cpu.run_next_instruction().unwrap();
+    assert_eq!(cpu.pc(), 0x20A);
+  }
+
+  #[test]
+  fn loads_a_long_i_and_skips_over_it_whole() {
+    // SE V0, 0 then LD I, 0x1234 twice.
+    let mut cpu = cpu(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0xF0, 0x00, 0x12, 0x34]);
+
+    cpu.run_next_instruction().unwrap();
+    assert_eq!(cpu.pc(), 0x206);
+
+    cpu.run_next_instruction().unwrap();
+    assert_eq!((cpu.i(), cpu.pc()), (0x1234, 0x20A));
+  }
+
+  #[test]
+  fn saves_and_loads_register_ranges_either_way() {
+    let mut cpu = cpu(&[
+      0xA3, 0x00, // LD I, 0x300
+      0x61, 0x11, 0x62, 0x22, 0x63, 0x33, // V1-V3 = 0x11, 0x22, 0x33
+      0x51, 0x32, // SAVE V1, V3
+      0x53, 0x13, // LOAD V3, V1
+    ]);
+
+    for _ in 0..5 {
+      cpu.run_next_instruction().unwrap();
+    }
+    assert_eq!((cpu.bus().load(0x300), cpu.bus().load(0x302)), (Ok(0x11), Ok(0x33)));
+    assert_eq!(cpu.i(), 0x300);
+
+    cpu.run_next_instruction().unwrap();
+    assert_eq!(cpu.v()[1..4], [0x33, 0x22, 0x11]);
+  }
+
+  #[test]
+  fn loads_an_audio_pattern_and_pitch() {
+    let mut cpu = cpu(&[0xA2, 0x08, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A, 0xAA, 0x55]);
+    assert_eq!((cpu.audio_pattern(), cpu.pitch()), (None, DEFAULT_PITCH));
+
+    for _ in 0..4 {
+      cpu.run_next_instruction().unwrap();
+    }
+    let pattern = cpu.audio_pattern().unwrap();
+    assert_eq!((pattern[0], pattern[1]), (0xAA, 0x55));
+    assert_eq!(cpu.pitch(), 0x70);
+
+    cpu.restart();
+    assert_eq!((cpu.audio_pattern(), cpu.pitch()), (None, DEFAULT_PITCH));
+  }
+
+  #[test]
+  fn draws_16x16_sprites_only_with_the_large_sprites_quirk() {
+    // VF = 1, I at 32 bytes of 0xFF, then DRW V0, V0, 0.
+    let mut program = vec![0x6F, 0x01, 0xA2, 0x06, 0xD0, 0x00];
+    program.extend_from_slice(&[0xFF; 32]);
+
+    // The VIP draws nothing, and clears VF.
+    let mut vip = cpu(&program);
+    for _ in 0..3 {
+      vip.run_next_instruction().unwrap();
+    }
+    assert_eq!(vip.display().pixel(0, 0), 0);
+    assert_eq!(vip.v()[0xf], 0);
+
+    let mut schip = cpu(&program);
+    schip.set_quirks(Quirks { large_sprites: true, ..Quirks::default() });
+    for _ in 0..3 {
+      schip.run_next_instruction().unwrap();
+    }
+    assert_eq!(schip.display().pixel(15, 15), 1);
+    assert_eq!(schip.display().pixel(16, 0), 0);
+  }
+}
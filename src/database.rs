@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+use toml::value::Table;
+
+use config;
+use platform::Platform;
+
+const BUNDLED: &str = include_str!("roms.toml");
+
+/// What is known about a ROM: where it came from and how to run it.
+///
+/// The settings tables use the same layout as the matching config file
+/// sections and are applied below the user's own configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RomInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub platform: Option<Platform>,
+    pub speed: Option<u32>,
+    pub load_address: Option<u16>,
+    pub entry: Option<u16>,
+    #[serde(default)]
+    pub quirks: Table,
+    #[serde(default)]
+    pub keymap: Table,
+    #[serde(default)]
+    pub palette: Table,
+    /// Database the entry was read from.
+    #[serde(skip)]
+    pub source: String,
+}
+
+impl RomInfo {
+    /// The entry as a config layer.
+    pub fn settings(&self) -> Table {
+        let mut cpu = Table::new();
+        if let Some(platform) = self.platform {
+            cpu.insert("platform".into(), Value::try_from(platform).unwrap());
+        }
+        if let Some(speed) = self.speed {
+            cpu.insert("speed".into(), Value::Integer(speed as i64));
+        }
+        if let Some(load_address) = self.load_address {
+            cpu.insert("load_address".into(), Value::Integer(load_address as i64));
+        }
+        if let Some(entry) = self.entry {
+            cpu.insert("entry".into(), Value::Integer(entry as i64));
+        }
+
+        let mut settings = Table::new();
+        settings.insert("cpu".into(), Value::Table(cpu));
+        settings.insert("quirks".into(), Value::Table(self.quirks.clone()));
+        settings.insert("keymap".into(), Value::Table(self.keymap.clone()));
+        settings.insert("palette".into(), Value::Table(self.palette.clone()));
+        settings
+    }
+}
+
+/// ROM metadata keyed by the lowercase hex SHA-1 of the ROM file.
+#[derive(Debug, Default)]
+pub struct Database {
+    entries: HashMap<String, RomInfo>,
+}
+
+impl Database {
+    /// The database shipped with the emulator.
+    pub fn bundled() -> Database {
+        Database::parse(BUNDLED, "bundled ROM database").expect("bundled ROM database is valid")
+    }
+
+    /// The bundled database extended with the user's local one, if present.
+    pub fn open() -> io::Result<Database> {
+        match local_path() {
+            Some(path) => Database::open_at(path),
+            None => Ok(Database::bundled()),
+        }
+    }
+
+    /// The bundled database extended with the one at `path`, if present.
+    pub fn open_at<P: AsRef<Path>>(path: P) -> io::Result<Database> {
+        let mut database = Database::bundled();
+
+        match Database::load(path) {
+            Ok(local) => database.extend(local),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        Ok(database)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Database> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+
+        Database::parse(&text, &path.display().to_string())
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+    }
+
+    fn parse(text: &str, source: &str) -> Result<Database, String> {
+        let entries: HashMap<String, RomInfo> = toml::from_str(text)
+            .map_err(|err| format!("invalid ROM database {}: {}", source, err.to_string().trim_end()))?;
+
+        let entries = entries.into_iter()
+            .map(|(hash, mut info)| {
+                info.source = source.to_string();
+                (hash.to_lowercase(), info)
+            })
+            .collect();
+
+        Ok(Database { entries })
+    }
+
+    /// Adds the entries of `other`, replacing any with the same hash.
+    pub fn extend(&mut self, other: Database) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn lookup(&self, hash: &str) -> Option<&RomInfo> {
+        self.entries.get(&hash.to_lowercase())
+    }
+}
+
+/// `$CHIP8_ROM_DB`, or `roms.toml` next to the default config file.
+pub fn local_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CHIP8_ROM_DB") {
+        return Some(PathBuf::from(path));
+    }
+
+    config::config_dir().map(|dir| dir.join("roms.toml"))
+}
@@ -0,0 +1,231 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use cheat::{self, Cheat, Comparison, Search};
+use machine::Machine;
+
+/// How many candidates `search` lists before only counting them.
+const SHOWN_CANDIDATES: usize = 16;
+
+/// An interactive debugger over a headless machine, one command per line.
+///
+/// Addresses, values and codes are hex, as in cheat codes. Commands:
+///
+/// - `step [frames]`: runs frames, 1 by default
+/// - `regs`: the registers and timers
+/// - `mem <address> [length]`: a hex dump, 16 bytes by default
+/// - `search start`: snapshots RAM for a cheat search
+/// - `search equal|changed|increased|decreased|<value>`: keeps the
+///   candidates whose byte changed that way since the last step, or is
+///   now `value`
+/// - `freeze <AAA:VV>`, `unfreeze <address>`: hold an address at a value
+///   every frame, or let it go
+/// - `cheats`: the frozen addresses as codes
+/// - `save-cheats`, `load-cheats`: keep the codes for the ROM's hash, or
+///   freeze the kept ones
+/// - `help`, `quit`
+pub struct Debugger {
+    machine: Machine,
+    search: Option<Search>,
+    /// Where `save-cheats` and `load-cheats` keep codes.
+    cheat_dir: Option<PathBuf>,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine) -> Debugger {
+        Debugger {
+            machine,
+            search: None,
+            cheat_dir: cheat::dir(),
+        }
+    }
+
+    /// Keeps cheats in `dir` instead of next to the default config file.
+    pub fn set_cheat_dir(&mut self, dir: PathBuf) {
+        self.cheat_dir = Some(dir);
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Prompts for and runs commands from `input` until it ends or one is
+    /// `quit`, writing what they print to `output`.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        let mut lines = input.lines();
+
+        loop {
+            write!(output, "(chip8) ")?;
+            output.flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return writeln!(output),
+            };
+            if line.trim() == "quit" {
+                return Ok(());
+            }
+
+            match self.execute(&line) {
+                Ok(reply) if reply.is_empty() => {}
+                Ok(reply) => writeln!(output, "{}", reply)?,
+                Err(message) => writeln!(output, "error: {}", message)?,
+            }
+        }
+    }
+
+    /// Runs one command line, returning what it prints.
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] => Ok(HELP.to_string()),
+            ["step"] => self.step(1),
+            ["step", frames] => self.step(number(frames)?),
+            ["regs"] => Ok(self.registers()),
+            ["mem", address] => self.dump(number(address)? as usize, 16),
+            ["mem", address, length] => self.dump(number(address)? as usize, number(length)? as usize),
+            ["search", "start"] => {
+                let search = Search::new(self.machine.memory());
+                let count = search.candidates().len();
+                self.search = Some(search);
+                Ok(format!("{} candidates", count))
+            }
+            ["search", compare] => {
+                let comparison = comparison(compare)?;
+                let search = self.search.as_mut().ok_or("no search started; run `search start` first")?;
+                search.narrow(self.machine.memory(), comparison);
+                Ok(candidates(search.candidates()))
+            }
+            ["freeze", code] => {
+                let cheat: Cheat = code.parse()?;
+                self.machine.freeze(cheat).map_err(|err| format!("cannot freeze {}: {}", cheat, err))?;
+                Ok(String::new())
+            }
+            ["unfreeze", address] => {
+                let address = number(address)?;
+                if self.machine.unfreeze(address as u16) {
+                    Ok(String::new())
+                } else {
+                    Err(format!("{:03X} is not frozen", address))
+                }
+            }
+            ["cheats"] => Ok(codes(&self.machine.cheats())),
+            ["save-cheats"] => {
+                let path = cheat::save_to(&self.cheat_dir()?, &self.rom_hash()?, &self.machine.cheats())
+                    .map_err(|err| format!("cannot save cheats: {}", err))?;
+                Ok(format!("saved to {}", path.display()))
+            }
+            ["load-cheats"] => {
+                let cheats = cheat::load_from(&self.cheat_dir()?, &self.rom_hash()?)
+                    .map_err(|err| format!("cannot load cheats: {}", err))?;
+                for &cheat in &cheats {
+                    self.machine.freeze(cheat).map_err(|err| format!("cannot freeze {}: {}", cheat, err))?;
+                }
+                Ok(codes(&cheats))
+            }
+            [command, ..] => Err(format!("unknown command or arguments for `{}`; `help` lists them", command)),
+        }
+    }
+
+    fn step(&mut self, frames: u32) -> Result<String, String> {
+        for _ in 0..frames {
+            if let Err(fault) = self.machine.run_frame() {
+                return Err(format!("{} at {:03X}", fault, self.machine.cpu().pc()));
+            }
+        }
+
+        Ok(format!("frame {}", self.machine.frame()))
+    }
+
+    fn registers(&self) -> String {
+        let registers = self.machine.registers();
+        let v: Vec<String> = registers.v.iter().map(|value| format!("{:02X}", value)).collect();
+
+        format!(
+            "PC {:03X}  I {:03X}  SP {:X}  DT {:02X}  ST {:02X}\nV0-V7 {}\nV8-VF {}",
+            registers.pc, registers.i, registers.sp, registers.delay_timer, registers.sound_timer,
+            v[..8].join(" "), v[8..].join(" "),
+        )
+    }
+
+    fn dump(&self, address: usize, length: usize) -> Result<String, String> {
+        let memory = self.machine.memory();
+        let bytes = address.checked_add(length)
+            .and_then(|end| memory.get(address..end))
+            .ok_or_else(|| format!("memory ends at {:03X}", memory.len()))?;
+
+        let lines: Vec<String> = bytes.chunks(16).enumerate()
+            .map(|(row, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+                format!("{:03X}: {}", address + row * 16, hex.join(" "))
+            })
+            .collect();
+
+        Ok(lines.join("\n"))
+    }
+
+    fn cheat_dir(&self) -> Result<PathBuf, String> {
+        self.cheat_dir.clone().ok_or_else(|| "no config directory to keep cheats in".to_string())
+    }
+
+    /// What the machine's cheats are kept under.
+    fn rom_hash(&self) -> Result<String, String> {
+        self.machine.rom()
+            .map(|rom| rom.hash().to_string())
+            .ok_or_else(|| "the machine was not loaded from a ROM".to_string())
+    }
+}
+
+const HELP: &str = "\
+step [frames]           run frames, 1 by default
+regs                    show the registers and timers
+mem <address> [length]  dump memory, 16 bytes by default
+search start            snapshot RAM to start a cheat search
+search <compare>        keep addresses whose byte is equal, changed,
+                        increased or decreased since the last search,
+                        or now holds a value
+freeze <AAA:VV>         hold address AAA at value VV every frame
+unfreeze <address>      let a frozen address change again
+cheats                  list the frozen addresses as codes
+save-cheats             keep the codes for this ROM
+load-cheats             freeze the codes kept for this ROM
+quit                    leave the debugger
+
+Addresses, values and codes are hex.";
+
+fn number(word: &str) -> Result<u32, String> {
+    u32::from_str_radix(word.trim_start_matches("0x"), 16).map_err(|_| format!("`{}` is not a hex number", word))
+}
+
+fn comparison(word: &str) -> Result<Comparison, String> {
+    match word {
+        "equal" => Ok(Comparison::Equal),
+        "changed" => Ok(Comparison::Changed),
+        "increased" => Ok(Comparison::Increased),
+        "decreased" => Ok(Comparison::Decreased),
+        _ => match number(word) {
+            Ok(value) if value <= 0xFF => Ok(Comparison::Value(value as u8)),
+            _ => Err(format!("cannot compare with `{}`; expected equal, changed, increased, decreased or a byte", word)),
+        },
+    }
+}
+
+fn candidates(addresses: &[u16]) -> String {
+    let shown: Vec<String> = addresses.iter().take(SHOWN_CANDIDATES).map(|address| format!("{:03X}", address)).collect();
+
+    match addresses.len() {
+        0 => "no candidates left".to_string(),
+        count if count > shown.len() => format!("{} candidates: {} ...", count, shown.join(" ")),
+        count => format!("{} candidates: {}", count, shown.join(" ")),
+    }
+}
+
+fn codes(cheats: &[Cheat]) -> String {
+    if cheats.is_empty() {
+        return "no cheats".to_string();
+    }
+
+    cheats.iter().map(Cheat::to_string).collect::<Vec<_>>().join("\n")
+}
@@ -0,0 +1,842 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use flow::{Block, EdgeKind, Graph, RegionKind, Terminator};
+use instruction::Instruction;
+use quirks::Quirks;
+
+/// An instruction the flow analysis reached.
+#[derive(Clone, Copy, Debug)]
+struct Line {
+    address: u16,
+    opcode: u16,
+    /// `None` for an opcode no platform defines.
+    instruction: Option<Instruction>,
+    /// The address F000 NNNN loads into I.
+    long: u16,
+}
+
+impl Line {
+    fn end(&self) -> u32 {
+        self.address as u32 + self.instruction.map_or(2, Instruction::width) as u32
+    }
+}
+
+/// Bytes a DXYN draws, found where the same block points I at them first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub address: u16,
+    /// 8, or 16 for DXY0.
+    pub width: u8,
+    pub height: u8,
+}
+
+impl Sprite {
+    fn len(&self) -> usize {
+        self.width as usize / 8 * self.height as usize
+    }
+}
+
+/// A stretch of the program, in the order it is printed.
+enum Item {
+    Code(Line),
+    /// Bytes outside the code from an address, and the region they are in.
+    Bytes(u16, Vec<u8>, Option<RegionKind>),
+}
+
+/// A subroutine's code, with blocks shared with another subroutine left
+/// to whichever comes first.
+struct Function {
+    name: String,
+    address: u16,
+    lines: Vec<Line>,
+    /// Whether it converts a register to decimal with FX33, as the
+    /// routines drawing scores do.
+    score: bool,
+}
+
+/// A statement of pseudo-code, at the address of its first instruction.
+enum Statement {
+    Line(u16, String),
+    Goto(u16, u16),
+    If(u16, String, Vec<Statement>, Vec<Statement>),
+    Loop(u16, Vec<Statement>),
+}
+
+impl Statement {
+    fn address(&self) -> u16 {
+        match *self {
+            Statement::Line(address, _)
+            | Statement::Goto(address, _)
+            | Statement::If(address, ..)
+            | Statement::Loop(address, _) => address,
+        }
+    }
+}
+
+/// The loop the statements being structured sit in.
+#[derive(Clone, Copy)]
+struct Loop {
+    head: u16,
+    exit: u32,
+}
+
+/// A program taken apart into functions, labels and data, to print as
+/// structured pseudo-code or as assembler source.
+///
+/// Built on the control-flow [`Graph`], so it shares its blind spots:
+/// code only BNNN reaches comes out as unreachable bytes.
+pub struct Listing<'a> {
+    pub graph: Graph,
+    pub sprites: Vec<Sprite>,
+    program: &'a [u8],
+    quirks: Quirks,
+    functions: Vec<Function>,
+    items: Vec<Item>,
+    /// Names of addresses a line or byte run starts at.
+    labels: BTreeMap<u16, String>,
+    /// Addresses control reaches other than by running on from the
+    /// instruction before.
+    entries: BTreeSet<u16>,
+}
+
+impl<'a> Listing<'a> {
+    /// Takes `program` apart, loaded at `load_address` and entered at
+    /// `entry`. The quirks decide how shifts and BNNN read.
+    pub fn new(program: &'a [u8], load_address: u16, entry: u16, quirks: Quirks) -> Listing<'a> {
+        let graph = Graph::build(program, load_address, entry);
+        let start = load_address as u32;
+        let end = start + program.len() as u32;
+        let inside = |address: u16| (address as u32) >= start && (address as u32) < end;
+
+        let mut lines = BTreeMap::new();
+        for block in &graph.blocks {
+            let mut address = block.start;
+            for &opcode in &block.opcodes {
+                let instruction = Instruction::decode(opcode);
+                let long = match instruction {
+                    Some(Instruction::SetLongI) => word(program, start, address as u32 + 2).unwrap_or(0),
+                    _ => 0,
+                };
+                let line = Line { address, opcode, instruction, long };
+                lines.insert(address, line);
+                address = line.end() as u16;
+            }
+        }
+
+        let sprites = sprites(&graph, &lines, quirks, &inside);
+        let functions = functions(&graph, &lines);
+
+        // Wanted names, the later ones winning where two meet.
+        let mut wanted = BTreeMap::new();
+        for unresolved in graph.unresolved.iter().filter(|unresolved| inside(unresolved.base)) {
+            wanted.insert(unresolved.base, format!("table_{:03X}", unresolved.base));
+        }
+        for line in lines.values() {
+            let pointer = match line.instruction {
+                Some(Instruction::SetI(nnn)) => nnn,
+                Some(Instruction::SetLongI) => line.long,
+                _ => continue,
+            };
+            if inside(pointer) {
+                wanted.insert(pointer, format!("data_{:03X}", pointer));
+            }
+        }
+        for sprite in &sprites {
+            wanted.insert(sprite.address, format!("sprite_{:03X}", sprite.address));
+        }
+        let mut entries = BTreeSet::new();
+        for block in &graph.blocks {
+            for edge in block.successors.iter().filter(|edge| edge.kind != EdgeKind::Next) {
+                entries.insert(edge.target);
+                if edge.kind == EdgeKind::Jump {
+                    wanted.insert(edge.target, format!("L_{:03X}", edge.target));
+                }
+            }
+        }
+        for function in &functions {
+            entries.insert(function.address);
+            wanted.insert(function.address, function.name.clone());
+        }
+
+        let items = items(&graph, program, &lines, &wanted);
+        let labels = items.iter()
+            .filter_map(|item| {
+                let address = match *item {
+                    Item::Code(line) => line.address,
+                    Item::Bytes(address, ..) => address,
+                };
+                wanted.get(&address).map(|name| (address, name.clone()))
+            })
+            .collect();
+
+        Listing { graph, sprites, program, quirks, functions, items, labels, entries }
+    }
+
+    /// The name given to `address`: `main`, `sub_XXX`, `score_XXX` for a
+    /// subroutine using FX33, `L_XXX` for a jump target, `sprite_XXX`,
+    /// `data_XXX` or `table_XXX` for what I or BNNN point at.
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// The program as C-like pseudo-code: a function per subroutine, with
+    /// skips and jumps turned into `if`, `else` and `loop` where they nest,
+    /// and `goto` where they do not, then the data with sprites drawn out.
+    pub fn to_pseudo_code(&self) -> String {
+        let mut code = String::new();
+        writeln!(
+            code, "// {} bytes loaded at {:#05X}, entered at {:#05X}.",
+            self.program.len(), self.graph.load_address, self.graph.entry,
+        ).unwrap();
+
+        let bodies: Vec<Vec<Statement>> = self.functions.iter()
+            .map(|function| Structurer { listing: self, lines: &function.lines }.statements(0, function.lines.len(), None))
+            .collect();
+        let mut targets = BTreeSet::new();
+        for body in &bodies {
+            gotos(body, &mut targets);
+        }
+
+        let mut printed = BTreeSet::new();
+        for (function, body) in self.functions.iter().zip(&bodies) {
+            code.push('\n');
+            if function.score {
+                code.push_str("// Converts to decimal with FX33, as score routines do.\n");
+            }
+            writeln!(code, "void {}() {{", function.name).unwrap();
+            printed.insert(function.address);
+            self.render(&mut code, body, 1, &targets, &mut printed);
+            code.push_str("}\n");
+        }
+
+        for item in &self.items {
+            let (address, bytes, kind) = match *item {
+                Item::Bytes(address, ref bytes, kind) => (address, bytes, kind),
+                Item::Code(_) => continue,
+            };
+            code.push('\n');
+            if kind == Some(RegionKind::Unreachable) {
+                let end = address as u32 + bytes.len() as u32 - 1;
+                writeln!(code, "// {:#05X}-{:#05X}, unreachable:", address, end).unwrap();
+                for pair in bytes.chunks(2) {
+                    match decode(pair) {
+                        Some(instruction) => writeln!(code, "//     {}", instruction).unwrap(),
+                        None => writeln!(code, "//     {}", hex(pair)).unwrap(),
+                    }
+                }
+                continue;
+            }
+
+            if let Some(sprite) = self.sprites.iter().find(|sprite| sprite.address == address) {
+                writeln!(code, "// {}x{} sprite:", sprite.width, sprite.height).unwrap();
+                for row in self.sprite_rows(sprite) {
+                    writeln!(code, "//     {}", row).unwrap();
+                }
+            }
+            let name = self.labels.get(&address).cloned().unwrap_or_else(|| format!("data_{:03X}", address));
+            if bytes.len() <= 8 {
+                writeln!(code, "byte {}[{}] = {{ {} }};", name, bytes.len(), hex(bytes)).unwrap();
+            } else {
+                writeln!(code, "byte {}[{}] = {{", name, bytes.len()).unwrap();
+                for chunk in bytes.chunks(8) {
+                    writeln!(code, "    {},", hex(chunk)).unwrap();
+                }
+                code.push_str("};\n");
+            }
+        }
+
+        code
+    }
+
+    /// The program as assembler source in the usual mnemonics, with labels
+    /// for subroutines, jump targets and data, and every byte outside the
+    /// code kept as `DB`, so assembling it gives back the same program.
+    pub fn to_assembly(&self) -> String {
+        let mut code = String::new();
+        writeln!(
+            code, "; {} bytes loaded at {:#05X}, entered at {:#05X}.",
+            self.program.len(), self.graph.load_address, self.graph.entry,
+        ).unwrap();
+        code.push('\n');
+
+        for item in &self.items {
+            let address = match *item {
+                Item::Code(line) => line.address,
+                Item::Bytes(address, ..) => address,
+            };
+            if let Some(label) = self.labels.get(&address) {
+                if self.functions.iter().any(|function| function.address == address && function.score) {
+                    code.push_str("; Converts to decimal with FX33, as score routines do.\n");
+                }
+                writeln!(code, "{}:", label).unwrap();
+            }
+
+            match *item {
+                Item::Code(line) => {
+                    let (text, comment) = self.assembly(&line);
+                    statement(&mut code, &text, comment);
+                }
+                Item::Bytes(_, ref bytes, Some(RegionKind::Unreachable)) => {
+                    for pair in bytes.chunks(2) {
+                        let comment = decode(pair).map(|instruction| format!("unreachable: {}", instruction));
+                        statement(&mut code, &format!("DB {}", hex(pair)), comment.as_deref());
+                    }
+                }
+                Item::Bytes(address, ref bytes, _) => {
+                    let mut rest = &bytes[..];
+                    if let Some(sprite) = self.sprites.iter().find(|sprite| sprite.address == address) {
+                        let width = sprite.width as usize / 8;
+                        for row in self.sprite_rows(sprite) {
+                            if rest.len() < width {
+                                break;
+                            }
+                            statement(&mut code, &format!("DB {}", hex(&rest[..width])), Some(&row));
+                            rest = &rest[width..];
+                        }
+                    }
+                    for chunk in rest.chunks(8) {
+                        statement(&mut code, &format!("DB {}", hex(chunk)), None);
+                    }
+                }
+            }
+        }
+
+        code
+    }
+
+    /// The name of `address` as an operand, or the address itself.
+    fn operand(&self, address: u16) -> String {
+        self.labels.get(&address).cloned().unwrap_or_else(|| format!("{:#05X}", address))
+    }
+
+    /// The label a `goto` to `address` uses.
+    fn target(&self, address: u16) -> String {
+        self.labels.get(&address).cloned().unwrap_or_else(|| format!("L_{:03X}", address))
+    }
+
+    /// The register BNNN adds to NNN under the current quirks.
+    fn offset_register(&self, nnn: u16) -> u16 {
+        if self.quirks.jump { nnn >> 8 } else { 0 }
+    }
+
+    /// A line as assembler, and a comment to go with it.
+    fn assembly(&self, line: &Line) -> (String, Option<&'static str>) {
+        let instruction = match line.instruction {
+            Some(instruction) => instruction,
+            None => {
+                let bytes = [(line.opcode >> 8) as u8, line.opcode as u8];
+                return (format!("DB {}", hex(&bytes)), Some("invalid instruction"));
+            }
+        };
+
+        let text = match instruction {
+            Instruction::Jump(nnn) => format!("JP {}", self.operand(nnn)),
+            Instruction::Call(nnn) => format!("CALL {}", self.operand(nnn)),
+            Instruction::SetI(nnn) => format!("LD I, {}", self.operand(nnn)),
+            Instruction::SetLongI => format!("LD I, LONG {}", self.operand(line.long)),
+            Instruction::JumpOffset(nnn) => {
+                return (format!("JP V0, {}", self.operand(nnn)), Some("computed, not followed"));
+            }
+            _ => instruction.to_string(),
+        };
+
+        (text, None)
+    }
+
+    /// A line as a pseudo-code statement.
+    fn pseudo_code(&self, line: &Line) -> String {
+        let instruction = match line.instruction {
+            Some(instruction) => instruction,
+            None => return format!("invalid({:#06X});", line.opcode),
+        };
+        let shifted = |x: u8, y: u8| if self.quirks.shift || x == y { format!("v{:x}", x) } else { format!("v{:x}", y) };
+
+        match instruction {
+            Instruction::Clear => "clear();".to_string(),
+            Instruction::Return => "return;".to_string(),
+            Instruction::ScrollDown(n) => format!("scroll_down({});", n),
+            Instruction::ScrollUp(n) => format!("scroll_up({});", n),
+            Instruction::ScrollRight => "scroll_right();".to_string(),
+            Instruction::ScrollLeft => "scroll_left();".to_string(),
+            Instruction::Exit => "exit();".to_string(),
+            Instruction::LowRes => "lores();".to_string(),
+            Instruction::HighRes => "hires();".to_string(),
+            Instruction::Jump(nnn) => format!("goto {};", self.target(nnn)),
+            Instruction::Call(nnn) => match self.labels.get(&nnn) {
+                Some(name) => format!("{}();", name),
+                None => format!("call({:#05X});", nnn),
+            },
+            Instruction::SaveRange(x, y) => format!("save(v{:x}..v{:x});", x, y),
+            Instruction::LoadRange(x, y) => format!("load(v{:x}..v{:x});", x, y),
+            Instruction::Set(x, nn) => format!("v{:x} = {};", x, nn),
+            Instruction::Add(x, nn) => format!("v{:x} += {};", x, nn),
+            Instruction::Move(x, y) => format!("v{:x} = v{:x};", x, y),
+            Instruction::Or(x, y) => format!("v{:x} |= v{:x};", x, y),
+            Instruction::And(x, y) => format!("v{:x} &= v{:x};", x, y),
+            Instruction::Xor(x, y) => format!("v{:x} ^= v{:x};", x, y),
+            Instruction::AddRegister(x, y) => format!("v{:x} += v{:x};", x, y),
+            Instruction::Sub(x, y) => format!("v{:x} -= v{:x};", x, y),
+            Instruction::ShiftRight(x, y) => format!("v{:x} = {} >> 1;", x, shifted(x, y)),
+            Instruction::SubReverse(x, y) => format!("v{:x} = v{:x} - v{:x};", x, y, x),
+            Instruction::ShiftLeft(x, y) => format!("v{:x} = {} << 1;", x, shifted(x, y)),
+            Instruction::SetI(nnn) => format!("i = {};", self.operand(nnn)),
+            Instruction::JumpOffset(nnn) => {
+                format!("jump({} + v{:x});  // not followed", self.operand(nnn), self.offset_register(nnn))
+            }
+            Instruction::Random(x, nn) => format!("v{:x} = rand() & {:#04X};", x, nn),
+            Instruction::Draw(x, y, n) => format!("vf = draw(v{:x}, v{:x}, {});", x, y, n),
+            Instruction::SetLongI => format!("i = {};", self.operand(line.long)),
+            Instruction::Planes(n) => format!("planes({});", n),
+            Instruction::Audio => "audio();".to_string(),
+            Instruction::GetDelay(x) => format!("v{:x} = delay;", x),
+            Instruction::WaitKey(x) => format!("v{:x} = wait_key();", x),
+            Instruction::SetDelay(x) => format!("delay = v{:x};", x),
+            Instruction::SetSound(x) => format!("sound = v{:x};", x),
+            Instruction::AddI(x) => format!("i += v{:x};", x),
+            Instruction::Font(x) => format!("i = font(v{:x});", x),
+            Instruction::LargeFont(x) => format!("i = large_font(v{:x});", x),
+            Instruction::Bcd(x) => format!("bcd(v{:x});", x),
+            Instruction::Pitch(x) => format!("pitch = v{:x};", x),
+            Instruction::Store(x) => format!("save(v0..v{:x});", x),
+            Instruction::Load(x) => format!("load(v0..v{:x});", x),
+            Instruction::SaveFlags(x) => format!("save_flags(v0..v{:x});", x),
+            Instruction::LoadFlags(x) => format!("load_flags(v0..v{:x});", x),
+            Instruction::SkipEqual(..)
+            | Instruction::SkipNotEqual(..)
+            | Instruction::SkipEqualRegister(..)
+            | Instruction::SkipNotEqualRegister(..)
+            | Instruction::SkipKey(_)
+            | Instruction::SkipNotKey(_) => {
+                format!("if ({}) skip;", condition(instruction, true).unwrap_or_default())
+            }
+        }
+    }
+
+    /// A sprite's rows, `#` for a set pixel and `.` for a clear one.
+    fn sprite_rows(&self, sprite: &Sprite) -> Vec<String> {
+        let offset = (sprite.address - self.graph.load_address) as usize;
+        let end = (offset + sprite.len()).min(self.program.len());
+
+        self.program[offset..end]
+            .chunks(sprite.width as usize / 8)
+            .map(|row| {
+                row.iter()
+                    .flat_map(|&byte| (0..8).rev().map(move |bit| if byte >> bit & 1 == 1 { '#' } else { '.' }))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn render(&self, code: &mut String, statements: &[Statement], depth: usize, targets: &BTreeSet<u16>, printed: &mut BTreeSet<u16>) {
+        let indent = "    ".repeat(depth);
+
+        for statement in statements {
+            let address = statement.address();
+            if targets.contains(&address) && printed.insert(address) {
+                writeln!(code, "{}{}:", "    ".repeat(depth - 1), self.target(address)).unwrap();
+            }
+
+            match *statement {
+                Statement::Line(_, ref text) => writeln!(code, "{}{}", indent, text).unwrap(),
+                Statement::Goto(_, target) => writeln!(code, "{}goto {};", indent, self.target(target)).unwrap(),
+                Statement::If(_, ref condition, ref then, ref otherwise) => {
+                    writeln!(code, "{}if ({}) {{", indent, condition).unwrap();
+                    self.render(code, then, depth + 1, targets, printed);
+                    if !otherwise.is_empty() {
+                        writeln!(code, "{}}} else {{", indent).unwrap();
+                        self.render(code, otherwise, depth + 1, targets, printed);
+                    }
+                    writeln!(code, "{}}}", indent).unwrap();
+                }
+                Statement::Loop(_, ref body) => {
+                    writeln!(code, "{}loop {{", indent).unwrap();
+                    self.render(code, body, depth + 1, targets, printed);
+                    writeln!(code, "{}}}", indent).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Recovers `if`, `else` and `loop` from the skips and jumps of a function.
+struct Structurer<'b, 'a: 'b> {
+    listing: &'b Listing<'a>,
+    lines: &'b [Line],
+}
+
+impl<'b, 'a> Structurer<'b, 'a> {
+    /// The statements for `lines[from..to]`, inside the loop `inner`.
+    fn statements(&self, from: usize, to: usize, inner: Option<Loop>) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        let mut index = from;
+
+        while index < to {
+            let line = self.lines[index];
+
+            // A backward jump to here, with nothing missing in between.
+            let heads_loop = !(index == from && inner.is_some_and(|inner| inner.head == line.address));
+            if let Some(last) = self.loop_end(index, to).filter(|_| heads_loop) {
+                let inner = Loop { head: line.address, exit: self.lines[last].end() };
+                let skip = if last > index { self.skip(last - 1) } else { None };
+                let mut body = self.statements(index, if skip.is_some() { last - 1 } else { last }, Some(inner));
+                if let Some(taken) = skip {
+                    // Skipping the jump back leaves the loop.
+                    let address = self.lines[last - 1].address;
+                    body.push(Statement::If(address, taken, vec![Statement::Line(address, "break;".to_string())], Vec::new()));
+                }
+                statements.push(Statement::Loop(line.address, body));
+                index = last + 1;
+                continue;
+            }
+
+            let taken = match self.skip(index) {
+                Some(taken) => taken,
+                None => {
+                    statements.push(self.single(line, inner));
+                    index += 1;
+                    continue;
+                }
+            };
+            let next = match self.lines.get(index + 1) {
+                Some(&next) if index + 1 < to && self.follows(index + 1) && !self.listing.entries.contains(&next.address) => next,
+                _ => {
+                    statements.push(self.goto_skipped(line, index, taken));
+                    index += 1;
+                    continue;
+                }
+            };
+
+            // skip; JP else; then...; [JP end; else...;] end:
+            if let Some(Instruction::Jump(target)) = next.instruction {
+                let then_end = if target > next.address { self.position(index + 1, to, target as u32) } else { None };
+                if let Some(then_end) = then_end {
+                    let (then, otherwise, end) = match self.else_end(index + 2, then_end, to, target) {
+                        Some(end) => (
+                            self.statements(index + 2, then_end - 1, inner),
+                            self.statements(then_end, end, inner),
+                            end,
+                        ),
+                        None => (self.statements(index + 2, then_end, inner), Vec::new(), then_end),
+                    };
+                    statements.push(Statement::If(line.address, taken, then, otherwise));
+                    index = end;
+                    continue;
+                }
+            }
+
+            if self.skip(index + 1).is_none() {
+                // skip; one instruction, run when the skip is not taken.
+                let negated = line.instruction.and_then(|instruction| condition(instruction, false)).unwrap_or_default();
+                statements.push(Statement::If(line.address, negated, vec![self.single(next, inner)], Vec::new()));
+                index += 2;
+                continue;
+            }
+
+            statements.push(self.goto_skipped(line, index, taken));
+            index += 1;
+        }
+
+        statements
+    }
+
+    /// The statement for a line on its own, jumps becoming `continue` and
+    /// `break` where they go to the head or the exit of `inner`.
+    fn single(&self, line: Line, inner: Option<Loop>) -> Statement {
+        match line.instruction {
+            Some(Instruction::Jump(target)) => match inner {
+                Some(inner) if inner.head == target => Statement::Line(line.address, "continue;".to_string()),
+                Some(inner) if inner.exit == target as u32 => Statement::Line(line.address, "break;".to_string()),
+                _ => Statement::Goto(line.address, target),
+            },
+            _ => Statement::Line(line.address, self.listing.pseudo_code(&line)),
+        }
+    }
+
+    /// A skip that fits no pattern, as a `goto` to where it lands.
+    fn goto_skipped(&self, line: Line, index: usize, taken: String) -> Statement {
+        let width = self.lines.get(index + 1)
+            .filter(|next| next.address as u32 == line.end())
+            .and_then(|next| next.instruction)
+            .map_or(2, Instruction::width);
+        let target = Statement::Goto(line.address, line.end() as u16 + width);
+
+        Statement::If(line.address, taken, vec![target], Vec::new())
+    }
+
+    /// The condition the line at `index` skips on, if it is a skip.
+    fn skip(&self, index: usize) -> Option<String> {
+        self.lines.get(index).and_then(|line| line.instruction).and_then(|instruction| condition(instruction, true))
+    }
+
+    /// Whether the line at `index` starts where the one before ends.
+    fn follows(&self, index: usize) -> bool {
+        self.lines[index - 1].end() == self.lines[index].address as u32
+    }
+
+    /// The last line in `index..to` jumping back to `index`, with no gaps
+    /// between.
+    fn loop_end(&self, index: usize, to: usize) -> Option<usize> {
+        let head = self.lines[index].address;
+        let mut last = None;
+
+        for at in index..to {
+            if at > index && !self.follows(at) {
+                break;
+            }
+            if self.lines[at].instruction == Some(Instruction::Jump(head)) {
+                last = Some(at);
+            }
+        }
+
+        last
+    }
+
+    /// The index in `from..=to` of the line at `target`, or `to` if the
+    /// last line ends there, when no gap comes first.
+    fn position(&self, from: usize, to: usize, target: u32) -> Option<usize> {
+        for at in from..to {
+            if at > from && !self.follows(at) {
+                return None;
+            }
+            match (self.lines[at].address as u32).cmp(&target) {
+                Ordering::Equal => return Some(at),
+                Ordering::Greater => return None,
+                Ordering::Less => {}
+            }
+        }
+
+        if to > from && self.lines[to - 1].end() == target { Some(to) } else { None }
+    }
+
+    /// Where the `else` ends, if the `then` in `from..then_end` closes with
+    /// an unconditional jump past the line at `target`.
+    fn else_end(&self, from: usize, then_end: usize, to: usize, target: u16) -> Option<usize> {
+        if then_end <= from || then_end >= to {
+            return None;
+        }
+        let jump = self.lines[then_end - 1];
+        let end = match jump.instruction {
+            Some(Instruction::Jump(end)) if end > target => end,
+            _ => return None,
+        };
+        if self.listing.entries.contains(&jump.address) || then_end - 1 > from && self.skip(then_end - 2).is_some() {
+            return None;
+        }
+
+        self.position(then_end, to, end as u32)
+    }
+}
+
+/// The condition a skip skips on, or with `holds` false, the one it runs
+/// the next instruction on. `None` if it is not a skip.
+fn condition(instruction: Instruction, holds: bool) -> Option<String> {
+    let (equal, unequal) = if holds { ("==", "!=") } else { ("!=", "==") };
+    let (down, up) = if holds { ("", "!") } else { ("!", "") };
+
+    let condition = match instruction {
+        Instruction::SkipEqual(x, nn) => format!("v{:x} {} {}", x, equal, nn),
+        Instruction::SkipNotEqual(x, nn) => format!("v{:x} {} {}", x, unequal, nn),
+        Instruction::SkipEqualRegister(x, y) => format!("v{:x} {} v{:x}", x, equal, y),
+        Instruction::SkipNotEqualRegister(x, y) => format!("v{:x} {} v{:x}", x, unequal, y),
+        Instruction::SkipKey(x) => format!("{}key(v{:x})", down, x),
+        Instruction::SkipNotKey(x) => format!("{}key(v{:x})", up, x),
+        _ => return None,
+    };
+
+    Some(condition)
+}
+
+/// Every address a `goto` in `statements` leads to.
+fn gotos(statements: &[Statement], targets: &mut BTreeSet<u16>) {
+    for statement in statements {
+        match *statement {
+            Statement::Goto(_, target) => {
+                targets.insert(target);
+            }
+            Statement::If(_, _, ref then, ref otherwise) => {
+                gotos(then, targets);
+                gotos(otherwise, targets);
+            }
+            Statement::Loop(_, ref body) => gotos(body, targets),
+            Statement::Line(..) => {}
+        }
+    }
+}
+
+fn word(program: &[u8], load_address: u32, address: u32) -> Option<u16> {
+    let offset = address.checked_sub(load_address)? as usize;
+    match (program.get(offset), program.get(offset + 1)) {
+        (Some(&high), Some(&low)) => Some((high as u16) << 8 | low as u16),
+        _ => None,
+    }
+}
+
+fn decode(pair: &[u8]) -> Option<Instruction> {
+    match *pair {
+        [high, low] => Instruction::decode((high as u16) << 8 | low as u16),
+        _ => None,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:#04X}", byte)).collect::<Vec<_>>().join(", ")
+}
+
+/// Writes an assembler statement, with the comment lined up after it.
+fn statement(code: &mut String, text: &str, comment: Option<&str>) {
+    match comment {
+        Some(comment) => writeln!(code, "    {:<24}; {}", text, comment).unwrap(),
+        None => writeln!(code, "    {}", text).unwrap(),
+    }
+}
+
+/// The lines of `block`.
+fn block_lines<'l>(lines: &'l BTreeMap<u16, Line>, block: &Block) -> impl Iterator<Item = Line> + 'l {
+    let end = block.end;
+    lines.range(block.start..).map(|(_, &line)| line).take_while(move |line| (line.address as u32) < end)
+}
+
+/// Where I points after `line`, given where it pointed before, or `None`
+/// once that depends on registers.
+fn pointer(line: &Line, i: Option<u16>, quirks: Quirks) -> Option<u16> {
+    match line.instruction {
+        Some(Instruction::SetI(nnn)) => Some(nnn),
+        Some(Instruction::SetLongI) => Some(line.long),
+        Some(Instruction::AddI(_)) | Some(Instruction::Font(_)) | Some(Instruction::LargeFont(_)) => None,
+        Some(Instruction::Store(_)) | Some(Instruction::Load(_)) if quirks.load_store => None,
+        _ => i,
+    }
+}
+
+/// Sprites a DXYN draws with I inside the program, following I through
+/// the blocks until paths that disagree on it meet. Calls are taken to
+/// change I.
+fn sprites(graph: &Graph, lines: &BTreeMap<u16, Line>, quirks: Quirks, inside: &dyn Fn(u16) -> bool) -> Vec<Sprite> {
+    let blocks: BTreeMap<u16, &Block> = graph.blocks.iter().map(|block| (block.start, block)).collect();
+
+    // Where I points on entering each block.
+    let mut entering: BTreeMap<u16, Option<u16>> = BTreeMap::new();
+    let mut pending = Vec::new();
+    for subroutine in &graph.subroutines {
+        entering.insert(subroutine.address, None);
+        pending.push(subroutine.address);
+    }
+    while let Some(start) = pending.pop() {
+        let block = match blocks.get(&start) {
+            Some(block) => block,
+            None => continue,
+        };
+        let leaving = match block.terminator {
+            Terminator::Call => None,
+            _ => block_lines(lines, block).fold(entering[&start], |i, line| pointer(&line, i, quirks)),
+        };
+
+        for edge in &block.successors {
+            let merged = match entering.get(&edge.target) {
+                Some(&before) if before != leaving => None,
+                _ => leaving,
+            };
+            if entering.get(&edge.target) != Some(&merged) {
+                entering.insert(edge.target, merged);
+                pending.push(edge.target);
+            }
+        }
+    }
+
+    let mut found: BTreeMap<u16, Sprite> = BTreeMap::new();
+    for block in &graph.blocks {
+        let mut i = entering.get(&block.start).cloned().unwrap_or(None);
+        for line in block_lines(lines, block) {
+            if let (Some(Instruction::Draw(_, _, n)), Some(address)) = (line.instruction, i) {
+                if inside(address) {
+                    let (width, height) = if n == 0 { (16, 16) } else { (8, n) };
+                    let sprite = found.entry(address).or_insert(Sprite { address, width, height });
+                    if sprite.width == width {
+                        sprite.height = sprite.height.max(height);
+                    }
+                }
+            }
+            i = pointer(&line, i, quirks);
+        }
+    }
+
+    found.into_values().collect()
+}
+
+/// The subroutines' code, each block going to the first subroutine that
+/// reaches it without passing through another's start.
+fn functions(graph: &Graph, lines: &BTreeMap<u16, Line>) -> Vec<Function> {
+    let starts: BTreeSet<u16> = graph.subroutines.iter().map(|subroutine| subroutine.address).collect();
+    let mut owned = BTreeSet::new();
+    let mut functions = Vec::new();
+
+    for subroutine in &graph.subroutines {
+        let mut blocks = Vec::new();
+        let mut pending = vec![subroutine.address];
+        while let Some(start) = pending.pop() {
+            if owned.contains(&start) || start != subroutine.address && starts.contains(&start) {
+                continue;
+            }
+            let block = match graph.blocks.iter().find(|block| block.start == start) {
+                Some(block) => block,
+                None => continue,
+            };
+            owned.insert(start);
+            blocks.push(block);
+            pending.extend(block.successors.iter().filter(|edge| edge.kind != EdgeKind::Call).map(|edge| edge.target));
+        }
+
+        let mut code: Vec<Line> = blocks.iter().flat_map(|block| block_lines(lines, block)).collect();
+        code.sort_by_key(|line| line.address);
+        code.dedup_by_key(|line| line.address);
+
+        let score = code.iter().any(|line| matches!(line.instruction, Some(Instruction::Bcd(_))));
+        let name = if subroutine.address == graph.entry || !score {
+            subroutine.name.clone()
+        } else {
+            format!("score_{:03X}", subroutine.address)
+        };
+        functions.push(Function { name, address: subroutine.address, lines: code, score });
+    }
+
+    functions
+}
+
+/// The program cut into code lines and runs of bytes, the runs split
+/// where a label is wanted or the region changes.
+fn items(graph: &Graph, program: &[u8], lines: &BTreeMap<u16, Line>, wanted: &BTreeMap<u16, String>) -> Vec<Item> {
+    let start = graph.load_address as u32;
+    let end = start + program.len() as u32;
+    let region = |address: u32| {
+        graph.regions.iter()
+            .find(|region| region.start as u32 <= address && address < region.end)
+            .map(|region| region.kind)
+    };
+
+    let mut items = Vec::new();
+    let mut address = start;
+    while address < end {
+        if let Some(&line) = lines.get(&(address as u16)) {
+            items.push(Item::Code(line));
+            address = line.end();
+            continue;
+        }
+
+        let from = address;
+        let kind = region(from);
+        address += 1;
+        while address < end
+            && !lines.contains_key(&(address as u16))
+            && !wanted.contains_key(&(address as u16))
+            && region(address) == kind
+        {
+            address += 1;
+        }
+        let bytes = program[(from - start) as usize..(address - start) as usize].to_vec();
+        items.push(Item::Bytes(from as u16, bytes, kind));
+    }
+
+    items
+}
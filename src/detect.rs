@@ -0,0 +1,312 @@
+use std::fmt;
+
+use toml::Value;
+
+use database::RomInfo;
+use platform::Platform;
+
+/// Memory of the platforms before XO-CHIP.
+const CLASSIC_MEMORY_SIZE: usize = 0x1000;
+
+/// How far past a load/store to look for another one, or a jump back over it,
+/// that relies on I having been advanced.
+const LOAD_STORE_WINDOW: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A guess at the platform and quirks of a ROM missing from the database.
+#[derive(Clone, Debug)]
+pub struct Detection {
+    pub platform: Platform,
+    pub confidence: Confidence,
+    /// Where the program was assumed to be loaded.
+    pub load_address: u16,
+    /// Set when the program appears to depend on FX55/FX65 advancing I.
+    pub load_store: bool,
+    /// Human readable reasons for the guess.
+    pub evidence: Vec<String>,
+}
+
+impl Detection {
+    /// The guess as a ROM database entry, so it is applied like one.
+    pub fn info(&self) -> RomInfo {
+        let mut info = RomInfo {
+            platform: Some(self.platform),
+            source: format!("platform detection ({} confidence)", self.confidence),
+            ..RomInfo::default()
+        };
+
+        if self.load_store {
+            info.quirks.insert("load_store".into(), Value::Boolean(true));
+        }
+
+        info
+    }
+}
+
+/// Statically scans a program loaded at `load_address` for opcodes only
+/// later interpreters support.
+///
+/// The scan is a linear sweep over every aligned word, so sprite data can
+/// produce false hits; a single hit is reported with low confidence.
+pub fn detect(program: &[u8], load_address: u16) -> Detection {
+    let mut schip = Hits::default();
+    let mut xochip = Hits::default();
+    let mut evidence = Vec::new();
+    let mut load_store = false;
+
+    let mut offset = 0;
+    while offset + 1 < program.len() {
+        let opcode = word(program, offset);
+        let addr = load_address as usize + offset;
+
+        if let Some(name) = schip_opcode(opcode) {
+            schip.add(name, addr);
+        }
+        if let Some(name) = xochip_opcode(opcode) {
+            xochip.add(name, addr);
+        }
+
+        if is_load_store(opcode) && !load_store {
+            if let Some((next, next_opcode)) = next_load_store(program, offset, load_address) {
+                load_store = true;
+                evidence.push(if next == offset {
+                    format!("{:04X} at {:#05x} runs again through a backward jump without resetting I", opcode, addr)
+                } else {
+                    format!(
+                        "{:04X} at {:#05x} is followed by {:04X} at {:#05x} without resetting I",
+                        opcode, addr, next_opcode, load_address as usize + next,
+                    )
+                });
+            }
+        }
+
+        // F000 NNNN is the only four byte instruction.
+        offset += if opcode == 0xf000 { 4 } else { 2 };
+    }
+
+    let (platform, confidence) = if load_address as usize + program.len() > CLASSIC_MEMORY_SIZE {
+        evidence.insert(0, format!("{} bytes at {:#05x} do not fit in 4 KiB of memory", program.len(), load_address));
+        (Platform::Xochip, Confidence::High)
+    } else if !xochip.is_empty() {
+        (Platform::Xochip, xochip.confidence())
+    } else if !schip.is_empty() {
+        (Platform::Schip, schip.confidence())
+    } else {
+        evidence.insert(0, "no SUPER-CHIP or XO-CHIP opcodes found".to_string());
+        (Platform::Vip, Confidence::Medium)
+    };
+
+    evidence.extend(xochip.describe());
+    evidence.extend(schip.describe());
+
+    Detection {
+        platform,
+        confidence,
+        load_address,
+        load_store,
+        evidence,
+    }
+}
+
+/// Occurrences of extended opcodes, grouped by mnemonic.
+#[derive(Default)]
+struct Hits {
+    found: Vec<(&'static str, Vec<usize>)>,
+}
+
+impl Hits {
+    fn add(&mut self, name: &'static str, addr: usize) {
+        match self.found.iter_mut().find(|entry| entry.0 == name) {
+            Some(entry) => entry.1.push(addr),
+            None => self.found.push((name, vec![addr])),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.found.is_empty()
+    }
+
+    fn confidence(&self) -> Confidence {
+        let occurrences: usize = self.found.iter().map(|entry| entry.1.len()).sum();
+
+        if self.found.len() >= 2 || occurrences >= 3 {
+            Confidence::High
+        } else if occurrences == 2 {
+            Confidence::Medium
+        } else {
+            Confidence::Low
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        self.found.iter()
+            .map(|(name, addrs)| {
+                let shown: Vec<String> = addrs.iter().take(4).map(|addr| format!("{:#05x}", addr)).collect();
+                let more = if addrs.len() > shown.len() { ", ..." } else { "" };
+                format!("{} at {}{}", name, shown.join(", "), more)
+            })
+            .collect()
+    }
+}
+
+fn schip_opcode(opcode: u16) -> Option<&'static str> {
+    match opcode {
+        0x00fb => Some("00FB (scroll right)"),
+        0x00fc => Some("00FC (scroll left)"),
+        0x00fd => Some("00FD (exit)"),
+        0x00fe => Some("00FE (low resolution)"),
+        0x00ff => Some("00FF (high resolution)"),
+        _ if opcode & 0xfff0 == 0x00c0 && opcode & 0xf != 0 => Some("00CN (scroll down)"),
+        _ if opcode & 0xf00f == 0xd000 => Some("DXY0 (16x16 sprite)"),
+        _ if opcode & 0xf0ff == 0xf030 => Some("FX30 (large font)"),
+        _ if opcode & 0xf0ff == 0xf075 => Some("FX75 (save flags)"),
+        _ if opcode & 0xf0ff == 0xf085 => Some("FX85 (load flags)"),
+        _ => None,
+    }
+}
+
+fn xochip_opcode(opcode: u16) -> Option<&'static str> {
+    match opcode {
+        0xf000 => Some("F000 (long I)"),
+        0xf002 => Some("F002 (audio pattern)"),
+        _ if opcode & 0xfff0 == 0x00d0 && opcode & 0xf != 0 => Some("00DN (scroll up)"),
+        _ if opcode & 0xf00f == 0x5002 => Some("5XY2 (save range)"),
+        _ if opcode & 0xf00f == 0x5003 => Some("5XY3 (load range)"),
+        _ if opcode & 0xf0ff == 0xf001 => Some("FN01 (plane select)"),
+        _ if opcode & 0xf0ff == 0xf03a => Some("FX3A (pitch)"),
+        _ => None,
+    }
+}
+
+fn is_load_store(opcode: u16) -> bool {
+    opcode & 0xf0ff == 0xf055 || opcode & 0xf0ff == 0xf065
+}
+
+fn sets_i(opcode: u16) -> bool {
+    opcode & 0xf000 == 0xa000 || opcode & 0xf0ff == 0xf01e || opcode & 0xf0ff == 0xf029
+}
+
+fn word(program: &[u8], offset: usize) -> u16 {
+    (program[offset] as u16) << 8 | program[offset + 1] as u16
+}
+
+/// Finds the next FX55/FX65 to run after the one at `offset` without I being
+/// set in between: either shortly after it on the straight-line path, or
+/// itself again through a backward jump. Jump targets are addresses in
+/// memory, with the program at `load_address`.
+fn next_load_store(program: &[u8], offset: usize, load_address: u16) -> Option<(usize, u16)> {
+    let start = load_address as usize;
+    let mut next = offset + 2;
+
+    for _ in 0..LOAD_STORE_WINDOW {
+        if next + 1 >= program.len() {
+            return None;
+        }
+
+        let opcode = word(program, next);
+
+        if is_load_store(opcode) {
+            return Some((next, opcode));
+        }
+
+        if opcode & 0xf000 == 0x1000 {
+            let target = (opcode & 0x0fff) as usize;
+            let looped = target >= start && target - start <= offset
+                && (target - start..next).step_by(2).all(|at| !sets_i(word(program, at)));
+
+            return if looped { Some((offset, word(program, offset))) } else { None };
+        }
+
+        let branches = matches!(opcode & 0xf000, 0x2000 | 0xb000) || opcode == 0x00ee;
+
+        if sets_i(opcode) || branches {
+            return None;
+        }
+
+        next += 2;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_the_vip_without_extended_opcodes() {
+        let detection = detect(&[0x00, 0xE0, 0xA2, 0x00, 0x12, 0x02], 0x200);
+
+        assert_eq!(detection.platform, Platform::Vip);
+        assert_eq!(detection.confidence, Confidence::Medium);
+        assert!(!detection.load_store);
+    }
+
+    #[test]
+    fn grows_more_confident_with_more_hits() {
+        let one = detect(&[0x00, 0xFF, 0x12, 0x02], 0x200);
+        assert_eq!((one.platform, one.confidence), (Platform::Schip, Confidence::Low));
+        assert!(one.evidence.contains(&"00FF (high resolution) at 0x200".to_string()));
+
+        let two = detect(&[0x00, 0xFF, 0xD0, 0x10], 0x200);
+        assert_eq!((two.platform, two.confidence), (Platform::Schip, Confidence::High));
+
+        // XO-CHIP opcodes win over SUPER-CHIP ones, which it also has.
+        let xochip = detect(&[0x00, 0xFF, 0xF1, 0x01, 0xF2, 0x01], 0x200);
+        assert_eq!((xochip.platform, xochip.confidence), (Platform::Xochip, Confidence::Medium));
+    }
+
+    #[test]
+    fn takes_programs_too_large_for_4_kib_for_xo_chip() {
+        let detection = detect(&[0; 0xE01], 0x200);
+
+        assert_eq!((detection.platform, detection.confidence), (Platform::Xochip, Confidence::High));
+        assert_eq!(detection.evidence[0], "3585 bytes at 0x200 do not fit in 4 KiB of memory");
+        assert_eq!(detect(&[0; 0xA01], 0x600).platform, Platform::Xochip);
+    }
+
+    #[test]
+    fn finds_loads_and_stores_relying_on_i_advancing() {
+        // F265 then F255 with no write to I between them.
+        let detection = detect(&[0xA3, 0x00, 0xF2, 0x65, 0x70, 0x01, 0xF2, 0x55], 0x200);
+        assert!(detection.load_store);
+        assert_eq!(detection.evidence[1], "F265 at 0x202 is followed by F255 at 0x206 without resetting I");
+        assert_eq!(detection.info().quirks.get("load_store"), Some(&Value::Boolean(true)));
+
+        // Setting I in between resets it.
+        assert!(!detect(&[0xF2, 0x65, 0xA3, 0x00, 0xF2, 0x55], 0x200).load_store);
+    }
+
+    #[test]
+    fn follows_backward_jumps_from_the_load_address() {
+        // 0x600: LD I, 0x700; 0x602: LD V2, [I]; 0x604: JP 0x602
+        let program = [0xA7, 0x00, 0xF2, 0x65, 0x16, 0x02];
+
+        let detection = detect(&program, 0x600);
+        assert!(detection.load_store);
+        assert_eq!(detection.evidence[1], "F265 at 0x602 runs again through a backward jump without resetting I");
+
+        // Loaded at 0x200 the jump leaves the program.
+        assert!(!detect(&program, 0x200).load_store);
+
+        // A jump back to before LD I sets I again on the way.
+        assert!(!detect(&[0xA7, 0x00, 0xF2, 0x65, 0x16, 0x00], 0x600).load_store);
+    }
+}
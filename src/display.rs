@@ -0,0 +1,326 @@
+/// Display size in the CHIP-8 resolution.
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+
+/// Display size in the SUPER-CHIP and XO-CHIP high resolution.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Bit planes; XO-CHIP draws in two for four colors.
+pub const PLANES: usize = 2;
+
+/// A monochrome or four-color display, packed one bit per pixel.
+///
+/// Each row of each plane is a `u128` with the leftmost pixel in the most
+/// significant bit, so a sprite row is drawn with a shift and a single XOR
+/// and collisions are found with an AND. In the low resolution only the
+/// upper 64 bits of a row are used.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    hires: bool,
+    /// Planes affected by drawing, clearing and scrolling, one bit each.
+    selected: u8,
+    planes: [[u128; HIRES_HEIGHT]; PLANES],
+}
+
+impl Default for Framebuffer {
+    fn default() -> Framebuffer {
+        Framebuffer {
+            hires: false,
+            selected: 1,
+            planes: [[0; HIRES_HEIGHT]; PLANES],
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Length of `to_bytes` output.
+    pub const ENCODED_SIZE: usize = 2 + PLANES * HIRES_HEIGHT * 16;
+
+    pub fn new() -> Framebuffer {
+        Framebuffer::default()
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switches resolution, which clears the display.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.planes = [[0; HIRES_HEIGHT]; PLANES];
+    }
+
+    /// Planes affected by drawing, one bit each.
+    pub fn selected(&self) -> u8 {
+        self.selected
+    }
+
+    pub fn select(&mut self, planes: u8) {
+        self.selected = planes & ((1 << PLANES) - 1);
+    }
+
+    /// The color index of a pixel: bit N is set when plane N is lit.
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let bit = 1 << (127 - x);
+
+        (0..PLANES).fold(0, |color, plane| {
+            color | ((self.planes[plane][y] & bit != 0) as u8) << plane
+        })
+    }
+
+    /// The visible rows of a plane, leftmost pixel in the top bit.
+    pub fn rows(&self, plane: usize) -> impl Iterator<Item = u128> + '_ {
+        self.planes[plane][..self.height()].iter().cloned()
+    }
+
+    /// Whether any plane has a lit pixel in row `y`.
+    pub fn row_lit(&self, y: usize) -> bool {
+        self.planes.iter().any(|plane| plane[y] != 0)
+    }
+
+    /// Clears the selected planes.
+    pub fn clear(&mut self) {
+        for plane in self.selected_planes() {
+            self.planes[plane] = [0; HIRES_HEIGHT];
+        }
+    }
+
+    /// XORs a sprite onto the selected planes at (`x`, `y`), returning
+    /// whether a lit pixel was turned off.
+    ///
+    /// `sprite` holds the rows for each selected plane in turn, one byte
+    /// per row, or two when `wide` for 16-pixel sprites. Parts past the
+    /// edge are dropped when `clip`, and wrap around otherwise.
+    pub fn draw(&mut self, x: u8, y: u8, sprite: &[u8], wide: bool, clip: bool) -> bool {
+        let (width, height) = (self.width(), self.height());
+        let x = x as usize % width;
+        let y = y as usize % height;
+
+        let row_bytes = if wide { 2 } else { 1 };
+        let count = self.selected_planes().count();
+        if count == 0 {
+            return false;
+        }
+        let rows = sprite.len() / row_bytes / count;
+
+        let mut collision = false;
+        let mut data = sprite.chunks(row_bytes);
+
+        for plane in self.selected_planes() {
+            for offset in 0..rows {
+                let bytes = data.next().unwrap();
+                let bits = if wide {
+                    (bytes[0] as u128) << 120 | (bytes[1] as u128) << 112
+                } else {
+                    (bytes[0] as u128) << 120
+                };
+
+                let row = y + offset;
+                if row >= height && clip {
+                    continue;
+                }
+
+                let line = self.place(bits, x, clip);
+                let target = &mut self.planes[plane][row % height];
+
+                collision |= *target & line != 0;
+                *target ^= line;
+            }
+        }
+
+        collision
+    }
+
+    /// Scrolls the selected planes down by `n` rows.
+    pub fn scroll_down(&mut self, n: usize) {
+        let height = self.height();
+        for plane in self.selected_planes() {
+            let rows = &mut self.planes[plane][..height];
+            let n = n.min(height);
+            rows.copy_within(..height - n, n);
+            rows[..n].iter_mut().for_each(|row| *row = 0);
+        }
+    }
+
+    /// Scrolls the selected planes up by `n` rows.
+    pub fn scroll_up(&mut self, n: usize) {
+        let height = self.height();
+        for plane in self.selected_planes() {
+            let rows = &mut self.planes[plane][..height];
+            let n = n.min(height);
+            rows.copy_within(n.., 0);
+            rows[height - n..].iter_mut().for_each(|row| *row = 0);
+        }
+    }
+
+    /// Scrolls the selected planes right by four pixels.
+    pub fn scroll_right(&mut self) {
+        let mask = self.row_mask();
+        for plane in self.selected_planes() {
+            for row in self.planes[plane].iter_mut() {
+                *row = (*row >> 4) & mask;
+            }
+        }
+    }
+
+    /// Scrolls the selected planes left by four pixels.
+    pub fn scroll_left(&mut self) {
+        for plane in self.selected_planes() {
+            for row in self.planes[plane].iter_mut() {
+                *row <<= 4;
+            }
+        }
+    }
+
+    /// A flat encoding for snapshots: resolution, selected planes, then
+    /// every row of every plane.
+    #[cfg(feature = "std")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.hires as u8, self.selected];
+        for plane in &self.planes {
+            for row in plane.iter() {
+                bytes.extend_from_slice(&row.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decodes `to_bytes` output, or `None` if it is too short.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Framebuffer> {
+        if bytes.len() < Framebuffer::ENCODED_SIZE {
+            return None;
+        }
+
+        let mut framebuffer = Framebuffer {
+            hires: bytes[0] != 0,
+            selected: bytes[1],
+            planes: [[0; HIRES_HEIGHT]; PLANES],
+        };
+
+        let mut rows = bytes[2..Framebuffer::ENCODED_SIZE].chunks(16);
+        for plane in framebuffer.planes.iter_mut() {
+            for row in plane.iter_mut() {
+                let mut word = [0; 16];
+                word.copy_from_slice(rows.next().unwrap());
+                *row = u128::from_le_bytes(word);
+            }
+        }
+
+        Some(framebuffer)
+    }
+
+    fn selected_planes(&self) -> impl Iterator<Item = usize> {
+        let selected = self.selected;
+        (0..PLANES).filter(move |plane| selected & 1 << plane != 0)
+    }
+
+    /// Bits of a row that are on screen.
+    fn row_mask(&self) -> u128 {
+        !0 << (128 - self.width())
+    }
+
+    /// Moves sprite bits from the left edge to column `x`.
+    fn place(&self, bits: u128, x: usize, clip: bool) -> u128 {
+        if clip {
+            (bits >> x) & self.row_mask()
+        } else if self.hires {
+            bits.rotate_right(x as u32)
+        } else {
+            let row = (bits >> 64) as u64;
+            (row.rotate_right(x as u32) as u128) << 64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The lit pixels of row `y`, as `#` and `.`.
+    fn row(framebuffer: &Framebuffer, y: usize) -> String {
+        (0..framebuffer.width()).map(|x| if framebuffer.pixel(x, y) != 0 { '#' } else { '.' }).collect()
+    }
+
+    #[test]
+    fn xors_sprites_and_reports_collisions() {
+        let mut framebuffer = Framebuffer::new();
+
+        assert!(!framebuffer.draw(0, 0, &[0xF0], false, true));
+        assert!(row(&framebuffer, 0).starts_with("####...."));
+
+        // Only turning a lit pixel off is a collision.
+        assert!(!framebuffer.draw(4, 0, &[0xF0], false, true));
+        assert!(framebuffer.draw(2, 0, &[0xC0], false, true));
+        assert!(row(&framebuffer, 0).starts_with("##..####."));
+
+        framebuffer.clear();
+        assert!(!framebuffer.row_lit(0));
+    }
+
+    #[test]
+    fn wraps_the_starting_position() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.draw(64 + 2, 32 + 1, &[0x80], false, true);
+
+        assert_eq!(framebuffer.pixel(2, 1), 1);
+    }
+
+    #[test]
+    fn clips_at_the_edges() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.draw(60, 30, &[0xFF, 0xFF, 0xFF], false, true);
+
+        assert!(row(&framebuffer, 30).ends_with("....####"));
+        assert!(row(&framebuffer, 31).ends_with("....####"));
+        assert!(!framebuffer.row_lit(0));
+        assert!(row(&framebuffer, 30).starts_with("........"));
+    }
+
+    #[test]
+    fn wraps_around_the_edges_without_clipping() {
+        let mut framebuffer = Framebuffer::new();
+        assert!(!framebuffer.draw(60, 31, &[0xFF, 0x81], false, false));
+
+        assert_eq!(row(&framebuffer, 31), format!("####{}####", ".".repeat(56)));
+        assert_eq!(row(&framebuffer, 0), format!("...#{}#...", ".".repeat(56)));
+        // Nothing leaks into the unused half of a low resolution row.
+        assert_eq!(framebuffer.rows(0).next(), Some(1u128 << 124 | 1u128 << 67));
+    }
+
+    #[test]
+    fn draws_wide_sprites_in_high_resolution() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.set_hires(true);
+        framebuffer.draw(120, 63, &[0xFF, 0x01], true, false);
+
+        assert_eq!(row(&framebuffer, 63), format!("{}#{}########", ".".repeat(7), ".".repeat(112)));
+        assert!(!framebuffer.row_lit(0));
+    }
+
+    #[test]
+    fn draws_each_selected_plane_from_its_own_rows() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.select(3);
+        // A row for the first plane, then one for the second.
+        framebuffer.draw(0, 0, &[0xC0, 0xA0], false, true);
+
+        assert_eq!((0..4).map(|x| framebuffer.pixel(x, 0)).collect::<Vec<_>>(), [3, 1, 2, 0]);
+
+        framebuffer.select(2);
+        assert!(framebuffer.draw(0, 0, &[0x80], false, true));
+        assert_eq!(framebuffer.pixel(0, 0), 1);
+
+        framebuffer.select(0);
+        assert!(!framebuffer.draw(0, 0, &[0xFF], false, true));
+        assert_eq!(framebuffer.pixel(1, 0), 1);
+    }
+}
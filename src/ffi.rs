@@ -0,0 +1,347 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use config::{Config, Sources};
+use cpu::Fault;
+use machine::{self, Machine};
+use rom::Rom;
+
+/// What a call of the C API came to.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Status {
+    Ok = 0,
+    /// A required pointer was NULL.
+    NullPointer = 1,
+    /// No ROM is loaded.
+    NoRom = 2,
+    /// The ROM is unreadable or does not fit in memory.
+    InvalidRom = 3,
+    /// The configuration for the ROM is invalid.
+    InvalidConfig = 4,
+    /// The buffer is too small; the length needed was stored.
+    BufferTooSmall = 5,
+    /// The state was not saved from a machine like this one.
+    InvalidState = 6,
+    /// The program ran an instruction no platform defines.
+    UnknownInstruction = 7,
+    /// The program accessed memory its policies forbid, or that does not
+    /// exist.
+    MemoryFault = 8,
+    /// The program called with all 16 stack levels in use.
+    StackOverflow = 9,
+    /// The program returned with nothing on the stack.
+    StackUnderflow = 10,
+    /// A bug in the emulator, caught before it crossed into C.
+    Internal = 11,
+}
+
+impl From<Fault> for Chip8Status {
+    fn from(fault: Fault) -> Chip8Status {
+        match fault {
+            Fault::UnknownInstruction(_) => Chip8Status::UnknownInstruction,
+            Fault::Inaccessible(..) | Fault::Unmapped(_) => Chip8Status::MemoryFault,
+            Fault::StackOverflow => Chip8Status::StackOverflow,
+            Fault::StackUnderflow => Chip8Status::StackUnderflow,
+        }
+    }
+}
+
+/// A CHIP-8 machine for C programs, made by `chip8_create`.
+///
+/// It runs a frame at a time like `Machine`. After a fault the program
+/// counter stays on the faulting instruction, so every further frame
+/// faults the same way until a reset or a state is loaded.
+pub struct Chip8Machine {
+    machine: Option<Machine>,
+    /// Details of the last error, for `chip8_last_error`.
+    error: CString,
+}
+
+impl Chip8Machine {
+    fn fail<S: Into<String>>(&mut self, status: Chip8Status, message: S) -> Chip8Status {
+        self.error = CString::new(message.into().replace('\0', "")).unwrap_or_default();
+        status
+    }
+
+    fn machine(&mut self) -> Result<&mut Machine, Chip8Status> {
+        match self.machine {
+            Some(ref mut machine) => Ok(machine),
+            None => {
+                self.error = CString::new("no ROM is loaded").unwrap();
+                Err(Chip8Status::NoRom)
+            }
+        }
+    }
+}
+
+/// Runs `call` on the machine behind `machine`, turning a NULL pointer
+/// and panics into status codes.
+unsafe fn with_machine<F>(machine: *mut Chip8Machine, call: F) -> Chip8Status
+    where F: FnOnce(&mut Chip8Machine) -> Result<(), Chip8Status>
+{
+    let machine = match machine.as_mut() {
+        Some(machine) => machine,
+        None => return Chip8Status::NullPointer,
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| call(&mut *machine))) {
+        Ok(Ok(())) => Chip8Status::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => machine.fail(Chip8Status::Internal, "internal error"),
+    }
+}
+
+/// Copies `data` to `buffer`, storing its length in `length` whether or
+/// not it fits.
+unsafe fn copy_out(data: &[u8], buffer: *mut u8, size: usize, length: *mut usize) -> Result<(), Chip8Status> {
+    if !length.is_null() {
+        *length = data.len();
+    }
+    if buffer.is_null() || size < data.len() {
+        return Err(Chip8Status::BufferTooSmall);
+    }
+    ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+
+    Ok(())
+}
+
+/// Makes a machine with no ROM loaded. Free it with `chip8_destroy`.
+#[no_mangle]
+pub extern "C" fn chip8_create() -> *mut Chip8Machine {
+    Box::into_raw(Box::new(Chip8Machine { machine: None, error: CString::default() }))
+}
+
+/// Frees a machine. Does nothing given NULL.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create` and not have been destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(machine: *mut Chip8Machine) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+/// Loads a ROM image, replacing any program loaded before. The platform,
+/// quirks and speed come from the bundled ROM database or detection, on
+/// top of the built-in defaults; no config file is read.
+///
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(machine: *mut Chip8Machine, data: *const u8, size: usize) -> Chip8Status {
+    chip8_load_rom_with_config(machine, data, size, ptr::null())
+}
+
+/// Loads a ROM image like `chip8_load_rom`, then applies `config`, a
+/// NUL-terminated config file as TOML text, on top. A NULL `config` is
+/// the same as `chip8_load_rom`.
+///
+/// # Safety
+///
+/// `data` must point to `size` readable bytes, and `config` be NULL or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom_with_config(
+    machine: *mut Chip8Machine,
+    data: *const u8,
+    size: usize,
+    config: *const c_char,
+) -> Chip8Status {
+    with_machine(machine, |machine| {
+        if data.is_null() {
+            return Err(Chip8Status::NullPointer);
+        }
+        let text = match config.as_ref() {
+            Some(_) => match CStr::from_ptr(config).to_str() {
+                Ok(text) => Some(text.to_string()),
+                Err(err) => return Err(machine.fail(Chip8Status::InvalidConfig, format!("config is not UTF-8: {}", err))),
+            },
+            None => None,
+        };
+
+        let rom = Rom::from_bytes(slice::from_raw_parts(data, size), None)
+            .map_err(|err| machine.fail(Chip8Status::InvalidRom, err.to_string()))?;
+        let sources = Sources { text, ignore_default_file: true, ..Sources::default() };
+        let config = Config::load(&sources, Some(&rom))
+            .map_err(|err| machine.fail(Chip8Status::InvalidConfig, err.to_string()))?;
+        let loaded = Machine::new(rom, &config).map_err(|err| match err {
+            machine::Error::Config(err) => machine.fail(Chip8Status::InvalidConfig, err.to_string()),
+            machine::Error::Rom(err) => machine.fail(Chip8Status::InvalidRom, err.to_string()),
+        })?;
+
+        machine.machine = Some(loaded);
+        Ok(())
+    })
+}
+
+/// Restarts the program from its pristine image.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_reset(machine: *mut Chip8Machine) -> Chip8Status {
+    with_machine(machine, |machine| {
+        machine.machine()?.reset();
+        Ok(())
+    })
+}
+
+/// Runs one frame, a sixtieth of a second of emulated time. Stores
+/// whether the buzzer sounded in `sound`, unless it is NULL.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`, and `sound` be NULL or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(machine: *mut Chip8Machine, sound: *mut bool) -> Chip8Status {
+    with_machine(machine, |machine| {
+        let result = machine.machine()?.run_frame();
+        match result {
+            Ok(beeped) => {
+                if !sound.is_null() {
+                    *sound = beeped;
+                }
+                Ok(())
+            }
+            Err(fault) => {
+                let pc = machine.machine()?.cpu().pc();
+                Err(machine.fail(fault.into(), format!("{} at {:#05x}", fault, pc)))
+            }
+        }
+    })
+}
+
+/// Sets the pressed keys, bit N for key N.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_keys(machine: *mut Chip8Machine, keys: u16) -> Chip8Status {
+    with_machine(machine, |machine| {
+        machine.machine()?.set_keys(keys);
+        Ok(())
+    })
+}
+
+/// Copies the display to `pixels`, one color index per pixel, row by row.
+/// Stores its size in `width` and `height`, which may be NULL: 64x32, or
+/// 128x64 in SUPER-CHIP's high resolution. A buffer of 128 * 64 bytes
+/// always fits.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`, `pixels` be NULL or point to
+/// `size` writable bytes, and `width` and `height` be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(
+    machine: *mut Chip8Machine,
+    pixels: *mut u8,
+    size: usize,
+    width: *mut usize,
+    height: *mut usize,
+) -> Chip8Status {
+    with_machine(machine, |machine| {
+        let display = machine.machine()?.display();
+        if !width.is_null() {
+            *width = display.width();
+        }
+        if !height.is_null() {
+            *height = display.height();
+        }
+
+        let frame: Vec<u8> = (0..display.height())
+            .flat_map(|y| (0..display.width()).map(move |x| display.pixel(x, y)))
+            .collect();
+        copy_out(&frame, pixels, size, ptr::null_mut())
+    })
+}
+
+/// Copies all of RAM to `buffer`, storing its length in `length`, which
+/// may be NULL.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`, `buffer` be NULL or point to
+/// `size` writable bytes, and `length` be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_memory(machine: *mut Chip8Machine, buffer: *mut u8, size: usize, length: *mut usize) -> Chip8Status {
+    with_machine(machine, |machine| copy_out(machine.machine()?.memory(), buffer, size, length))
+}
+
+/// Saves the whole machine state to `buffer`, storing its length in
+/// `length`, which may be NULL. Call with a NULL buffer to learn the
+/// length.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`, `buffer` be NULL or point to
+/// `size` writable bytes, and `length` be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(machine: *mut Chip8Machine, buffer: *mut u8, size: usize, length: *mut usize) -> Chip8Status {
+    with_machine(machine, |machine| copy_out(&machine.machine()?.save_state(), buffer, size, length))
+}
+
+/// Restores a state from `chip8_save_state` of a machine with the same
+/// memory size.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`, and `data` point to `size`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(machine: *mut Chip8Machine, data: *const u8, size: usize) -> Chip8Status {
+    with_machine(machine, |machine| {
+        if data.is_null() {
+            return Err(Chip8Status::NullPointer);
+        }
+
+        let result = machine.machine()?.load_state(slice::from_raw_parts(data, size));
+        result.map_err(|err| machine.fail(Chip8Status::InvalidState, err.to_string()))
+    })
+}
+
+/// Details of the machine's last error, valid until the next call with
+/// the machine. Empty before any error.
+///
+/// # Safety
+///
+/// `machine` must come from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_last_error(machine: *const Chip8Machine) -> *const c_char {
+    match machine.as_ref() {
+        Some(machine) => machine.error.as_ptr(),
+        None => b"no machine\0".as_ptr() as *const c_char,
+    }
+}
+
+/// A description of a status code, as a static string.
+#[no_mangle]
+pub extern "C" fn chip8_status_message(status: c_int) -> *const c_char {
+    let message: &[u8] = match status {
+        0 => b"ok\0",
+        1 => b"null pointer\0",
+        2 => b"no ROM is loaded\0",
+        3 => b"invalid ROM\0",
+        4 => b"invalid configuration\0",
+        5 => b"buffer too small\0",
+        6 => b"invalid state\0",
+        7 => b"unknown instruction\0",
+        8 => b"memory fault\0",
+        9 => b"stack overflow\0",
+        10 => b"stack underflow\0",
+        11 => b"internal error\0",
+        _ => b"unknown status\0",
+    };
+
+    message.as_ptr() as *const c_char
+}
@@ -0,0 +1,315 @@
+use config::Rgb;
+use video::Image;
+
+/// A software upscaler applied to the composed display, before it is
+/// shown, saved as a screenshot or recorded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// One pixel per CHIP-8 pixel, scaled up by the renderer.
+    #[default]
+    None,
+    /// EPX/AdvMAME2x edge-directed scaling.
+    Scale2x,
+    /// AdvMAME3x edge-directed scaling.
+    Scale3x,
+    /// Scale2x applied twice.
+    Scale4x,
+    /// An HQ2x-style smoother: Scale2x with similar rather than equal
+    /// colors, blending the corners it rounds off.
+    Hq2x,
+    /// 3x with every third row darkened, like a CRT's scanlines.
+    Scanlines,
+    /// 3x with each column tinted red, green or blue, like an aperture
+    /// grille.
+    Dotmask,
+}
+
+impl Filter {
+    /// Every filter, in the order the hotkey cycles through them.
+    pub const ALL: [Filter; 7] = [
+        Filter::None,
+        Filter::Scale2x,
+        Filter::Scale3x,
+        Filter::Scale4x,
+        Filter::Hq2x,
+        Filter::Scanlines,
+        Filter::Dotmask,
+    ];
+
+    /// The filter after this one in `ALL`.
+    pub fn next(self) -> Filter {
+        let index = Filter::ALL.iter().position(|&filter| filter == self).unwrap();
+        Filter::ALL[(index + 1) % Filter::ALL.len()]
+    }
+
+    /// Output pixels per input pixel along each axis.
+    pub fn scale(self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scale2x | Filter::Hq2x => 2,
+            Filter::Scale3x | Filter::Scanlines | Filter::Dotmask => 3,
+            Filter::Scale4x => 4,
+        }
+    }
+
+    pub fn apply(self, image: &Image) -> Image {
+        match self {
+            Filter::None => image.clone(),
+            Filter::Scale2x => scale2x(image, |a, b| a == b, |_, _, _| None),
+            Filter::Scale3x => scale3x(image),
+            Filter::Scale4x => {
+                let twice = scale2x(image, |a, b| a == b, |_, _, _| None);
+                scale2x(&twice, |a, b| a == b, |_, _, _| None)
+            }
+            Filter::Hq2x => scale2x(image, similar, |e, a, b| Some(average(&[e, e, a, b]))),
+            Filter::Scanlines => mask(image, |_, row, color| if row == 2 { shade(color, 50) } else { color }),
+            Filter::Dotmask => mask(image, |column, row, color| {
+                let Rgb(r, g, b) = color;
+                let tinted = match column {
+                    0 => Rgb(r, shade1(g, 70), shade1(b, 70)),
+                    1 => Rgb(shade1(r, 70), g, shade1(b, 70)),
+                    _ => Rgb(shade1(r, 70), shade1(g, 70), b),
+                };
+                if row == 2 { shade(tinted, 75) } else { tinted }
+            }),
+        }
+    }
+}
+
+/// Neighbor of (`x`, `y`) at the given offset, clamped to the edges.
+fn at(image: &Image, x: usize, y: usize, dx: isize, dy: isize) -> Rgb {
+    let x = (x as isize + dx).max(0).min(image.width as isize - 1) as usize;
+    let y = (y as isize + dy).max(0).min(image.height as isize - 1) as usize;
+
+    image.pixels[y * image.width + x]
+}
+
+/// Scale2x with a pluggable color comparison, and optionally a blend for
+/// the corners it rounds off instead of copying the neighbor.
+fn scale2x<S, B>(image: &Image, same: S, corner: B) -> Image
+    where S: Fn(Rgb, Rgb) -> bool, B: Fn(Rgb, Rgb, Rgb) -> Option<Rgb>
+{
+    let mut output = Image::new(image.width * 2, image.height * 2);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let e = at(image, x, y, 0, 0);
+            let b = at(image, x, y, 0, -1);
+            let d = at(image, x, y, -1, 0);
+            let f = at(image, x, y, 1, 0);
+            let h = at(image, x, y, 0, 1);
+
+            // Rounds off a corner where `p` and `q` meet along an edge that
+            // `p_away` and `q_away` do not continue.
+            let round = |p: Rgb, q: Rgb, p_away: Rgb, q_away: Rgb, pick: Rgb| {
+                if same(p, q) && !same(p, p_away) && !same(q, q_away) {
+                    corner(e, p, q).unwrap_or(pick)
+                } else {
+                    e
+                }
+            };
+
+            let corners = [
+                round(d, b, h, f, d),
+                round(b, f, d, h, f),
+                round(d, h, b, f, d),
+                round(h, f, d, b, f),
+            ];
+
+            for (index, &color) in corners.iter().enumerate() {
+                let (ox, oy) = (x * 2 + index % 2, y * 2 + index / 2);
+                output.pixels[oy * output.width + ox] = color;
+            }
+        }
+    }
+
+    output
+}
+
+fn scale3x(image: &Image) -> Image {
+    let mut output = Image::new(image.width * 3, image.height * 3);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let a = at(image, x, y, -1, -1);
+            let b = at(image, x, y, 0, -1);
+            let c = at(image, x, y, 1, -1);
+            let d = at(image, x, y, -1, 0);
+            let e = at(image, x, y, 0, 0);
+            let f = at(image, x, y, 1, 0);
+            let g = at(image, x, y, -1, 1);
+            let h = at(image, x, y, 0, 1);
+            let i = at(image, x, y, 1, 1);
+
+            let block = if b != h && d != f {
+                [
+                    if d == b { d } else { e },
+                    if (d == b && e != c) || (b == f && e != a) { b } else { e },
+                    if b == f { f } else { e },
+                    if (d == b && e != g) || (d == h && e != a) { d } else { e },
+                    e,
+                    if (b == f && e != i) || (h == f && e != c) { f } else { e },
+                    if d == h { d } else { e },
+                    if (d == h && e != i) || (h == f && e != g) { h } else { e },
+                    if h == f { f } else { e },
+                ]
+            } else {
+                [e; 9]
+            };
+
+            for (index, &color) in block.iter().enumerate() {
+                let (ox, oy) = (x * 3 + index % 3, y * 3 + index / 3);
+                output.pixels[oy * output.width + ox] = color;
+            }
+        }
+    }
+
+    output
+}
+
+/// Scales by 3, passing each output pixel's column and row within its
+/// 3x3 block to `effect`.
+fn mask<F: Fn(usize, usize, Rgb) -> Rgb>(image: &Image, effect: F) -> Image {
+    let mut output = Image::new(image.width * 3, image.height * 3);
+
+    for oy in 0..output.height {
+        for ox in 0..output.width {
+            let color = image.pixels[(oy / 3) * image.width + ox / 3];
+            output.pixels[oy * output.width + ox] = effect(ox % 3, oy % 3, color);
+        }
+    }
+
+    output
+}
+
+/// Whether two colors are close enough to count as one edge for Hq2x.
+fn similar(a: Rgb, b: Rgb) -> bool {
+    let distance = |p: u8, q: u8| (p as i32 - q as i32).abs();
+
+    distance(a.0, b.0) + distance(a.1, b.1) + distance(a.2, b.2) < 48
+}
+
+fn average(colors: &[Rgb]) -> Rgb {
+    let count = colors.len() as u32;
+    let sum = colors.iter().fold((0, 0, 0), |(r, g, b), color| {
+        (r + color.0 as u32, g + color.1 as u32, b + color.2 as u32)
+    });
+
+    Rgb((sum.0 / count) as u8, (sum.1 / count) as u8, (sum.2 / count) as u8)
+}
+
+/// `color` at `percent` brightness.
+fn shade(color: Rgb, percent: u32) -> Rgb {
+    Rgb(shade1(color.0, percent), shade1(color.1, percent), shade1(color.2, percent))
+}
+
+fn shade1(channel: u8, percent: u32) -> u8 {
+    (channel as u32 * percent / 100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgb = Rgb(255, 255, 255);
+    const BLACK: Rgb = Rgb(0, 0, 0);
+
+    fn image(rows: &[&str]) -> Image {
+        let mut image = Image::new(rows[0].len(), rows.len());
+        image.pixels = rows.iter()
+            .flat_map(|row| row.chars().map(|pixel| if pixel == '#' { WHITE } else { BLACK }))
+            .collect();
+        image
+    }
+
+    /// White as `#`, black as `.` and anything else as `%`.
+    fn rows(image: &Image) -> Vec<String> {
+        image.pixels.chunks(image.width)
+            .map(|row| row.iter().map(|&color| match color {
+                WHITE => '#',
+                BLACK => '.',
+                _ => '%',
+            }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn rounds_off_diagonals_with_scale2x() {
+        let output = Filter::Scale2x.apply(&image(&["#.", ".#"]));
+
+        assert_eq!((output.width, output.height), (4, 4));
+        assert_eq!(rows(&output), ["##..", "#.#.", ".#.#", "..##"]);
+    }
+
+    #[test]
+    fn keeps_straight_lines_straight() {
+        let output = Filter::Scale2x.apply(&image(&["....", "####", "...."]));
+
+        assert_eq!(rows(&output), ["........", "........", "########", "########", "........", "........"]);
+    }
+
+    #[test]
+    fn blends_the_corners_it_rounds_off_with_hq2x() {
+        let output = Filter::Hq2x.apply(&image(&["#.", ".#"]));
+
+        assert_eq!(rows(&output), ["##..", "#%%.", ".%%#", "..##"]);
+        assert_eq!(output.pixels[5], Rgb(127, 127, 127));
+    }
+
+    #[test]
+    fn treats_similar_colors_as_one_edge_with_hq2x() {
+        let mut input = image(&["#.", ".#"]);
+        input.pixels[3] = Rgb(250, 250, 250);
+
+        // Scale2x sees three colors and keeps the blocks square.
+        assert_eq!(rows(&Filter::Scale2x.apply(&input))[1], "#...");
+        assert_eq!(rows(&Filter::Hq2x.apply(&input))[1], "#%%.");
+    }
+
+    #[test]
+    fn rounds_off_diagonals_with_scale3x() {
+        let output = Filter::Scale3x.apply(&image(&["#.", ".#"]));
+
+        assert_eq!(rows(&output), ["###...", "##.#..", "#..##.", ".##..#", "..#.##", "...###"]);
+        assert_eq!(rows(&Filter::Scale3x.apply(&image(&["...", ".#.", "..."])))[3..6], ["...###...", "...###...", "...###..."]);
+    }
+
+    #[test]
+    fn applies_scale2x_twice_for_scale4x() {
+        let output = Filter::Scale4x.apply(&image(&["#.", ".#"]));
+
+        assert_eq!(rows(&output), [
+            "####....", "###.#...", "##..##..", "#...###.",
+            ".###...#", "..##..##", "...#.###", "....####",
+        ]);
+    }
+
+    #[test]
+    fn darkens_every_third_row_with_scanlines() {
+        let output = Filter::Scanlines.apply(&image(&["#."]));
+
+        assert_eq!(rows(&output), ["###...", "###...", "%%%..."]);
+        assert_eq!(output.pixels[12], Rgb(127, 127, 127));
+    }
+
+    #[test]
+    fn tints_columns_red_green_and_blue_with_dotmask() {
+        let output = Filter::Dotmask.apply(&image(&["#."]));
+
+        assert_eq!(output.pixels[..3], [Rgb(255, 178, 178), Rgb(178, 255, 178), Rgb(178, 178, 255)]);
+        assert_eq!(output.pixels[12..15], [Rgb(191, 133, 133), Rgb(133, 191, 133), Rgb(133, 133, 191)]);
+        assert_eq!(rows(&output), ["%%%...", "%%%...", "%%%..."]);
+    }
+
+    #[test]
+    fn scales_by_each_filters_factor() {
+        let input = image(&["#..", ".#."]);
+
+        for &filter in &Filter::ALL {
+            let output = filter.apply(&input);
+            assert_eq!((output.width, output.height), (3 * filter.scale(), 2 * filter.scale()), "{:?}", filter);
+        }
+        assert_eq!(Filter::ALL.iter().fold(Filter::None, |filter, _| filter.next()), Filter::None);
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use serde_json;
+
+use instruction::Instruction;
+
+/// What an instruction does to the flow of control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    /// Carries on with the next instruction.
+    Next,
+    Jump(u16),
+    Call(u16),
+    Return,
+    /// Skips the next instruction when its condition holds.
+    Skip,
+    /// BNNN: jumps to NNN plus a register, unknown until it runs.
+    Computed(u16),
+    /// 00FD, SUPER-CHIP's exit.
+    Exit,
+    /// No platform defines the instruction.
+    Invalid,
+}
+
+fn classify(opcode: u16) -> Op {
+    match Instruction::decode(opcode) {
+        Some(Instruction::Return) => Op::Return,
+        Some(Instruction::Exit) => Op::Exit,
+        Some(Instruction::Jump(nnn)) => Op::Jump(nnn),
+        Some(Instruction::Call(nnn)) => Op::Call(nnn),
+        Some(Instruction::JumpOffset(nnn)) => Op::Computed(nnn),
+        Some(instruction) if instruction.is_skip() => Op::Skip,
+        Some(_) => Op::Next,
+        None => Op::Invalid,
+    }
+}
+
+/// How control gets from one block to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EdgeKind {
+    /// On to the next instruction, including past a skip not taken and
+    /// back from a call.
+    Next,
+    Jump,
+    /// A skip taken, over the next instruction.
+    Skip,
+    Call,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub target: u16,
+    pub kind: EdgeKind,
+}
+
+/// Why a block ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Terminator {
+    /// The next instruction starts another block.
+    Next,
+    Jump,
+    Skip,
+    Call,
+    Return,
+    Exit,
+    /// A BNNN computed jump, whose targets are not followed.
+    Computed,
+    /// An instruction no platform defines.
+    Invalid,
+    /// The program ends before the next instruction.
+    End,
+}
+
+/// Instructions that run one after another, entered only at the first.
+#[derive(Clone, Debug, Serialize)]
+pub struct Block {
+    pub start: u16,
+    /// Just past the last instruction.
+    pub end: u32,
+    pub opcodes: Vec<u16>,
+    pub terminator: Terminator,
+    pub successors: Vec<Edge>,
+}
+
+/// Code reached from the entry point or a 2NNN call, up to its returns.
+#[derive(Clone, Debug, Serialize)]
+pub struct Subroutine {
+    /// `main` for the entry point, `sub_XXX` after the address otherwise.
+    pub name: String,
+    pub address: u16,
+    /// Starts of the blocks reached without following calls.
+    pub blocks: Vec<u16>,
+    /// Addresses of the subroutines it calls.
+    pub calls: Vec<u16>,
+}
+
+/// A BNNN jump, whose target depends on a register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Unresolved {
+    pub address: u16,
+    pub base: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionKind {
+    /// Pointed to by I, or not made of valid instructions: sprites,
+    /// tables and the like.
+    Data,
+    /// Valid instructions that nothing reaches, as far as the analysis
+    /// can tell.
+    Unreachable,
+}
+
+/// Bytes of the program outside every block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Region {
+    pub start: u16,
+    /// Just past the last byte.
+    pub end: u32,
+    pub kind: RegionKind,
+}
+
+/// The basic blocks and call graph of a program, found by following
+/// jumps, calls, skips and returns from the entry point without running
+/// it.
+///
+/// BNNN jumps are not followed, so code only they reach shows up as
+/// unreachable, and telling data from unreachable code is a guess.
+#[derive(Clone, Debug, Serialize)]
+pub struct Graph {
+    pub entry: u16,
+    pub load_address: u16,
+    pub size: usize,
+    pub blocks: Vec<Block>,
+    pub subroutines: Vec<Subroutine>,
+    pub unresolved: Vec<Unresolved>,
+    pub regions: Vec<Region>,
+}
+
+/// The program as it sits in memory.
+struct Image<'a> {
+    program: &'a [u8],
+    load_address: u32,
+}
+
+impl<'a> Image<'a> {
+    fn contains(&self, address: u32, length: u32) -> bool {
+        address >= self.load_address && address + length <= self.load_address + self.program.len() as u32
+    }
+
+    fn word(&self, address: u32) -> Option<u16> {
+        if !self.contains(address, 2) {
+            return None;
+        }
+        let offset = (address - self.load_address) as usize;
+
+        Some((self.program[offset] as u16) << 8 | self.program[offset + 1] as u16)
+    }
+
+    /// Bytes taken by the instruction at `address`.
+    fn width(&self, address: u32) -> u32 {
+        self.word(address).and_then(Instruction::decode).map_or(2, |instruction| instruction.width() as u32)
+    }
+
+    /// Where control can go after the instruction at `address`.
+    fn successors(&self, address: u32, opcode: u16) -> Vec<(u32, EdgeKind)> {
+        let next = address + self.width(address);
+
+        match classify(opcode) {
+            Op::Next => vec![(next, EdgeKind::Next)],
+            Op::Jump(target) => vec![(target as u32, EdgeKind::Jump)],
+            Op::Call(target) => vec![(target as u32, EdgeKind::Call), (next, EdgeKind::Next)],
+            Op::Skip => vec![(next, EdgeKind::Next), (next + self.width(next), EdgeKind::Skip)],
+            Op::Return | Op::Exit | Op::Computed(_) | Op::Invalid => Vec::new(),
+        }
+    }
+}
+
+impl Graph {
+    /// Analyses `program` loaded at `load_address`, starting at `entry`.
+    pub fn build(program: &[u8], load_address: u16, entry: u16) -> Graph {
+        let image = Image { program, load_address: load_address as u32 };
+
+        // Every instruction reached, by address.
+        let mut reached = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut pending = vec![entry as u32];
+        leaders.insert(entry as u32);
+
+        while let Some(address) = pending.pop() {
+            if reached.contains_key(&address) {
+                continue;
+            }
+            let opcode = match image.word(address) {
+                Some(opcode) if image.contains(address, image.width(address)) => opcode,
+                _ => continue,
+            };
+            reached.insert(address, opcode);
+
+            for (target, _) in image.successors(address, opcode) {
+                if classify(opcode) != Op::Next {
+                    leaders.insert(target);
+                }
+                pending.push(target);
+            }
+        }
+
+        let blocks: Vec<Block> = leaders.iter()
+            .filter(|address| reached.contains_key(address))
+            .map(|&start| block(&image, &reached, &leaders, start))
+            .collect();
+
+        let mut starts: Vec<u32> = vec![entry as u32];
+        for block in &blocks {
+            for edge in block.successors.iter().filter(|edge| edge.kind == EdgeKind::Call) {
+                if !starts.contains(&(edge.target as u32)) {
+                    starts.push(edge.target as u32);
+                }
+            }
+        }
+        starts[1..].sort_unstable();
+        let subroutines = starts.iter().map(|&address| subroutine(&blocks, address as u16, entry)).collect();
+
+        let unresolved = reached.iter()
+            .filter_map(|(&address, &opcode)| match classify(opcode) {
+                Op::Computed(base) => Some(Unresolved { address: address as u16, base }),
+                _ => None,
+            })
+            .collect();
+
+        Graph {
+            entry,
+            load_address,
+            size: program.len(),
+            regions: regions(&image, &reached),
+            blocks,
+            subroutines,
+            unresolved,
+        }
+    }
+
+    /// The name of the subroutine at `address`, if one starts there.
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.subroutines.iter().find(|subroutine| subroutine.address == address).map(|subroutine| subroutine.name.as_str())
+    }
+
+    /// The graph as JSON, addresses as numbers.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("graphs serialize")
+    }
+
+    /// The graph in Graphviz's DOT language: a cluster of blocks per
+    /// subroutine, call edges dashed, and the regions outside the code as
+    /// grey boxes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let node = |address: u16| format!("\"{:#05x}\"", address);
+
+        dot.push_str("digraph chip8 {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for subroutine in &self.subroutines {
+            writeln!(dot, "    subgraph \"cluster_{}\" {{", subroutine.name).unwrap();
+            writeln!(dot, "        label=\"{}\";", subroutine.name).unwrap();
+            for &start in &subroutine.blocks {
+                writeln!(dot, "        {};", node(start)).unwrap();
+            }
+            dot.push_str("    }\n");
+        }
+
+        for block in &self.blocks {
+            let mut label = String::new();
+            let mut address = block.start;
+            for &opcode in &block.opcodes {
+                match Instruction::decode(opcode) {
+                    Some(instruction) => {
+                        write!(label, "{:#05x}: {}\\l", address, instruction).unwrap();
+                        address = address.wrapping_add(instruction.width());
+                    }
+                    None => {
+                        write!(label, "{:#05x}: {:04X}\\l", address, opcode).unwrap();
+                        address = address.wrapping_add(2);
+                    }
+                }
+            }
+            let style = match block.terminator {
+                Terminator::Computed => ", color=red, xlabel=\"unresolved BNNN\"",
+                Terminator::Invalid => ", color=red, xlabel=\"invalid instruction\"",
+                _ => "",
+            };
+            writeln!(dot, "    {} [label=\"{}\"{}];", node(block.start), label, style).unwrap();
+        }
+
+        for target in self.targets().filter(|&target| !self.blocks.iter().any(|block| block.start == target)) {
+            writeln!(dot, "    {} [label=\"{:#05x}\\noutside the program\", style=dashed];", node(target), target).unwrap();
+        }
+
+        for region in &self.regions {
+            let kind = match region.kind {
+                RegionKind::Data => "data",
+                RegionKind::Unreachable => "unreachable",
+            };
+            writeln!(
+                dot, "    \"{}_{:#05x}\" [label=\"{:#05x}-{:#05x}\\n{}\", style=filled, fillcolor=lightgrey];",
+                kind, region.start, region.start, region.end - 1, kind,
+            ).unwrap();
+        }
+
+        for block in &self.blocks {
+            for edge in &block.successors {
+                let attributes = match edge.kind {
+                    EdgeKind::Next => String::new(),
+                    EdgeKind::Jump => " [label=\"jump\"]".to_string(),
+                    EdgeKind::Skip => " [label=\"skip\"]".to_string(),
+                    EdgeKind::Call => {
+                        let name = self.name(edge.target).unwrap_or("call");
+                        format!(" [label=\"{}\", style=dashed]", name)
+                    }
+                };
+                writeln!(dot, "    {} -> {}{};", node(block.start), node(edge.target), attributes).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Every address an edge leads to.
+    fn targets(&self) -> impl Iterator<Item = u16> + '_ {
+        let targets: BTreeSet<u16> = self.blocks.iter().flat_map(|block| block.successors.iter().map(|edge| edge.target)).collect();
+        targets.into_iter()
+    }
+}
+
+/// The block starting at `start`, running until a control instruction,
+/// another block or the end of the program.
+fn block(image: &Image, reached: &BTreeMap<u32, u16>, leaders: &BTreeSet<u32>, start: u32) -> Block {
+    let mut opcodes = Vec::new();
+    let mut address = start;
+
+    loop {
+        let opcode = reached[&address];
+        opcodes.push(opcode);
+
+        let successors = image.successors(address, opcode);
+        let terminator = match classify(opcode) {
+            Op::Next => None,
+            Op::Jump(_) => Some(Terminator::Jump),
+            Op::Call(_) => Some(Terminator::Call),
+            Op::Skip => Some(Terminator::Skip),
+            Op::Return => Some(Terminator::Return),
+            Op::Exit => Some(Terminator::Exit),
+            Op::Computed(_) => Some(Terminator::Computed),
+            Op::Invalid => Some(Terminator::Invalid),
+        };
+        let next = address + image.width(address);
+
+        let (terminator, successors) = match terminator {
+            Some(terminator) => (terminator, successors),
+            None if !reached.contains_key(&next) => (Terminator::End, Vec::new()),
+            None if leaders.contains(&next) => (Terminator::Next, successors),
+            None => {
+                address = next;
+                continue;
+            }
+        };
+
+        return Block {
+            start: start as u16,
+            end: next,
+            opcodes,
+            terminator,
+            successors: successors.into_iter()
+                .map(|(target, kind)| Edge { target: target as u16, kind })
+                .collect(),
+        };
+    }
+}
+
+/// The subroutine at `address`: the blocks reached from it without
+/// following calls.
+fn subroutine(blocks: &[Block], address: u16, entry: u16) -> Subroutine {
+    let mut members = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut pending = vec![address];
+
+    while let Some(start) = pending.pop() {
+        let block = match blocks.iter().find(|block| block.start == start) {
+            Some(block) if members.insert(start) => block,
+            _ => continue,
+        };
+        for edge in &block.successors {
+            match edge.kind {
+                EdgeKind::Call => {
+                    calls.insert(edge.target);
+                }
+                _ => pending.push(edge.target),
+            }
+        }
+    }
+
+    Subroutine {
+        name: if address == entry { "main".to_string() } else { format!("sub_{:03X}", address) },
+        address,
+        blocks: members.into_iter().collect(),
+        calls: calls.into_iter().collect(),
+    }
+}
+
+/// The stretches of the program no reached instruction covers.
+fn regions(image: &Image, reached: &BTreeMap<u32, u16>) -> Vec<Region> {
+    let start = image.load_address;
+    let end = start + image.program.len() as u32;
+
+    let mut covered = vec![false; image.program.len()];
+    for &address in reached.keys() {
+        for byte in address..address + image.width(address) {
+            covered[(byte - start) as usize] = true;
+        }
+    }
+
+    // Where ANNN and F000 NNNN point I.
+    let pointers: Vec<u32> = reached.iter()
+        .filter_map(|(&address, &opcode)| match Instruction::decode(opcode) {
+            Some(Instruction::SetI(nnn)) => Some(nnn as u32),
+            Some(Instruction::SetLongI) => image.word(address + 2).map(u32::from),
+            _ => None,
+        })
+        .collect();
+
+    let mut regions = Vec::new();
+    let mut address = start;
+    while address < end {
+        if covered[(address - start) as usize] {
+            address += 1;
+            continue;
+        }
+        let from = address;
+        while address < end && !covered[(address - start) as usize] {
+            address += 1;
+        }
+
+        let pointed = pointers.iter().any(|&pointer| pointer >= from && pointer < address);
+        let decodes = (address - from).is_multiple_of(2)
+            && (from..address).step_by(2).all(|at| image.word(at).and_then(Instruction::decode).is_some());
+        regions.push(Region {
+            start: from as u16,
+            end: address,
+            kind: if pointed || !decodes { RegionKind::Data } else { RegionKind::Unreachable },
+        });
+    }
+
+    regions
+}
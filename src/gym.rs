@@ -0,0 +1,384 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+
+use config::Config;
+use display::{HIRES_HEIGHT, HIRES_WIDTH};
+use machine::{self, Machine};
+use random::Random;
+use rom::Rom;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(String, String),
+    Invalid(String),
+    Machine(machine::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref path, ref err) => write!(f, "cannot read script {}: {}", path.display(), err),
+            Error::Parse(ref source, ref message) => write!(f, "invalid script in {}: {}", source, message.trim_end()),
+            Error::Invalid(ref message) => write!(f, "invalid script: {}", message),
+            Error::Machine(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<machine::Error> for Error {
+    fn from(err: machine::Error) -> Error {
+        Error::Machine(err)
+    }
+}
+
+/// How a number is stored in memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Unsigned, most significant byte first.
+    #[default]
+    Binary,
+    /// One decimal digit per byte, most significant first, as FX33 stores
+    /// them.
+    Bcd,
+}
+
+/// What a ROM's game means to an agent, read from its memory.
+///
+/// Written in TOML:
+///
+/// ```toml
+/// # Refuse to run with any other ROM.
+/// sha1 = "..."
+/// # Keys held for each action. By default: none, then each key alone.
+/// actions = [[], [4], [6]]
+///
+/// # The reward is the weighted change of these numbers.
+/// [[reward]]
+/// address = 0x2F0
+/// format = "bcd"
+/// length = 3
+///
+/// # The episode ends when any of these holds.
+/// [[done]]
+/// address = 0x2F5
+/// equals = 0
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Script {
+    #[serde(default)]
+    pub sha1: Option<String>,
+    #[serde(default = "default_actions")]
+    pub actions: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub reward: Vec<Reward>,
+    #[serde(default)]
+    pub done: Vec<Done>,
+}
+
+fn default_actions() -> Vec<Vec<u8>> {
+    std::iter::once(Vec::new()).chain((0..16).map(|key| vec![key])).collect()
+}
+
+/// A number whose increase is rewarded, `weight` per unit.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Reward {
+    pub address: u16,
+    #[serde(default)]
+    pub format: Format,
+    /// Bytes the number takes.
+    #[serde(default = "one_byte")]
+    pub length: usize,
+    #[serde(default = "unit_weight")]
+    pub weight: f64,
+}
+
+/// A number that ends the episode when it equals, is below or is above a
+/// value.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Done {
+    pub address: u16,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default = "one_byte")]
+    pub length: usize,
+    pub equals: Option<u64>,
+    pub below: Option<u64>,
+    pub above: Option<u64>,
+}
+
+fn one_byte() -> usize {
+    1
+}
+
+fn unit_weight() -> f64 {
+    1.0
+}
+
+impl Default for Script {
+    fn default() -> Script {
+        Script {
+            sha1: None,
+            actions: default_actions(),
+            reward: Vec::new(),
+            done: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Script {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Script, Error> {
+        toml::from_str(text).map_err(|err| Error::Parse("script".into(), err.to_string()))
+    }
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Script, Error> {
+        let text = fs::read_to_string(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+
+        toml::from_str(&text).map_err(|err| Error::Parse(path.display().to_string(), err.to_string()))
+    }
+
+    /// Checks the script against the ROM and the memory it runs in.
+    fn validate(&self, hash: &str, memory_size: usize) -> Result<(), Error> {
+        if let Some(ref sha1) = self.sha1 {
+            if !sha1.eq_ignore_ascii_case(hash) {
+                return Err(Error::Invalid(format!("written for ROM {}, not {}", sha1, hash)));
+            }
+        }
+        if self.actions.is_empty() {
+            return Err(Error::Invalid("no actions".into()));
+        }
+        if let Some(key) = self.actions.iter().flatten().find(|&&key| key > 0xF) {
+            return Err(Error::Invalid(format!("no key {} in actions; keys go from 0 to 15", key)));
+        }
+
+        let numbers = self.reward.iter().map(|reward| (reward.address, reward.format, reward.length))
+            .chain(self.done.iter().map(|done| (done.address, done.format, done.length)));
+        for (address, format, length) in numbers {
+            let digits = match format {
+                Format::Binary => 8,
+                Format::Bcd => 19,
+            };
+            if length == 0 || length > digits {
+                return Err(Error::Invalid(format!("{:?} numbers take 1 to {} bytes, not {}", format, digits, length)));
+            }
+            if address as usize + length > memory_size {
+                return Err(Error::Invalid(format!("{:#x} is past the end of memory", address)));
+            }
+        }
+
+        for done in &self.done {
+            if done.equals.iter().chain(&done.below).chain(&done.above).count() != 1 {
+                return Err(Error::Invalid(format!("done at {:#x} needs one of equals, below or above", done.address)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn score(&self, memory: &[u8]) -> f64 {
+        self.reward.iter()
+            .map(|reward| reward.weight * read(memory, reward.address, reward.format, reward.length) as f64)
+            .sum()
+    }
+
+    fn done(&self, memory: &[u8]) -> bool {
+        self.done.iter().any(|done| {
+            let value = read(memory, done.address, done.format, done.length);
+
+            done.equals.is_some_and(|equals| value == equals)
+                || done.below.is_some_and(|below| value < below)
+                || done.above.is_some_and(|above| value > above)
+        })
+    }
+}
+
+fn read(memory: &[u8], address: u16, format: Format, length: usize) -> u64 {
+    let bytes = &memory[address as usize..address as usize + length];
+
+    match format {
+        Format::Binary => bytes.iter().fold(0, |value, &byte| value << 8 | byte as u64),
+        Format::Bcd => bytes.iter().fold(0, |value, &byte| value * 10 + byte.min(9) as u64),
+    }
+}
+
+/// What the agent sees after each step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Observation {
+    /// A color index per pixel, always at 128x64 with low-resolution
+    /// pixels doubled, so the size does not change mid-game.
+    #[default]
+    Pixels,
+    /// All of RAM.
+    Ram,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub observation: Observation,
+    /// Frames each action is held for.
+    pub frame_skip: u32,
+    /// Chance that a frame keeps the keys of the frame before instead of
+    /// taking the action's, which keeps agents from memorizing exact
+    /// input timings.
+    pub sticky: f64,
+    /// Frames after which an episode is cut short.
+    pub max_frames: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            observation: Observation::Pixels,
+            frame_skip: 4,
+            sticky: 0.0,
+            max_frames: None,
+        }
+    }
+}
+
+/// A reinforcement-learning environment over a `Machine`, in the style of
+/// OpenAI Gym.
+///
+/// Everything random, including CXNN, follows the seed given to `reset`,
+/// so an episode replays exactly from its seed and actions.
+pub struct Environment {
+    machine: Machine,
+    script: Script,
+    options: Options,
+    keys: u16,
+    score: f64,
+    rng: Random,
+}
+
+impl Environment {
+    pub fn new(rom: Rom, config: &Config, script: Script, options: Options) -> Result<Environment, Error> {
+        if !(0.0..=1.0).contains(&options.sticky) {
+            return Err(Error::Invalid(format!("sticky is a probability, not {}", options.sticky)));
+        }
+
+        let hash = rom.hash().to_string();
+        let machine = Machine::new(rom, config)?;
+        script.validate(&hash, machine.memory().len())?;
+        let mut environment = Environment {
+            machine,
+            script,
+            options,
+            keys: 0,
+            score: 0.0,
+            rng: Random::new(0),
+        };
+        environment.reset(0);
+
+        Ok(environment)
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn action_count(&self) -> usize {
+        self.script.actions.len()
+    }
+
+    /// Bytes in every observation.
+    pub fn observation_size(&self) -> usize {
+        match self.options.observation {
+            Observation::Pixels => HIRES_WIDTH * HIRES_HEIGHT,
+            Observation::Ram => self.machine.memory().len(),
+        }
+    }
+
+    /// Starts a new episode, returning the first observation.
+    pub fn reset(&mut self, seed: u64) -> Vec<u8> {
+        self.machine.reset();
+        self.machine.cpu_mut().seed(seed);
+        // A different stream than CXNN's, so actions do not shift it.
+        self.rng = Random::new(!seed);
+        self.keys = 0;
+        self.score = self.script.score(self.machine.memory());
+
+        self.observe()
+    }
+
+    /// Holds the keys of `action` for `frame_skip` frames, or until the
+    /// episode ends or the program faults, returning the observation, the reward and whether the
+    /// episode is over. Panics if `action` is not below `action_count`.
+    pub fn step(&mut self, action: usize) -> (Vec<u8>, f64, bool) {
+        let keys = self.script.actions[action].iter().fold(0, |mask, key| mask | 1 << key);
+
+        let mut done = false;
+        for _ in 0..self.options.frame_skip.max(1) {
+            if self.options.sticky == 0.0 || self.rng.next_f64() >= self.options.sticky {
+                self.keys = keys;
+            }
+            self.machine.set_keys(self.keys);
+            // A program that faults cannot go on, which ends the episode.
+            let faulted = self.machine.run_frame().is_err();
+
+            let memory = self.machine.memory();
+            done = faulted
+                || self.script.done(memory)
+                || self.options.max_frames.is_some_and(|max| self.machine.frame() >= max);
+            if done {
+                break;
+            }
+        }
+
+        let score = self.script.score(self.machine.memory());
+        let reward = score - self.score;
+        self.score = score;
+
+        (self.observe(), reward, done)
+    }
+
+    fn observe(&self) -> Vec<u8> {
+        match self.options.observation {
+            Observation::Ram => self.machine.memory().to_vec(),
+            Observation::Pixels => {
+                let display = self.machine.display();
+                let shift = if display.hires() { 0 } else { 1 };
+
+                (0..HIRES_HEIGHT)
+                    .flat_map(|y| (0..HIRES_WIDTH).map(move |x| display.pixel(x >> shift, y >> shift)))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Steps each environment with its action, spreading them over as many
+/// threads as the host has cores.
+pub fn step_all(environments: &mut [Environment], actions: &[usize]) -> Vec<(Vec<u8>, f64, bool)> {
+    assert_eq!(environments.len(), actions.len(), "one action per environment");
+
+    let threads = thread::available_parallelism().map_or(1, |count| count.get());
+    let chunk = environments.len().div_ceil(threads).max(1);
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = environments.chunks_mut(chunk)
+            .zip(actions.chunks(chunk))
+            .map(|(environments, actions)| {
+                scope.spawn(move || {
+                    environments.iter_mut().zip(actions).map(|(environment, &action)| environment.step(action)).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    })
+}
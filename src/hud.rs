@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use chip8::cpu::Cpu;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, WindowCanvas};
+
+/// How often the rates are recomputed.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Glyph size of the built-in font, and the gap around each one.
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+const SPACING: i32 = 1;
+
+const TEXT: Color = Color { r: 0xff, g: 0xff, b: 0xff, a: 0xff };
+const BACKING: Color = Color { r: 0x00, g: 0x00, b: 0x00, a: 0xa0 };
+
+/// Emulator statistics drawn over the display.
+pub struct Hud {
+    pub visible: bool,
+    last_sample: Instant,
+    frames: u32,
+    instructions: u64,
+    fps: f64,
+    ips: f64,
+}
+
+impl Hud {
+    pub fn new(visible: bool, now: Instant) -> Hud {
+        Hud {
+            visible,
+            last_sample: now,
+            frames: 0,
+            instructions: 0,
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    /// Counts a redraw, given the clock's running instruction count.
+    pub fn frame_drawn(&mut self, now: Instant, instructions: u64) {
+        self.frames += 1;
+
+        let elapsed = now - self.last_sample;
+        if elapsed >= SAMPLE_INTERVAL {
+            let seconds = elapsed.as_secs_f64();
+            self.fps = self.frames as f64 / seconds;
+            self.ips = instructions.saturating_sub(self.instructions) as f64 / seconds;
+
+            self.last_sample = now;
+            self.frames = 0;
+            self.instructions = instructions;
+        }
+    }
+
+    /// Draws the overlay in the top left corner, `size` window pixels per
+    /// font pixel.
+    pub fn draw(&self, canvas: &mut WindowCanvas, cpu: &Cpu, ipf: u32, size: u32) {
+        let v = cpu.v();
+        let keys: Vec<String> = (0..16)
+            .filter(|key| cpu.keys() & 1 << key != 0)
+            .map(|key| format!("{:X}", key))
+            .collect();
+
+        let lines = [
+            format!("FPS {:.0}  IPS {:.0}  IPF {}", self.fps, self.ips, ipf),
+            format!("PC {:04X}  I {:04X}", cpu.pc(), cpu.i()),
+            format!("V0-7 {}", hex(&v[..8])),
+            format!("V8-F {}", hex(&v[8..])),
+            format!("DT {:02X}  ST {:02X}", cpu.delay_timer(), cpu.sound_timer),
+            format!("KEYS {}", keys.join(" ")),
+        ];
+
+        let size = size as i32;
+        let advance = (GLYPH_WIDTH + SPACING) * size;
+        let line_height = (GLYPH_HEIGHT + SPACING) * size;
+
+        let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32;
+        let width = columns * advance + SPACING * size;
+        let height = lines.len() as i32 * line_height + SPACING * size;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(BACKING);
+        let _ = canvas.fill_rect(Rect::new(0, 0, width as u32, height as u32));
+        canvas.set_blend_mode(BlendMode::None);
+
+        canvas.set_draw_color(TEXT);
+        for (row, line) in lines.iter().enumerate() {
+            let top = SPACING * size + row as i32 * line_height;
+
+            for (column, c) in line.chars().enumerate() {
+                let left = SPACING * size + column as i32 * advance;
+
+                for (y, bits) in glyph(c).iter().enumerate() {
+                    for x in 0..GLYPH_WIDTH {
+                        if bits & 0b100 >> x != 0 {
+                            let pixel = Rect::new(left + x * size, top + y as i32 * size, size as u32, size as u32);
+                            let _ = canvas.fill_rect(pixel);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+/// A 3x5 glyph of the HUD font, one row per byte, leftmost pixel in bit 2.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 1, 2, 2],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        ' ' => [0, 0, 0, 0, 0],
+        ':' => [0, 2, 0, 2, 0],
+        '.' => [0, 0, 0, 0, 2],
+        '-' => [0, 0, 7, 0, 0],
+        '/' => [1, 1, 2, 4, 4],
+        '%' => [5, 1, 2, 4, 5],
+        _ => [6, 1, 2, 0, 2],
+    }
+}
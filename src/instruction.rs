@@ -0,0 +1,266 @@
+use core::fmt;
+
+/// A decoded instruction of CHIP-8 or its SUPER-CHIP and XO-CHIP
+/// extensions. `x` and `y` name registers V0 to VF.
+///
+/// `Display` writes it in the usual assembler mnemonics, `DRW V0, V1, 5`
+/// for D015.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0
+    Clear,
+    /// 00EE
+    Return,
+    /// 00CN: scrolls down N rows.
+    ScrollDown(u8),
+    /// 00DN: scrolls up N rows.
+    ScrollUp(u8),
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD: SUPER-CHIP's exit.
+    Exit,
+    /// 00FE
+    LowRes,
+    /// 00FF
+    HighRes,
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN: skips the next instruction if VX = NN.
+    SkipEqual(u8, u8),
+    /// 4XNN: skips if VX != NN.
+    SkipNotEqual(u8, u8),
+    /// 5XY0: skips if VX = VY.
+    SkipEqualRegister(u8, u8),
+    /// 5XY2: stores VX to VY at I.
+    SaveRange(u8, u8),
+    /// 5XY3: loads VX to VY from I.
+    LoadRange(u8, u8),
+    /// 6XNN
+    Set(u8, u8),
+    /// 7XNN, without carry.
+    Add(u8, u8),
+    /// 8XY0
+    Move(u8, u8),
+    /// 8XY1
+    Or(u8, u8),
+    /// 8XY2
+    And(u8, u8),
+    /// 8XY3
+    Xor(u8, u8),
+    /// 8XY4, with the carry in VF.
+    AddRegister(u8, u8),
+    /// 8XY5: VX - VY, with not borrow in VF.
+    Sub(u8, u8),
+    /// 8XY6
+    ShiftRight(u8, u8),
+    /// 8XY7: VY - VX, with not borrow in VF.
+    SubReverse(u8, u8),
+    /// 8XYE
+    ShiftLeft(u8, u8),
+    /// 9XY0: skips if VX != VY.
+    SkipNotEqualRegister(u8, u8),
+    /// ANNN
+    SetI(u16),
+    /// BNNN: jumps to NNN plus V0, or plus VX with the jump quirk, where X
+    /// is the top nibble of NNN.
+    JumpOffset(u16),
+    /// CXNN: a random byte masked with NN.
+    Random(u8, u8),
+    /// DXYN: an N-row sprite at (VX, VY), or 16x16 when N is 0.
+    Draw(u8, u8, u8),
+    /// EX9E: skips if key VX is down.
+    SkipKey(u8),
+    /// EXA1: skips if key VX is up.
+    SkipNotKey(u8),
+    /// F000 NNNN: sets I to the 16-bit address in the next word.
+    SetLongI,
+    /// FN01: selects the bit planes N.
+    Planes(u8),
+    /// F002: loads the audio pattern from I.
+    Audio,
+    /// FX07
+    GetDelay(u8),
+    /// FX0A
+    WaitKey(u8),
+    /// FX15
+    SetDelay(u8),
+    /// FX18
+    SetSound(u8),
+    /// FX1E
+    AddI(u8),
+    /// FX29: points I at the small glyph of VX's low nibble.
+    Font(u8),
+    /// FX30: points I at the large glyph.
+    LargeFont(u8),
+    /// FX33: stores VX's hundreds, tens and ones digits at I.
+    Bcd(u8),
+    /// FX3A: sets the audio pitch.
+    Pitch(u8),
+    /// FX55: stores V0 to VX at I.
+    Store(u8),
+    /// FX65: loads V0 to VX from I.
+    Load(u8),
+    /// FX75: saves V0 to VX to the flag registers.
+    SaveFlags(u8),
+    /// FX85: loads V0 to VX from the flag registers.
+    LoadFlags(u8),
+}
+
+impl Instruction {
+    /// The instruction an opcode encodes, or `None` if no platform defines
+    /// it.
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let nnn = opcode & 0x0fff;
+        let nn = (opcode & 0x00ff) as u8;
+        let n = (opcode & 0x000f) as u8;
+        let x = ((opcode & 0x0f00) >> 8) as u8;
+        let y = ((opcode & 0x00f0) >> 4) as u8;
+
+        let instruction = match opcode >> 12 {
+            0x0 => match nnn {
+                0x0e0 => Instruction::Clear,
+                0x0ee => Instruction::Return,
+                0x0c0..=0x0cf => Instruction::ScrollDown(n),
+                0x0d0..=0x0df => Instruction::ScrollUp(n),
+                0x0fb => Instruction::ScrollRight,
+                0x0fc => Instruction::ScrollLeft,
+                0x0fd => Instruction::Exit,
+                0x0fe => Instruction::LowRes,
+                0x0ff => Instruction::HighRes,
+                _ => return None,
+            },
+            0x1 => Instruction::Jump(nnn),
+            0x2 => Instruction::Call(nnn),
+            0x3 => Instruction::SkipEqual(x, nn),
+            0x4 => Instruction::SkipNotEqual(x, nn),
+            0x5 => match n {
+                0x0 => Instruction::SkipEqualRegister(x, y),
+                0x2 => Instruction::SaveRange(x, y),
+                0x3 => Instruction::LoadRange(x, y),
+                _ => return None,
+            },
+            0x6 => Instruction::Set(x, nn),
+            0x7 => Instruction::Add(x, nn),
+            0x8 => match n {
+                0x0 => Instruction::Move(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddRegister(x, y),
+                0x5 => Instruction::Sub(x, y),
+                0x6 => Instruction::ShiftRight(x, y),
+                0x7 => Instruction::SubReverse(x, y),
+                0xe => Instruction::ShiftLeft(x, y),
+                _ => return None,
+            },
+            0x9 if n == 0 => Instruction::SkipNotEqualRegister(x, y),
+            0xa => Instruction::SetI(nnn),
+            0xb => Instruction::JumpOffset(nnn),
+            0xc => Instruction::Random(x, nn),
+            0xd => Instruction::Draw(x, y, n),
+            0xe => match nn {
+                0x9e => Instruction::SkipKey(x),
+                0xa1 => Instruction::SkipNotKey(x),
+                _ => return None,
+            },
+            0xf => match nn {
+                0x00 if x == 0 => Instruction::SetLongI,
+                0x01 => Instruction::Planes(x),
+                0x02 if x == 0 => Instruction::Audio,
+                0x07 => Instruction::GetDelay(x),
+                0x0a => Instruction::WaitKey(x),
+                0x15 => Instruction::SetDelay(x),
+                0x18 => Instruction::SetSound(x),
+                0x1e => Instruction::AddI(x),
+                0x29 => Instruction::Font(x),
+                0x30 => Instruction::LargeFont(x),
+                0x33 => Instruction::Bcd(x),
+                0x3a => Instruction::Pitch(x),
+                0x55 => Instruction::Store(x),
+                0x65 => Instruction::Load(x),
+                0x75 => Instruction::SaveFlags(x),
+                0x85 => Instruction::LoadFlags(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /// Bytes the instruction takes: 4 for F000 NNNN, 2 for the rest.
+    pub fn width(self) -> u16 {
+        if self == Instruction::SetLongI { 4 } else { 2 }
+    }
+
+    /// Whether it skips the next instruction on some condition.
+    pub fn is_skip(self) -> bool {
+        matches!(
+            self,
+            Instruction::SkipEqual(..) | Instruction::SkipNotEqual(..)
+                | Instruction::SkipEqualRegister(..) | Instruction::SkipNotEqualRegister(..)
+                | Instruction::SkipKey(_) | Instruction::SkipNotKey(_)
+        )
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::Jump(nnn) => write!(f, "JP {:#05X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            Instruction::SkipEqual(x, nn) => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Instruction::SkipNotEqual(x, nn) => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Instruction::SkipEqualRegister(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange(x, y) => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Instruction::Set(x, nn) => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Instruction::Add(x, nn) => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Instruction::Move(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddRegister(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNotEqualRegister(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::SetI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::JumpOffset(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Random(x, nn) => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::SetLongI => write!(f, "LD I, LONG"),
+            Instruction::Planes(n) => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::GetDelay(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LargeFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::Store(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::SaveFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "std")]
+extern crate flate2;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+extern crate rand;
+#[cfg(feature = "std")]
+extern crate serde;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "std")]
+extern crate sha1_smol;
+#[cfg(feature = "std")]
+extern crate toml;
+#[cfg(feature = "std")]
+extern crate zip;
+
+// The interpreter itself, which needs neither `std` nor a heap.
+pub mod bus;
+pub mod cpu;
+pub mod display;
+pub mod font;
+pub mod instruction;
+pub mod memory;
+pub mod quirks;
+pub mod random;
+
+#[cfg(feature = "std")]
+mod archive;
+
+#[cfg(feature = "std")]
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod capture;
+#[cfg(feature = "std")]
+pub mod cheat;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod database;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod decompile;
+#[cfg(feature = "std")]
+pub mod detect;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod flow;
+#[cfg(feature = "std")]
+pub mod gym;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod libretro;
+#[cfg(feature = "std")]
+pub mod machine;
+#[cfg(feature = "std")]
+pub mod palette;
+#[cfg(feature = "std")]
+pub mod platform;
+#[cfg(feature = "std")]
+pub mod remote;
+#[cfg(feature = "std")]
+pub mod rewind;
+#[cfg(feature = "std")]
+pub mod rom;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod video;
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+pub mod wasm;
@@ -0,0 +1,604 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::{Mutex, MutexGuard};
+
+use audio::SquareWave;
+use cheat::Cheat;
+use config::{Config, Rgb, Sources};
+use database::Database;
+use machine::{Machine, FRAME_RATE};
+use palette;
+use rom::Rom;
+use video::{Compositor, Image};
+
+const API_VERSION: c_uint = 1;
+
+const ENVIRONMENT_SET_MESSAGE: c_uint = 6;
+const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+
+const PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const DEVICE_JOYPAD: c_uint = 1;
+const MEMORY_SYSTEM_RAM: c_uint = 2;
+const REGION_NTSC: c_uint = 0;
+
+const SAMPLE_RATE: u32 = 44_100;
+
+/// Keypad key for each RetroPad button, in button id order: B, Y, Select,
+/// Start, Up, Down, Left, Right, A, X, L, R, L2, R2, L3, R3.
+///
+/// `NUMERIC` puts the directions on 2, 8, 4 and 6 around 5, as most
+/// CHIP-8 games expect; `WASD` on 5, 8, 7 and 9, as games written for a
+/// keyboard mapped like the standalone emulator's do.
+const NUMERIC: [u8; 16] = [0x0, 0x3, 0xE, 0xF, 0x2, 0x8, 0x4, 0x6, 0x5, 0x1, 0x7, 0x9, 0xA, 0xB, 0xC, 0xD];
+const WASD: [u8; 16] = [0x4, 0x3, 0xE, 0xF, 0x5, 0x8, 0x7, 0x9, 0x6, 0x1, 0x2, 0x0, 0xA, 0xB, 0xC, 0xD];
+
+const QUIRKS: [&str; 6] = ["shift", "load_store", "jump", "vf_reset", "clipping", "large_sprites"];
+
+const SPEEDS: [u32; 14] = [200, 300, 400, 500, 600, 700, 800, 1000, 1200, 1500, 2000, 3000, 5000, 10000];
+
+#[repr(C)]
+pub struct SystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    pub geometry: GameGeometry,
+    pub timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+pub struct Variable {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
+
+#[repr(C)]
+pub struct InputDescriptor {
+    pub port: c_uint,
+    pub device: c_uint,
+    pub index: c_uint,
+    pub id: c_uint,
+    pub description: *const c_char,
+}
+
+#[repr(C)]
+pub struct Message {
+    pub msg: *const c_char,
+    pub frames: c_uint,
+}
+
+pub type EnvironmentFn = unsafe extern "C" fn(c_uint, *mut c_void) -> bool;
+pub type VideoRefreshFn = unsafe extern "C" fn(*const c_void, c_uint, c_uint, usize);
+pub type AudioSampleFn = unsafe extern "C" fn(i16, i16);
+pub type AudioSampleBatchFn = unsafe extern "C" fn(*const i16, usize) -> usize;
+pub type InputPollFn = unsafe extern "C" fn();
+pub type InputStateFn = unsafe extern "C" fn(c_uint, c_uint, c_uint, c_uint) -> i16;
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+/// The callbacks, copied out so none is called with the lock held.
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn core() -> MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn environment<T>(command: c_uint, data: &mut T) -> bool {
+    match callbacks().environment {
+        Some(callback) => unsafe { callback(command, data as *mut T as *mut c_void) },
+        None => false,
+    }
+}
+
+fn variable(key: &str) -> Option<String> {
+    let key = CString::new(key).unwrap();
+    let mut variable = Variable { key: key.as_ptr(), value: ptr::null() };
+
+    if !environment(ENVIRONMENT_GET_VARIABLE, &mut variable) || variable.value.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(variable.value) }.to_string_lossy().into_owned())
+}
+
+/// Shows `text` on screen for a few seconds.
+fn show(text: &str) {
+    let text = CString::new(text.replace('\0', "")).unwrap();
+    let mut message = Message { msg: text.as_ptr(), frames: 3 * FRAME_RATE };
+
+    environment(ENVIRONMENT_SET_MESSAGE, &mut message);
+}
+
+fn declare_variables() {
+    let speeds: Vec<String> = SPEEDS.iter().map(u32::to_string).collect();
+    let mut declarations = vec![
+        ("chip8_speed".to_string(), format!("Speed (instructions per second); auto|{}", speeds.join("|"))),
+        ("chip8_palette".to_string(), format!("Palette; auto|{}", palette::NAMES.join("|"))),
+        ("chip8_keypad".to_string(), "Keypad layout on the D-pad; numeric|wasd".to_string()),
+    ];
+    for quirk in &QUIRKS {
+        declarations.push((format!("chip8_quirk_{}", quirk), format!("Quirk: {}; auto|enabled|disabled", quirk)));
+    }
+
+    let strings: Vec<(CString, CString)> = declarations.into_iter()
+        .map(|(key, value)| (CString::new(key).unwrap(), CString::new(value).unwrap()))
+        .collect();
+    let mut variables: Vec<Variable> = strings.iter()
+        .map(|(key, value)| Variable { key: key.as_ptr(), value: value.as_ptr() })
+        .chain(Some(Variable { key: ptr::null(), value: ptr::null() }))
+        .collect();
+
+    environment(ENVIRONMENT_SET_VARIABLES, &mut variables[0]);
+}
+
+/// The core options as config overrides.
+fn overrides() -> Vec<String> {
+    let mut overrides = Vec::new();
+
+    if let Some(speed) = variable("chip8_speed").filter(|speed| speed != "auto") {
+        overrides.push(format!("cpu.speed={}", speed));
+    }
+    if let Some(preset) = variable("chip8_palette").filter(|preset| preset != "auto") {
+        overrides.push(format!("palette.preset={}", preset));
+    }
+    for quirk in &QUIRKS {
+        match variable(&format!("chip8_quirk_{}", quirk)).as_deref() {
+            Some("enabled") => overrides.push(format!("quirks.{}=true", quirk)),
+            Some("disabled") => overrides.push(format!("quirks.{}=false", quirk)),
+            _ => {}
+        }
+    }
+
+    overrides
+}
+
+fn keypad() -> [u8; 16] {
+    match variable("chip8_keypad").as_deref() {
+        Some("wasd") => WASD,
+        _ => NUMERIC,
+    }
+}
+
+fn describe_input(keypad: &[u8; 16]) {
+    const NAMES: [&[u8]; 16] = [
+        b"Key 0\0", b"Key 1\0", b"Key 2\0", b"Key 3\0", b"Key 4\0", b"Key 5\0", b"Key 6\0", b"Key 7\0",
+        b"Key 8\0", b"Key 9\0", b"Key A\0", b"Key B\0", b"Key C\0", b"Key D\0", b"Key E\0", b"Key F\0",
+    ];
+
+    let mut descriptors: Vec<InputDescriptor> = keypad.iter().enumerate()
+        .map(|(button, &key)| InputDescriptor {
+            port: 0,
+            device: DEVICE_JOYPAD,
+            index: 0,
+            id: button as c_uint,
+            description: NAMES[key as usize].as_ptr() as *const c_char,
+        })
+        .chain(Some(InputDescriptor { port: 0, device: 0, index: 0, id: 0, description: ptr::null() }))
+        .collect();
+
+    environment(ENVIRONMENT_SET_INPUT_DESCRIPTORS, &mut descriptors[0]);
+}
+
+fn load_config(rom: Option<&Rom>) -> Result<Config, String> {
+    let sources = Sources { overrides: overrides(), ..Sources::default() };
+
+    Config::load(&sources, rom).map_err(|err| err.to_string())
+}
+
+/// A loaded game.
+///
+/// Settings come from the same layers as in the standalone emulator, with
+/// the core options applied last like `--set` overrides. Changes to the
+/// speed, quirks, palette and keypad layout take effect at once.
+struct Core {
+    machine: Machine,
+    compositor: Compositor,
+    image: Image,
+    /// The image in XRGB8888.
+    pixels: Vec<u32>,
+    /// `None` when the buzzer is disabled.
+    tone: Option<SquareWave>,
+    samples: Vec<i16>,
+    keypad: [u8; 16],
+    /// Set when the program crashed, after which it stands still until
+    /// reset or a state is loaded.
+    crashed: bool,
+}
+
+impl Core {
+    fn new(rom: Rom) -> Result<Core, String> {
+        let config = load_config(Some(&rom))?;
+        let machine = Machine::new(rom, &config).map_err(|err| err.to_string())?;
+
+        Ok(Core {
+            machine,
+            compositor: Compositor::new(config.palette.colors(), &config.video),
+            image: Image::default(),
+            pixels: Vec::new(),
+            tone: if config.audio.enabled { Some(SquareWave::new(&config.audio, SAMPLE_RATE)) } else { None },
+            samples: Vec::new(),
+            keypad: keypad(),
+            crashed: false,
+        })
+    }
+
+    /// Applies changed core options.
+    fn reconfigure(&mut self) {
+        let config = match load_config(self.machine.cpu().bus().rom()) {
+            Ok(config) => config,
+            Err(message) => return show(&message),
+        };
+
+        self.machine.set_speed(config.cpu.speed);
+        self.machine.cpu_mut().set_quirks(config.quirks);
+        self.compositor.set_colors(config.palette.colors());
+        self.keypad = keypad();
+        describe_input(&self.keypad);
+    }
+
+    fn run(&mut self) {
+        let callbacks = callbacks();
+
+        if let Some(input_poll) = callbacks.input_poll {
+            unsafe { input_poll() };
+        }
+        if let Some(input_state) = callbacks.input_state {
+            let keys = self.keypad.iter().enumerate()
+                .filter(|&(button, _)| unsafe { input_state(0, DEVICE_JOYPAD, 0, button as c_uint) } != 0)
+                .fold(0, |mask, (_, &key)| mask | 1 << key);
+            self.machine.set_keys(keys);
+        }
+
+        let mut sound = false;
+        if !self.crashed {
+            let machine = &mut self.machine;
+            match panic::catch_unwind(AssertUnwindSafe(|| machine.run_frame())) {
+                Ok(Ok(beeped)) => sound = beeped,
+                Ok(Err(fault)) => {
+                    self.crashed = true;
+                    show(&format!("The CHIP-8 program stopped: {} at {:#05x}", fault, self.machine.cpu().pc()));
+                }
+                Err(_) => {
+                    self.crashed = true;
+                    show("The CHIP-8 program crashed");
+                }
+            }
+        }
+
+        self.compositor.compose(self.machine.display(), &mut self.image);
+        self.pixels.clear();
+        self.pixels.extend(self.image.pixels.iter().map(|&Rgb(r, g, b)| (r as u32) << 16 | (g as u32) << 8 | b as u32));
+        if let Some(video_refresh) = callbacks.video_refresh {
+            let (width, height) = (self.image.width, self.image.height);
+            unsafe { video_refresh(self.pixels.as_ptr() as *const c_void, width as c_uint, height as c_uint, width * 4) };
+        }
+
+        let frames = (SAMPLE_RATE / FRAME_RATE) as usize;
+        self.samples.clear();
+        if let Some(ref mut tone) = self.tone {
+            let cpu = self.machine.cpu();
+            tone.set_pattern(cpu.audio_pattern(), cpu.pitch());
+        }
+        for _ in 0..frames {
+            let sample = match self.tone {
+                Some(ref mut tone) if sound => (tone.next_sample() * i16::MAX as f32) as i16,
+                _ => 0,
+            };
+            self.samples.extend_from_slice(&[sample, sample]);
+        }
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            let mut written = 0;
+            while written < frames {
+                let accepted = unsafe { audio_sample_batch(self.samples[written * 2..].as_ptr(), frames - written) };
+                if accepted == 0 {
+                    break;
+                }
+                written += accepted;
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).environment = Some(callback);
+    declare_variables();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).video_refresh = Some(callback);
+}
+
+/// Unused: audio goes out a frame at a time through the batch callback.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+/// # Safety
+///
+/// `info` must point to a writable `retro_system_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: b"chip8\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8|c8|sc8|xo8|bin|gz\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+///
+/// `info` must point to a writable `retro_system_av_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: 64,
+            base_height: 32,
+            max_width: 128,
+            max_height: 64,
+            aspect_ratio: 2.0,
+        },
+        timing: SystemTiming {
+            fps: FRAME_RATE as f64,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_: c_uint, _: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(ref mut core) = *core() {
+        core.machine.reset();
+        core.crashed = false;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let mut updated = false;
+    if environment(ENVIRONMENT_GET_VARIABLE_UPDATE, &mut updated) && updated {
+        if let Some(ref mut core) = *core() {
+            core.reconfigure();
+        }
+    }
+
+    if let Some(ref mut core) = *core() {
+        core.run();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    core().as_ref().map_or(0, |core| core.machine.save_state().len())
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let state = match *core() {
+        Some(ref core) => core.machine.save_state(),
+        None => return false,
+    };
+    if data.is_null() || size < state.len() {
+        return false;
+    }
+
+    ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+    true
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let state = slice::from_raw_parts(data as *const u8, size);
+
+    match *core() {
+        Some(ref mut core) => {
+            let loaded = core.machine.load_state(state).is_ok();
+            core.crashed &= !loaded;
+            loaded
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {
+    if let Some(ref mut core) = *core() {
+        core.machine.cpu_mut().bus_mut().unfreeze_all();
+    }
+}
+
+/// Freezes, or when disabled lets go of, the addresses of `code`: cheats
+/// written `"AAA:VV"` in hex, several joined by `+`.
+///
+/// # Safety
+///
+/// `code` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(_: c_uint, enabled: bool, code: *const c_char) {
+    if code.is_null() {
+        return;
+    }
+    let code = CStr::from_ptr(code).to_string_lossy();
+
+    if let Some(ref mut core) = *core() {
+        for code in code.split('+').filter(|code| !code.trim().is_empty()) {
+            let result = code.parse::<Cheat>().and_then(|cheat| {
+                if !enabled {
+                    core.machine.unfreeze(cheat.address);
+                    return Ok(());
+                }
+                core.machine.freeze(cheat).map_err(|err| format!("cannot freeze {}: {}", cheat, err))
+            });
+            if let Err(message) = result {
+                show(&message);
+            }
+        }
+    }
+}
+
+/// # Safety
+///
+/// `game` must be null or point to a valid `retro_game_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let game = match game.as_ref() {
+        Some(game) => game,
+        None => return false,
+    };
+
+    // As in the standalone emulator, a broken local database leaves the
+    // bundled one.
+    let database = Database::open().unwrap_or_else(|_| Database::bundled());
+    let rom = if !game.data.is_null() {
+        Rom::load(slice::from_raw_parts(game.data as *const u8, game.size), None, &database)
+    } else if !game.path.is_null() {
+        Rom::open(CStr::from_ptr(game.path).to_string_lossy().into_owned(), None, &database)
+    } else {
+        return false;
+    };
+
+    let mut format = PIXEL_FORMAT_XRGB8888;
+    if !environment(ENVIRONMENT_SET_PIXEL_FORMAT, &mut format) {
+        return false;
+    }
+
+    match rom.map_err(|err| err.to_string()).and_then(Core::new) {
+        Ok(loaded) => {
+            describe_input(&loaded.keypad);
+            *core() = Some(loaded);
+            true
+        }
+        Err(message) => {
+            show(&format!("Cannot load the ROM: {}", message));
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_: c_uint, _: *const GameInfo, _: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+/// The machine's RAM, which stays in place while the game is loaded.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match *core() {
+        Some(ref mut core) if id == MEMORY_SYSTEM_RAM => core.machine.memory_mut().as_mut_ptr() as *mut c_void,
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match *core() {
+        Some(ref core) if id == MEMORY_SYSTEM_RAM => core.machine.memory().len(),
+        _ => 0,
+    }
+}
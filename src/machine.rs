@@ -0,0 +1,233 @@
+use std::error;
+use std::fmt;
+
+use bus::{Bus, FreezeError};
+use cheat::Cheat;
+use config::{self, Config};
+use cpu::{Cpu, Fault};
+use display::Framebuffer;
+use rom::{self, Rom};
+use snapshot::{self, Snapshot};
+
+/// Ticks of the delay and sound timers per emulated second.
+pub const FRAME_RATE: u32 = 60;
+
+#[derive(Debug)]
+pub enum Error {
+    Config(config::Error),
+    Rom(rom::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Config(ref err) => write!(f, "{}", err),
+            Error::Rom(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<config::Error> for Error {
+    fn from(err: config::Error) -> Error {
+        Error::Config(err)
+    }
+}
+
+impl From<rom::Error> for Error {
+    fn from(err: rom::Error) -> Error {
+        Error::Rom(err)
+    }
+}
+
+/// The registers, as reported to tools.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Registers {
+    pub pc: u16,
+    pub i: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub v: [u8; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// A CHIP-8 computer without a frontend, run a frame at a time.
+///
+/// Each frame runs the configured speed's share of instructions and then
+/// ticks the timers once, so runs do not depend on the host's clock. Bots,
+/// tests and the remote control drive it directly; the windowed frontend
+/// paces its own `Cpu` against real time instead.
+pub struct Machine {
+    cpu: Cpu,
+    speed: u32,
+    /// Instructions per second left over from earlier frames, in
+    /// sixtieths, so speeds that are not a multiple of 60 average out.
+    remainder: u32,
+    frame: u64,
+}
+
+impl Machine {
+    /// Loads `rom` with the memory layout, quirks and speed of `config`.
+    /// Fails when the program and the fonts do not fit that layout.
+    pub fn new(rom: Rom, config: &Config) -> Result<Machine, Error> {
+        let bus = Bus::new(rom, config.layout()?, config.memory)?;
+        let cpu = Cpu::new(bus, config.quirks, config.entry_point());
+
+        Ok(Machine::with_cpu(cpu, config.cpu.speed))
+    }
+
+    /// Runs `cpu` at `speed` instructions per second.
+    pub fn with_cpu(cpu: Cpu, speed: u32) -> Machine {
+        Machine {
+            cpu,
+            speed,
+            remainder: 0,
+            frame: 0,
+        }
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    /// Instructions per second.
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed;
+    }
+
+    /// Frames run since the machine was made or last reset.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Runs one frame, returning whether the buzzer sounded during it.
+    /// A fault stops the frame short, before the timers tick, and leaves
+    /// the program counter on the faulting instruction.
+    pub fn run_frame(&mut self) -> Result<bool, Fault> {
+        let owed = self.speed + self.remainder;
+
+        // Writes through `memory_mut` get past the bus.
+        self.cpu.bus_mut().refreeze();
+
+        let mut sound = false;
+        for _ in 0..owed / FRAME_RATE {
+            self.cpu.run_next_instruction()?;
+            sound |= self.cpu.make_sound;
+        }
+        self.remainder = owed % FRAME_RATE;
+        self.cpu.tick_timers();
+        self.frame += 1;
+
+        Ok(sound || self.cpu.sound_timer > 0)
+    }
+
+    /// Restarts the program from its pristine image.
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.remainder = 0;
+        self.frame = 0;
+    }
+
+    /// Pressed keys, bit N for key N.
+    pub fn keys(&self) -> u16 {
+        self.cpu.keys()
+    }
+
+    pub fn set_keys(&mut self, mask: u16) {
+        self.cpu.set_keys(mask);
+    }
+
+    pub fn display(&self) -> &Framebuffer {
+        self.cpu.display()
+    }
+
+    pub fn registers(&self) -> Registers {
+        let cpu = &self.cpu;
+
+        Registers {
+            pc: cpu.pc(),
+            i: cpu.i() as u16,
+            sp: cpu.sp(),
+            stack: *cpu.stack(),
+            v: *cpu.v(),
+            delay_timer: cpu.delay_timer(),
+            sound_timer: cpu.sound_timer,
+        }
+    }
+
+    /// All of RAM, whatever the access policies of its regions.
+    pub fn memory(&self) -> &[u8] {
+        self.cpu.bus().memory.as_slice()
+    }
+
+    /// RAM for tools to change, such as cheat finders. It stays in place
+    /// for as long as the machine lives.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.cpu.bus_mut().memory.as_mut_slice()
+    }
+
+    /// The program image the machine was loaded from.
+    pub fn rom(&self) -> Option<&Rom> {
+        self.cpu.bus().rom()
+    }
+
+    /// The frozen addresses, as cheat codes.
+    pub fn cheats(&self) -> Vec<Cheat> {
+        self.cpu.bus().frozen().map(|(address, value)| Cheat { address, value }).collect()
+    }
+
+    /// Freezes an address for every frame from now on, including this one.
+    pub fn freeze(&mut self, cheat: Cheat) -> Result<(), FreezeError> {
+        self.cpu.bus_mut().freeze(cheat.address, cheat.value)
+    }
+
+    /// Lets the program change `address` again, returning whether it was
+    /// frozen.
+    pub fn unfreeze(&mut self, address: u16) -> bool {
+        self.cpu.bus_mut().unfreeze(address)
+    }
+
+    /// The whole machine state, in the snapshot encoding.
+    pub fn save_state(&self) -> Vec<u8> {
+        self.cpu.snapshot().to_bytes()
+    }
+
+    /// Restores a state from `save_state`. The frame count carries on.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), snapshot::Error> {
+        self.cpu.restore(&Snapshot::from_bytes(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Sources;
+
+    fn config(text: &str, rom: &Rom) -> Config {
+        let sources = Sources { text: Some(text.into()), ignore_default_file: true, ..Sources::default() };
+        Config::load(&sources, Some(rom)).unwrap()
+    }
+
+    #[test]
+    fn checks_the_rom_against_the_configured_layout() {
+        // Fits at 0x200, the VIP's load address, but not at 0x300.
+        let rom = Rom::from_bytes(&[0x12; 0xE00], None).unwrap();
+        assert!(Machine::new(rom.clone(), &config("[cpu]\nplatform = \"vip\"\n", &rom)).is_ok());
+
+        let config = config("[cpu]\nplatform = \"vip\"\nload_address = 0x300\n", &rom);
+        assert_eq!(
+            Machine::new(rom, &config).err().unwrap().to_string(),
+            "ROM is 3584 bytes but only 3328 fit between 0x300 and the end of the 4 KiB memory",
+        );
+    }
+}
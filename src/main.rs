@@ -1,72 +1,92 @@
-extern crate rand;
+extern crate chip8;
 extern crate sdl2;
 
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, IsTerminal};
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::env::args;
-use std::time::{Instant, Duration};
+use std::thread;
+use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
+
+mod cli;
+mod clock;
+mod hud;
+mod record;
+mod screen;
+mod watch;
+
+use chip8::audio::SquareWave;
+use chip8::capture;
+use chip8::cheat;
+use chip8::config::{self, AudioConfig, Config, WindowState};
+use chip8::database::Database;
+use chip8::debugger::Debugger;
+use chip8::decompile::Listing;
+use chip8::flow::Graph;
+use chip8::cpu::{Cpu, DEFAULT_PITCH};
+use chip8::machine::Machine;
+use chip8::memory::Layout;
+use chip8::bus::Bus;
+use chip8::palette;
+use chip8::remote::{Address, Server};
+use chip8::rewind::Rewind;
+use chip8::rom::{self, Rom};
+use chip8::video::Image;
+use cli::{Command, DecompileFormat, GraphFormat};
+use clock::{Clock, Mode};
+use hud::Hud;
+use record::Recorder;
+use screen::Screen;
+use watch::Watcher;
 
-mod cpu;
-mod bus;
-mod rom;
-
-use cpu::Cpu;
-use bus::Bus;
-use rom::Rom;
-
-use sdl2::rect::{Rect};
 use sdl2::event::{Event};
 use sdl2::keyboard::Keycode;
+use sdl2::video::{FullscreenType, Window, WindowPos};
 
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::Sdl;
 
-pub struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
+pub struct Tone(SquareWave);
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Tone {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
         for x in out.iter_mut() {
-            *x = match self.phase {
-                0.0...0.5 => self.volume,
-                _ => -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            *x = self.0.next_sample();
         }
     }
 }
 
 pub struct Beeper {
-    pub device: AudioDevice<SquareWave>,
+    pub device: AudioDevice<Tone>,
     duration: Duration,
     start: Instant,
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
 }
 
 impl Beeper {
-    pub fn new(context: &Sdl, duration: Duration) -> Self {
+    pub fn new(context: &Sdl, audio: &AudioConfig) -> Self {
         let desired_spec = AudioSpecDesired {
-            freq: Some(24100),
+            freq: Some(audio.sample_rate),
             channels: Some(1),
             samples: None,
         };
         let sub = context.audio().unwrap();
         let device = sub.open_playback(None, &desired_spec, |spec| {
-
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25
-            }
+            Tone(SquareWave::new(audio, spec.freq as u32))
         }).unwrap();
 
         Beeper {
-            device: device,
-            duration: duration,
+            device,
+            duration: Duration::from_millis(audio.duration),
             start: Instant::now(),
+            pattern: None,
+            pitch: DEFAULT_PITCH,
         }
     }
 
@@ -80,220 +100,590 @@ impl Beeper {
             }
         }
     }
+
+    /// Switches to the XO-CHIP audio pattern the program loaded, if any.
+    pub fn set_pattern(&mut self, pattern: Option<&[u8; 16]>, pitch: u8) {
+        if (pattern, pitch) != (self.pattern.as_ref(), self.pitch) {
+            self.pattern = pattern.copied();
+            self.pitch = pitch;
+            self.device.lock().0.set_pattern(pattern, pitch);
+        }
+    }
+
+    /// Stops any beep at once.
+    pub fn silence(&mut self) {
+        self.device.pause();
+    }
+}
+
+/// Hotkeys resolved to SDL keycodes.
+struct Hotkeys {
+    reset: Keycode,
+    pause: Keycode,
+    frame_advance: Keycode,
+    fast_forward: Keycode,
+    slow_motion: Keycode,
+    rewind: Keycode,
+    hud: Keycode,
+    palette: Keycode,
+    filter: Keycode,
+    screenshot: Keycode,
+    record: Keycode,
+    fullscreen: Keycode,
 }
 
 fn main() {
-    let rom_file = args().nth(1).unwrap();
+    let args = match cli::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("chip8: {}\n\n{}", message, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Command::Help = args.command {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    // Read once, so that a reload sees the same entries.
+    let database = open_database();
+
+    let result = match args.command {
+        Command::Help => Ok(()),
+        Command::ConfigDump(rom_file) => dump_config(rom_file, args.entry.as_deref(), &args.sources, &database),
+        Command::Serve(address, rom_file) => serve(&address, &rom_file, args.entry.as_deref(), &args.sources, &database),
+        Command::Debug(rom_file) => debug(&rom_file, args.entry.as_deref(), &args.sources, &database),
+        Command::Graph(format, rom_file) => graph(format, &rom_file, args.entry.as_deref(), &args.sources, &database),
+        Command::Decompile(format, rom_file) => decompile(format, &rom_file, args.entry.as_deref(), &args.sources, &database),
+        Command::Run(rom_file) => run(&rom_file, args.entry.as_deref(), args.watch, &args.sources, &database),
+    };
+
+    if let Err(message) = result {
+        eprintln!("chip8: {}", message);
+        process::exit(1);
+    }
+}
+
+fn dump_config(rom_file: Option<String>, entry: Option<&str>, sources: &config::Sources, database: &Database) -> Result<(), String> {
+    let rom = match rom_file {
+        Some(rom_file) => Some(load_rom(&rom_file, entry, database)?),
+        None => None,
+    };
+
+    let config = Config::load(sources, rom.as_ref()).map_err(|err| err.to_string())?;
+    let rom = rom.map(|rom| rom.with_load_address(config.cpu.load_address));
+
+    if let Some(ref rom) = rom {
+        println!("# ROM sha1 {}", rom.hash());
+        if let Some(info) = rom.info() {
+            let title = info.title.as_ref().map_or("untitled", String::as_str);
+            match info.author {
+                Some(ref author) => println!("# {} by {} ({})", title, author, info.source),
+                None => println!("# {} ({})", title, info.source),
+            }
+        }
+        if let Some(detection) = rom.detection() {
+            println!("# detected {} ({} confidence):", detection.platform, detection.confidence);
+            for reason in &detection.evidence {
+                println!("#   {}", reason);
+            }
+        }
+        println!();
+    }
+
+    print!("{}", config.dump());
+
+    Ok(())
+}
+
+fn serve(address: &Address, rom_file: &str, entry: Option<&str>, sources: &config::Sources, database: &Database) -> Result<(), String> {
+    let rom = load_rom(rom_file, entry, database)?;
+    let config = Config::load(sources, Some(&rom)).map_err(|err| err.to_string())?;
+    let mut machine = Machine::new(rom, &config).map_err(|err| format!("cannot load {}: {}", rom_file, err))?;
+    load_cheats(machine.cpu_mut().bus_mut());
 
-    let rom = Rom::new(&rom_file).unwrap();
+    let mut server = Server::bind(address, machine).map_err(|err| format!("cannot listen on {}: {}", address, err))?;
+    let address = server.address().map_err(|err| err.to_string())?;
+    eprintln!("chip8: listening on {}, paused", address);
 
-    let bus = Bus::new(rom);
+    server.run().map_err(|err| err.to_string())
+}
+
+fn debug(rom_file: &str, entry: Option<&str>, sources: &config::Sources, database: &Database) -> Result<(), String> {
+    let rom = load_rom(rom_file, entry, database)?;
+    let config = Config::load(sources, Some(&rom)).map_err(|err| err.to_string())?;
+    let mut machine = Machine::new(rom, &config).map_err(|err| format!("cannot load {}: {}", rom_file, err))?;
+    load_cheats(machine.cpu_mut().bus_mut());
+
+    let stdin = io::stdin();
+    Debugger::new(machine).run(stdin.lock(), io::stdout()).map_err(|err| err.to_string())
+}
+
+fn graph(format: GraphFormat, rom_file: &str, entry: Option<&str>, sources: &config::Sources, database: &Database) -> Result<(), String> {
+    let rom = load_rom(rom_file, entry, database)?;
+    let config = Config::load(sources, Some(&rom)).map_err(|err| err.to_string())?;
+    let layout = config.layout().map_err(|err| err.to_string())?;
+
+    let graph = Graph::build(rom.program(), layout.load_address, config.entry_point());
+    match format {
+        GraphFormat::Dot => print!("{}", graph.to_dot()),
+        GraphFormat::Json => println!("{}", graph.to_json()),
+    }
+
+    Ok(())
+}
+
+fn decompile(format: DecompileFormat, rom_file: &str, entry: Option<&str>, sources: &config::Sources, database: &Database) -> Result<(), String> {
+    let rom = load_rom(rom_file, entry, database)?;
+    let config = Config::load(sources, Some(&rom)).map_err(|err| err.to_string())?;
+    let layout = config.layout().map_err(|err| err.to_string())?;
+
+    let listing = Listing::new(rom.program(), layout.load_address, config.entry_point(), config.quirks);
+    match format {
+        DecompileFormat::Pseudo => print!("{}", listing.to_pseudo_code()),
+        DecompileFormat::Asm => print!("{}", listing.to_assembly()),
+    }
+
+    Ok(())
+}
+
+fn run(rom_file: &str, entry: Option<&str>, watch: bool, sources: &config::Sources, database: &Database) -> Result<(), String> {
+    let rom = load_rom(rom_file, entry, database)?;
 
-    let mut cpu = Cpu::new(bus);
+    let config = Config::load(sources, Some(&rom)).map_err(|err| err.to_string())?;
+
+    let keymap = build_keymap(&config.keymap)?;
+    let hotkeys = build_hotkeys(&config.hotkeys)?;
+
+    if let Some(detection) = rom.detection() {
+        eprintln!("chip8: unknown ROM, guessing {} ({} confidence)", detection.platform, detection.confidence);
+    }
+
+    let mut title = String::from("CHIP-8 Emulator by Vitaly Shvetsov");
+    if let Some(name) = rom.info().and_then(|info| info.title.as_ref()) {
+        title = format!("{} - {}", name, title);
+    }
+
+    let layout = config.layout().map_err(|err| err.to_string())?;
+
+    let mut cpu = build_cpu(rom, &layout, &config).map_err(|err| format!("cannot load {}: {}", rom_file, err))?;
+
+    let mut watcher = if watch { Some(Watcher::new(rom_file)) } else { None };
+
+    let screen_interval = Duration::from_secs(1) / config.video.refresh_rate;
 
     let mut now = Instant::now();
-    let mut last_instruction = now.clone();
-    let mut last_screen = now.clone();
+    let mut last_screen = now;
+
+    let rewind = if config.rewind.enabled {
+        Some(Rewind::new(config.rewind.interval, config.rewind.budget * 1024))
+    } else {
+        None
+    };
+
+    let mut clock = Clock::new(config.cpu.speed, &config.playback, rewind, now);
+    let mut mode = clock.mode();
+
+    let mut hud = Hud::new(config.video.hud, now);
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
-    let window  = video_subsystem.window("CHIP-8 Emulator by Vitaly Shvetsov", 640, 320)
+    let scale = config.video.scale;
+    let saved = WindowState::load();
+
+    let mut window = match saved {
+        Some(state) => video_subsystem.window(&title, state.width, state.height),
+        None => video_subsystem.window(&title, 64 * scale, 32 * scale),
+    };
+    let mut window = window
         .position_centered()
+        .resizable()
         .opengl()
         .build()
         .unwrap();
 
+    if let Some(state) = saved {
+        window.set_position(WindowPos::Positioned(state.x), WindowPos::Positioned(state.y));
+    }
+    let _ = window.set_minimum_size(64, 32);
+    if config.video.fullscreen {
+        let _ = window.set_fullscreen(FullscreenType::Desktop);
+    }
+    // Geometry to save: fullscreen leaves the window's own untouched.
+    let mut windowed = if config.video.fullscreen { saved } else { None };
+
     let mut renderer = window.into_canvas()
         .index(find_sdl_gl_driver().unwrap())
         .build()
         .unwrap();
 
-    let mut rect = Rect::new(10, 10, 10, 10);
-    
-    let black = sdl2::pixels::Color::RGB(0, 0, 0);
-    let white = sdl2::pixels::Color::RGB(255, 255, 255);
+    // Scale the display texture with nearest-neighbor sampling.
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+
+    let texture_creator = renderer.texture_creator();
+    let mut screen = Screen::new(&texture_creator, config.palette.colors(), &config.video);
+    let mut palettes = palette::NAMES.iter().cycle().skip_while(|&&name| name != config.palette.preset).skip(1);
+
+    let mut recorder: Option<Recorder> = None;
 
     let mut events = sdl_context.event_pump().unwrap();
 
-    let mut beeper = Beeper::new(&sdl_context, Duration::from_millis(120));
+    let mut beeper = if config.audio.enabled {
+        Some(Beeper::new(&sdl_context, &config.audio))
+    } else {
+        None
+    };
 
     loop {
         now = Instant::now();
-        if now - last_instruction > Duration::from_millis(2) {
-            cpu.run_next_instruction();
-
-            last_instruction = now.clone();        
-            
-            cpu.decrease_timers(now.clone());
-            
-            if now - last_screen > Duration::from_millis(10) {
-           
-                let _ = renderer.set_draw_color(black);
-                let _ = renderer.clear();
-
-                for x in 0..64 {
-                    for y in 0..32 {
-                        if is_paint(x, y, &cpu.video) {
-                            let x_pos = (x * 10) as i32;
-                            let y_pos = (y * 10) as i32;
-                            rect.set_y(y_pos);
-                            rect.set_x(x_pos);
-                            let _ = renderer.fill_rect(rect);
-                            let _ = renderer.set_draw_color(white);
-                        }
+
+        if let Some(ref mut watcher) = watcher {
+            if watcher.changed(now) {
+                // Keep the configuration resolved at startup, even though
+                // an edited ROM has a different hash.
+                match load_rom(rom_file, entry, database).and_then(|rom| {
+                    build_cpu(rom, &layout, &config).map_err(|err| format!("cannot load {}: {}", rom_file, err))
+                }) {
+                    Ok(reloaded) => {
+                        cpu = reloaded;
+                        clock.clear_history();
+                        eprintln!("chip8: reloaded {}", rom_file);
                     }
+                    Err(message) => eprintln!("chip8: {}", message),
                 }
-                let _ = renderer.present();
-                
-                last_screen = now.clone();
+            }
+        }
 
-                beeper.set_beep(cpu.make_sound);
+        let mut beep = false;
+        if let Err(fault) = clock.run(&mut cpu, now, |cpu| beep |= cpu.make_sound) {
+            eprintln!("chip8: {} at {:#05x}, paused", fault, cpu.pc());
+        }
+
+        // The buzzer follows the game in real time and slow motion, and
+        // stays quiet rather than stutter when paused, fast-forwarding or
+        // rewinding.
+        if let Some(ref mut beeper) = beeper {
+            beeper.set_pattern(cpu.audio_pattern(), cpu.pitch());
+            match mode {
+                Mode::Normal | Mode::SlowMotion(_) => beeper.set_beep(beep),
+                Mode::Paused | Mode::FastForward(_) | Mode::Rewinding => beeper.silence(),
             }
+        }
 
-            for event in events.poll_iter() {
-                match event {
-                    Event::Quit {..} | Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
-                        process::exit(1);
-                    },
+        if now - last_screen > screen_interval {
+            if cpu.take_display_dirty() || screen.animating() {
+                screen.update(cpu.display());
+            }
+            screen.draw(&mut renderer);
 
-                    Event::KeyDown { keycode: Some(Keycode::X), ..} => {
-                        cpu.read_keys(0x0, true);
-                    },
+            if let Some(mut recording) = recorder.take() {
+                recorder = record_frame(&mut recording, screen.image(), rom_file, now)
+                    .map(|()| recording)
+                    .map_err(|message| eprintln!("chip8: {}", message))
+                    .ok();
+            }
 
-                    Event::KeyDown { keycode: Some(Keycode::Num1), ..} => {
-                        cpu.read_keys(0x1, true);
-                    },
+            if hud.visible {
+                // Glyph pixels a fifth of a display pixel, as at startup.
+                let size = screen.viewport(&renderer).map_or(1, |viewport| (viewport.height() / 160).max(1));
+                hud.draw(&mut renderer, &cpu, clock.instructions_per_frame(), size);
+            }
+            renderer.present();
+            hud.frame_drawn(now, clock.instructions());
 
-                    Event::KeyDown { keycode: Some(Keycode::Num2), ..} => {
-                        cpu.read_keys(0x2, true);
-                    },
+            last_screen = now;
+        }
 
-                    Event::KeyDown { keycode: Some(Keycode::Num3), ..} => {
-                        cpu.read_keys(0x3, true);
-                    },
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit {..} | Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
+                    if let Some(recording) = recorder.take() {
+                        eprintln!("chip8: {}", finish_recording(recording, now).unwrap_or_else(|message| message));
+                    }
+
+                    let window = renderer.window();
+                    if let Some(state) = windowed.or_else(|| window_state(window)) {
+                        if let Err(err) = state.save() {
+                            eprintln!("chip8: cannot save the window size: {}", err);
+                        }
+                    }
+                    return Ok(());
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.fullscreen => {
+                    let window = renderer.window_mut();
+                    if window.fullscreen_state() == FullscreenType::Off {
+                        windowed = window_state(window);
+                        let _ = window.set_fullscreen(FullscreenType::Desktop);
+                    } else {
+                        let _ = window.set_fullscreen(FullscreenType::Off);
+                        windowed = None;
+                    }
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.reset => {
+                    cpu.reset();
+                    clock.clear_history();
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.hud => {
+                    hud.visible = !hud.visible;
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.palette => {
+                    let name = palettes.next().unwrap();
+                    screen.set_colors(palette::preset(name).unwrap());
+                    screen.update(cpu.display());
+                    eprintln!("chip8: palette {}", name);
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.filter => {
+                    screen.set_filter(screen.filter().next());
+                    eprintln!("chip8: filter {:?}", screen.filter());
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.screenshot => {
+                    match save_screenshot(screen.image(), rom_file) {
+                        Ok(path) => eprintln!("chip8: saved {}", path.display()),
+                        Err(message) => eprintln!("chip8: {}", message),
+                    }
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.record => {
+                    let result = match recorder.take() {
+                        Some(recording) => finish_recording(recording, now),
+                        None => start_recording(screen.image(), rom_file, now).map(|recording| {
+                            recorder = Some(recording);
+                            "recording".into()
+                        }),
+                    };
+                    eprintln!("chip8: {}", result.unwrap_or_else(|message| message));
+                },
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, ..} if keycode == hotkeys.pause => {
+                    clock.paused = !clock.paused;
+                },
+
+                Event::KeyDown { keycode: Some(keycode), ..} if keycode == hotkeys.frame_advance => {
+                    clock.advance_frame();
+                },
+
+                Event::KeyDown { keycode: Some(keycode), ..} if keycode == hotkeys.fast_forward => {
+                    clock.fast_forward = true;
+                },
+
+                Event::KeyUp { keycode: Some(keycode), ..} if keycode == hotkeys.fast_forward => {
+                    clock.fast_forward = false;
+                },
+
+                Event::KeyDown { keycode: Some(keycode), ..} if keycode == hotkeys.slow_motion => {
+                    clock.slow_motion = true;
+                },
+
+                Event::KeyUp { keycode: Some(keycode), ..} if keycode == hotkeys.slow_motion => {
+                    clock.slow_motion = false;
+                },
+
+                Event::KeyDown { keycode: Some(keycode), ..} if keycode == hotkeys.rewind => {
+                    clock.rewinding = true;
+                },
+
+                Event::KeyUp { keycode: Some(keycode), ..} if keycode == hotkeys.rewind => {
+                    clock.rewinding = false;
+                },
+
+                Event::KeyDown { keycode: Some(keycode), ..} => {
+                    if let Some(&key) = keymap.get(&keycode) {
+                        cpu.read_keys(key, true);
+                    }
+                },
 
-                    Event::KeyDown { keycode: Some(Keycode::Q), ..} => {
-                        cpu.read_keys(0x4, true);
-                    },
+                Event::KeyUp { keycode: Some(keycode), ..} => {
+                    if let Some(&key) = keymap.get(&keycode) {
+                        cpu.read_keys(key, false);
+                    }
+                },
 
-                    Event::KeyDown { keycode: Some(Keycode::W), ..} => {
-                        cpu.read_keys(0x5, true);
-                    },
+                _ => {}
+            }
+        }
 
-                    Event::KeyDown { keycode: Some(Keycode::E), ..} => {
-                        cpu.read_keys(0x6, true);
-                    },
+        if clock.mode() != mode {
+            mode = clock.mode();
+            let title = match mode {
+                Mode::Normal => title.clone(),
+                _ => format!("{} [{}]", title, mode),
+            };
+            let _ = renderer.window_mut().set_title(&title);
+        }
 
-                    Event::KeyDown { keycode: Some(Keycode::A), ..} => {
-                        cpu.read_keys(0x7, true);
-                    },
+        if mode != Mode::FastForward(None) {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
 
-                    Event::KeyDown { keycode: Some(Keycode::S), ..} => {
-                        cpu.read_keys(0x8, true);
-                    },
+/// The bundled ROM database extended with the user's, or the bundled one
+/// alone when the user's cannot be read.
+fn open_database() -> Database {
+    Database::open().unwrap_or_else(|err| {
+        eprintln!("chip8: ignoring the local ROM database: {}", err);
+        Database::bundled()
+    })
+}
 
-                    Event::KeyDown { keycode: Some(Keycode::D), ..} => {
-                        cpu.read_keys(0x9, true);
-                    },
+/// Loads a ROM, asking which one to use if an archive holds several and
+/// there is a terminal to ask on.
+fn load_rom(rom_file: &str, entry: Option<&str>, database: &Database) -> Result<Rom, String> {
+    match Rom::open(rom_file, entry, database) {
+        Err(rom::Error::Ambiguous(ref names)) if rom_file != "-" && io::stdin().is_terminal() => {
+            let name = pick_entry(rom_file, names)?;
+            Rom::open(rom_file, Some(&name), database).map_err(|err| format!("cannot load {}: {}", rom_file, err))
+        }
+        result => result.map_err(|err| format!("cannot load {}: {}", rom_file, err)),
+    }
+}
 
-                    Event::KeyDown { keycode: Some(Keycode::Z), ..} => {
-                        cpu.read_keys(0xa, true);
-                    },
+fn pick_entry(rom_file: &str, names: &[String]) -> Result<String, String> {
+    eprintln!("{} holds several ROMs:", rom_file);
+    for (index, name) in names.iter().enumerate() {
+        eprintln!("  {}) {}", index + 1, name);
+    }
 
-                    Event::KeyDown { keycode: Some(Keycode::C), ..} => {
-                        cpu.read_keys(0xb, true);
-                    },
+    let stdin = io::stdin();
+    loop {
+        eprint!("pick one [1-{}]: ", names.len());
 
-                    Event::KeyDown { keycode: Some(Keycode::Num4), ..} => {
-                        cpu.read_keys(0xc, true);
-                    },
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(|err| err.to_string())? == 0 {
+            return Err("no ROM picked".into());
+        }
 
-                    Event::KeyDown { keycode: Some(Keycode::R), ..} => {
-                        cpu.read_keys(0xd, true);
-                    },
+        match line.trim().parse::<usize>() {
+            Ok(choice) if choice >= 1 && choice <= names.len() => return Ok(names[choice - 1].clone()),
+            _ => continue,
+        }
+    }
+}
 
-                    Event::KeyDown { keycode: Some(Keycode::F), ..} => {
-                        cpu.read_keys(0xe, true);
-                    },
+fn build_cpu(rom: Rom, layout: &Layout, config: &Config) -> Result<Cpu, rom::Error> {
+    let mut bus = Bus::new(rom, layout.clone(), config.memory)?;
+    load_cheats(&mut bus);
 
-                    Event::KeyDown { keycode: Some(Keycode::V), ..} => {
-                        cpu.read_keys(0xf, true);
-                    },
+    Ok(Cpu::new(bus, config.quirks, config.entry_point()))
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::X), ..} => {
-                        cpu.read_keys(0x0, false);
-                    },
+/// Freezes the cheats saved for the bus's ROM.
+fn load_cheats(bus: &mut Bus) {
+    let hash = match bus.rom() {
+        Some(rom) => rom.hash().to_string(),
+        None => return,
+    };
+
+    let cheats = match cheat::load(&hash) {
+        Ok(cheats) => cheats,
+        Err(err) => return eprintln!("chip8: cannot load cheats: {}", err),
+    };
+    for cheat in &cheats {
+        if let Err(err) = bus.freeze(cheat.address, cheat.value) {
+            eprintln!("chip8: cannot freeze {}: {}", cheat, err);
+        }
+    }
+    if !cheats.is_empty() {
+        eprintln!("chip8: {} cheats from {}", cheats.len(), cheat::path(&hash).unwrap().display());
+    }
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::Num1), ..} => {
-                        cpu.read_keys(0x1, false);
-                    },
+fn build_keymap(keymap: &config::Keymap) -> Result<HashMap<Keycode, usize>, String> {
+    let mut keys = HashMap::new();
 
-                    Event::KeyUp { keycode: Some(Keycode::Num2), ..} => {
-                        cpu.read_keys(0x2, false);
-                    },
+    for (key, name) in keymap.keys.iter().enumerate() {
+        keys.insert(key_by_name(&format!("keymap.{:X}", key), name)?, key);
+    }
 
-                    Event::KeyUp { keycode: Some(Keycode::Num3), ..} => {
-                        cpu.read_keys(0x3, false);
-                    },
+    Ok(keys)
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::Q), ..} => {
-                        cpu.read_keys(0x4, false);
-                    },
+fn build_hotkeys(hotkeys: &config::Hotkeys) -> Result<Hotkeys, String> {
+    Ok(Hotkeys {
+        reset: key_by_name("hotkeys.reset", &hotkeys.reset)?,
+        pause: key_by_name("hotkeys.pause", &hotkeys.pause)?,
+        frame_advance: key_by_name("hotkeys.frame_advance", &hotkeys.frame_advance)?,
+        fast_forward: key_by_name("hotkeys.fast_forward", &hotkeys.fast_forward)?,
+        slow_motion: key_by_name("hotkeys.slow_motion", &hotkeys.slow_motion)?,
+        rewind: key_by_name("hotkeys.rewind", &hotkeys.rewind)?,
+        hud: key_by_name("hotkeys.hud", &hotkeys.hud)?,
+        palette: key_by_name("hotkeys.palette", &hotkeys.palette)?,
+        filter: key_by_name("hotkeys.filter", &hotkeys.filter)?,
+        screenshot: key_by_name("hotkeys.screenshot", &hotkeys.screenshot)?,
+        record: key_by_name("hotkeys.record", &hotkeys.record)?,
+        fullscreen: key_by_name("hotkeys.fullscreen", &hotkeys.fullscreen)?,
+    })
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::W), ..} => {
-                        cpu.read_keys(0x5, false);
-                    },
+/// The window's geometry, unless it is fullscreen.
+fn window_state(window: &Window) -> Option<WindowState> {
+    if window.fullscreen_state() != FullscreenType::Off {
+        return None;
+    }
 
-                    Event::KeyUp { keycode: Some(Keycode::E), ..} => {
-                        cpu.read_keys(0x6, false);
-                    },
+    let (x, y) = window.position();
+    let (width, height) = window.size();
 
-                    Event::KeyUp { keycode: Some(Keycode::A), ..} => {
-                        cpu.read_keys(0x7, false);
-                    },
+    Some(WindowState { x, y, width, height })
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::S), ..} => {
-                        cpu.read_keys(0x8, false);
-                    },
+/// A new file in the current directory named after the ROM and the time.
+fn capture_path(rom_file: &str, extension: &str) -> PathBuf {
+    let stem = match Path::new(rom_file).file_stem() {
+        Some(stem) if rom_file != "-" => stem.to_string_lossy().into_owned(),
+        _ => "chip8".into(),
+    };
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis());
 
-                    Event::KeyUp { keycode: Some(Keycode::D), ..} => {
-                        cpu.read_keys(0x9, false);
-                    },
+    PathBuf::from(format!("{}-{}.{}", stem, millis, extension))
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::Z), ..} => {
-                        cpu.read_keys(0xa, false);
-                    },
+fn save_screenshot(image: &Image, rom_file: &str) -> Result<PathBuf, String> {
+    let path = capture_path(rom_file, "png");
 
-                    Event::KeyUp { keycode: Some(Keycode::C), ..} => {
-                        cpu.read_keys(0xb, false);
-                    },
+    File::create(&path)
+        .and_then(|file| capture::write_png(BufWriter::new(file), image))
+        .map_err(|err| format!("cannot save {}: {}", path.display(), err))?;
 
-                    Event::KeyUp { keycode: Some(Keycode::Num4), ..} => {
-                        cpu.read_keys(0xc, false);
-                    },
+    Ok(path)
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::R), ..} => {
-                        cpu.read_keys(0xd, false);
-                    },
+fn start_recording(image: &Image, rom_file: &str, now: Instant) -> Result<Recorder, String> {
+    let path = capture_path(rom_file, "gif");
 
-                    Event::KeyUp { keycode: Some(Keycode::F), ..} => {
-                        cpu.read_keys(0xe, false);
-                    },
+    Recorder::start(&path, image, now).map_err(|err| format!("cannot record {}: {}", path.display(), err))
+}
 
-                    Event::KeyUp { keycode: Some(Keycode::V), ..} => {
-                        cpu.read_keys(0xf, false);
-                    },
+fn finish_recording(recording: Recorder, now: Instant) -> Result<String, String> {
+    recording.finish(now)
+        .map(|path| format!("saved {}", path.display()))
+        .map_err(|err| format!("cannot finish recording: {}", err))
+}
 
-                    _ => {}
-                }
-            }
-        }
+/// Adds the picture to the recording, moving on to a new file when the
+/// resolution or filter changed its size.
+fn record_frame(recording: &mut Recorder, image: &Image, rom_file: &str, now: Instant) -> Result<(), String> {
+    if !recording.fits(image) {
+        let next = start_recording(image, rom_file, now)?;
+        let finished = mem::replace(recording, next);
+        eprintln!("chip8: {}", finish_recording(finished, now)?);
+        return Ok(());
     }
+
+    recording.frame(image, now).map_err(|err| format!("cannot record: {}", err))
 }
 
-fn is_paint(x: usize, y: usize, vid_buffer: &[[u8; 64]; 32]) -> bool {
-    vid_buffer[y][x] == 1
+fn key_by_name(setting: &str, name: &str) -> Result<Keycode, String> {
+    Keycode::from_name(name)
+        .ok_or_else(|| format!("invalid config: {} = \"{}\" is not a known key name", setting, name))
 }
 
 fn find_sdl_gl_driver() -> Option<u32> {
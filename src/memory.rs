@@ -0,0 +1,231 @@
+use core::fmt;
+
+#[cfg(feature = "std")]
+use rom::{self, Rom};
+
+/// Where programs go unless their platform says otherwise.
+#[cfg(feature = "std")]
+pub const DEFAULT_LOAD_ADDRESS: u16 = 0x200;
+
+/// RAM when no storage is given: on the heap with `std`, otherwise the
+/// COSMAC VIP's 4 KiB.
+#[cfg(feature = "std")]
+pub type Ram = Vec<u8>;
+#[cfg(not(feature = "std"))]
+pub type Ram = [u8; 4096];
+
+/// Font data when none is given: owned with `std`, so fonts can be read
+/// from files, otherwise the built-in tables.
+#[cfg(feature = "std")]
+pub type Glyphs = Vec<u8>;
+#[cfg(not(feature = "std"))]
+pub type Glyphs = &'static [u8];
+
+/// Anything RAM can live in: a `Vec`, an array or a borrowed slice.
+pub trait Storage: AsRef<[u8]> + AsMut<[u8]> {}
+
+impl<S: AsRef<[u8]> + AsMut<[u8]>> Storage for S {}
+
+/// Why a program and its fonts cannot be laid out in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The program does not fit in memory above its load address.
+    TooLarge { size: usize, load_address: u16, memory_size: usize },
+    /// The named font does not fit in memory at its address.
+    FontOutside(&'static str, u16),
+    /// The named font, at its address, overlaps the program at another.
+    FontOverlap(&'static str, u16, u16),
+    FontsOverlap,
+    /// The storage is not the size of the memory.
+    StorageSize { expected: usize, found: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TooLarge { size, load_address, memory_size } => {
+                write!(
+                    f, "ROM is {} bytes but only {} fit between {:#05x} and the end of the {} KiB memory",
+                    size, memory_size.saturating_sub(load_address as usize), load_address, memory_size / 1024,
+                )
+            }
+            Error::FontOutside(name, address) => write!(f, "{} at {:#05x} does not fit in memory", name, address),
+            Error::FontOverlap(name, address, start) => {
+                write!(f, "{} at {:#05x} overlaps the program at {:#05x}", name, address, start)
+            }
+            Error::FontsOverlap => write!(f, "font and large font overlap"),
+            Error::StorageSize { expected, found } => {
+                write!(f, "storage holds {} bytes but memory is {} bytes", found, expected)
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Where the program and fonts go in memory.
+#[derive(Clone, Debug)]
+pub struct Layout<F = Glyphs> {
+    pub memory_size: usize,
+    pub load_address: u16,
+    /// 4x5 glyphs for FX29.
+    pub font: F,
+    pub font_address: u16,
+    /// 8x10 glyphs for FX30.
+    pub large_font: F,
+    pub large_font_address: u16,
+}
+
+impl<F: AsRef<[u8]>> Layout<F> {
+    /// Checks that a program of `size` bytes and the fonts fit in memory
+    /// without overlapping.
+    pub fn check(&self, size: usize) -> Result<(), Error> {
+        let start = self.load_address as usize;
+        let end = start + size;
+
+        if end > self.memory_size {
+            return Err(Error::TooLarge {
+                size,
+                load_address: self.load_address,
+                memory_size: self.memory_size,
+            });
+        }
+
+        let fonts = [
+            ("font", self.font_address, self.font.as_ref().len()),
+            ("large font", self.large_font_address, self.large_font.as_ref().len()),
+        ];
+
+        for &(name, address, size) in &fonts {
+            if address as usize + size > self.memory_size {
+                return Err(Error::FontOutside(name, address));
+            }
+            if (address as usize) < end && start < address as usize + size {
+                return Err(Error::FontOverlap(name, address, self.load_address));
+            }
+        }
+        let (font, large) = (fonts[0].1 as usize, fonts[1].1 as usize);
+        if font < large + fonts[1].2 && large < font + fonts[0].2 {
+            return Err(Error::FontsOverlap);
+        }
+
+        Ok(())
+    }
+}
+
+/// The machine's RAM.
+#[derive(Clone)]
+pub struct Memory<S = Ram> {
+    data: S,
+}
+
+#[cfg(feature = "std")]
+impl Memory {
+    /// Fresh RAM holding the fonts and the program where `layout` says.
+    pub fn new(rom: &Rom, layout: &Layout) -> Result<Memory, rom::Error> {
+        Ok(Memory::with_storage(vec![0; layout.memory_size], layout, rom.program())?)
+    }
+}
+
+impl<S: Storage> Memory<S> {
+    /// RAM in `storage`, which must be the size of the layout's memory,
+    /// holding the fonts and the program where `layout` says.
+    pub fn with_storage<F: AsRef<[u8]>>(storage: S, layout: &Layout<F>, program: &[u8]) -> Result<Memory<S>, Error> {
+        let mut memory = Memory { data: storage };
+        memory.install(layout, program)?;
+
+        Ok(memory)
+    }
+
+    /// Clears RAM and puts the fonts and the program back where `layout`
+    /// says, as on power-up.
+    pub fn install<F: AsRef<[u8]>>(&mut self, layout: &Layout<F>, program: &[u8]) -> Result<(), Error> {
+        if self.size() != layout.memory_size {
+            return Err(Error::StorageSize { expected: layout.memory_size, found: self.size() });
+        }
+        layout.check(program.len())?;
+
+        let data = self.data.as_mut();
+        data.iter_mut().for_each(|byte| *byte = 0);
+
+        let pieces = [
+            (layout.font_address, layout.font.as_ref()),
+            (layout.large_font_address, layout.large_font.as_ref()),
+            (layout.load_address, program),
+        ];
+        for &(address, bytes) in &pieces {
+            let start = address as usize;
+            data[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.as_ref().len()
+    }
+
+    pub fn load(&self, address: u16) -> u8 {
+        self.data.as_ref()[address as usize]
+    }
+
+    pub fn store(&mut self, address: u16, value: u8) {
+        self.data.as_mut()[address as usize] = value;
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.data.as_mut()
+    }
+
+    /// Overwrites the whole RAM, which must be of the same size.
+    pub fn copy_from(&mut self, data: &[u8]) {
+        self.data.as_mut().copy_from_slice(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(load_address: u16) -> Layout<&'static [u8]> {
+        Layout {
+            memory_size: 0x1000,
+            load_address,
+            font: &[0xF0; 80],
+            font_address: 0x000,
+            large_font: &[0xFF; 160],
+            large_font_address: 0x050,
+        }
+    }
+
+    #[test]
+    fn fits_programs_up_to_the_end_of_memory() {
+        assert_eq!(layout(0x200).check(0xE00), Ok(()));
+        assert_eq!(
+            layout(0x200).check(0xE01),
+            Err(Error::TooLarge { size: 0xE01, load_address: 0x200, memory_size: 0x1000 }),
+        );
+        assert_eq!(
+            layout(0x600).check(0xE00).unwrap_err().to_string(),
+            "ROM is 3584 bytes but only 2560 fit between 0x600 and the end of the 4 KiB memory",
+        );
+    }
+
+    #[test]
+    fn keeps_fonts_apart_from_the_program_and_each_other() {
+        // The large font runs from 0x050 to 0x0F0.
+        assert_eq!(layout(0x0F0).check(2), Ok(()));
+        assert_eq!(layout(0x0EE).check(2), Err(Error::FontOverlap("large font", 0x050, 0x0EE)));
+
+        let mut shifted = layout(0x200);
+        shifted.large_font_address = 0x040;
+        assert_eq!(shifted.check(2), Err(Error::FontsOverlap));
+
+        shifted.large_font_address = 0xF80;
+        assert_eq!(shifted.check(2), Err(Error::FontOutside("large font", 0xF80)));
+    }
+}
@@ -0,0 +1,21 @@
+use config::Rgb;
+
+/// Names of the built-in palettes.
+pub const NAMES: [&str; 6] = ["mono", "octo", "amber", "green", "lcd", "cga"];
+
+/// A built-in palette by name: the background, the color of the first
+/// plane, and for XO-CHIP the second plane and both planes overlapping.
+pub fn preset(name: &str) -> Option<[Rgb; 4]> {
+    let colors = match name {
+        "mono" => [0x000000, 0xffffff, 0xaaaaaa, 0x555555],
+        // Octo's defaults, which most XO-CHIP games were made with.
+        "octo" => [0x996600, 0xffcc00, 0xff6600, 0x662200],
+        "amber" => [0x140c00, 0xffb000, 0xb07000, 0x603c00],
+        "green" => [0x051405, 0x33ff66, 0x22aa44, 0x116622],
+        "lcd" => [0x9bbc0f, 0x0f380f, 0x306230, 0x8bac0f],
+        "cga" => [0x000000, 0x55ffff, 0xff55ff, 0xffffff],
+        _ => return None,
+    };
+
+    Some(colors.map(|color: u32| Rgb((color >> 16) as u8, (color >> 8) as u8, color as u8)))
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+use quirks::Quirks;
+
+/// The interpreter a ROM was written for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    /// The original CHIP-8 interpreter on the COSMAC VIP.
+    Vip,
+    /// The ETI-660 learning computer, which loads programs at 0x600.
+    Eti660,
+    /// SUPER-CHIP 1.1 on the HP 48.
+    Schip,
+    /// Octo's XO-CHIP extension.
+    Xochip,
+}
+
+impl Platform {
+    /// Quirks the platform's interpreter exhibits.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Vip | Platform::Eti660 => Quirks {
+                shift: false,
+                load_store: true,
+                jump: false,
+                vf_reset: true,
+                clipping: true,
+                large_sprites: false,
+            },
+            Platform::Schip => Quirks {
+                shift: true,
+                load_store: false,
+                jump: true,
+                vf_reset: false,
+                clipping: true,
+                large_sprites: true,
+            },
+            Platform::Xochip => Quirks {
+                shift: false,
+                load_store: true,
+                jump: false,
+                vf_reset: false,
+                clipping: false,
+                large_sprites: true,
+            },
+        }
+    }
+
+    /// Bytes of addressable memory, including the interpreter area.
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::Vip | Platform::Eti660 | Platform::Schip => 0x1000,
+            Platform::Xochip => 0x10000,
+        }
+    }
+
+    /// Address programs are loaded at and start running from.
+    pub fn load_address(self) -> u16 {
+        match self {
+            Platform::Eti660 => 0x600,
+            _ => 0x200,
+        }
+    }
+
+    /// Name of the built-in 4x5 font the platform's interpreter shipped.
+    pub fn font(self) -> &'static str {
+        match self {
+            Platform::Vip => "vip",
+            Platform::Eti660 => "eti660",
+            Platform::Schip | Platform::Xochip => "schip",
+        }
+    }
+
+    /// Address of the 4x5 font, in the interpreter area below the program.
+    ///
+    /// The VIP and the ETI-660 kept their fonts in interpreter ROM, so this
+    /// is 0x050, where emulators of them and the test ROMs written against
+    /// those put it. Octo, and SUPER-CHIP emulators after it, use 0x000.
+    pub fn font_address(self) -> u16 {
+        match self {
+            Platform::Vip | Platform::Eti660 => 0x050,
+            Platform::Schip | Platform::Xochip => 0x000,
+        }
+    }
+
+    /// Address of the 8x10 font, right after the 4x5 one.
+    pub fn large_font_address(self) -> u16 {
+        self.font_address() + 0x050
+    }
+
+    /// Instructions per second that most ROMs for the platform expect.
+    pub fn speed(self) -> u32 {
+        match self {
+            Platform::Vip | Platform::Eti660 => 700,
+            Platform::Schip => 1800,
+            Platform::Xochip => 10000,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Platform::Vip => "CHIP-8 (VIP)",
+            Platform::Eti660 => "CHIP-8 (ETI-660)",
+            Platform::Schip => "SUPER-CHIP",
+            Platform::Xochip => "XO-CHIP",
+        };
+        f.write_str(name)
+    }
+}
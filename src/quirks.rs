@@ -0,0 +1,21 @@
+/// Behaviour differences between CHIP-8 interpreters.
+///
+/// The defaults match what this emulator has always done, so existing ROMs
+/// keep running unchanged unless a quirk is switched on.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(default, deny_unknown_fields))]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VX in place instead of copying VY first.
+    pub shift: bool,
+    /// FX55/FX65 leave I pointing past the last register they touched.
+    pub load_store: bool,
+    /// BNNN jumps to XNN + VX instead of NNN + V0.
+    pub jump: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to zero.
+    pub vf_reset: bool,
+    /// Sprites are clipped at the screen edge instead of wrapping around.
+    pub clipping: bool,
+    /// DXY0 draws a 16x16 sprite instead of nothing.
+    pub large_sprites: bool,
+}
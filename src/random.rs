@@ -0,0 +1,67 @@
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use rand;
+
+/// A seed for runs that need not be reproducible.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub fn entropy() -> u64 {
+    rand::random()
+}
+
+/// WebAssembly has no entropy source of its own, so machines there start
+/// from a fixed seed until the host provides one.
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+pub fn entropy() -> u64 {
+    0
+}
+
+/// Where CXNN gets its random bytes. Without `std` there is no entropy to
+/// seed `Random` from, so embedders can supply a hardware generator
+/// instead.
+pub trait Rng {
+    fn next_u8(&mut self) -> u8;
+}
+
+/// The xorshift64* generator behind CXNN.
+///
+/// Unlike a thread-local generator its whole state is one `u64`, so it is
+/// part of a machine snapshot and replays the same numbers after a restore.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Xorshift never leaves the all-zero state.
+        let state = if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed };
+
+        Random { state }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    /// A number from 0 up to but excluding 1.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Rng for Random {
+    fn next_u8(&mut self) -> u8 {
+        Random::next_u8(self)
+    }
+}
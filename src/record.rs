@@ -0,0 +1,66 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chip8::capture::GifEncoder;
+use chip8::video::Image;
+
+/// Shortest frame delay browsers honor; shorter ones are slowed down.
+const MIN_DELAY: Duration = Duration::from_millis(20);
+
+/// Records the display into an animated GIF.
+///
+/// A frame's delay is only known once the next one comes, so the latest
+/// frame is held back until then.
+pub struct Recorder {
+    encoder: GifEncoder<BufWriter<File>>,
+    path: PathBuf,
+    held: Image,
+    since: Instant,
+}
+
+impl Recorder {
+    pub fn start(path: &Path, image: &Image, now: Instant) -> io::Result<Recorder> {
+        let file = BufWriter::new(File::create(path)?);
+
+        Ok(Recorder {
+            encoder: GifEncoder::new(file, image.width, image.height)?,
+            path: path.to_path_buf(),
+            held: image.clone(),
+            since: now,
+        })
+    }
+
+    /// Whether `image` has the size of the animation.
+    pub fn fits(&self, image: &Image) -> bool {
+        image.width == self.encoder.width() && image.height == self.encoder.height()
+    }
+
+    /// Adds the display as it is at `now`, if enough time passed since the
+    /// last frame.
+    pub fn frame(&mut self, image: &Image, now: Instant) -> io::Result<()> {
+        if now - self.since < MIN_DELAY || *image == self.held {
+            return Ok(());
+        }
+
+        self.encoder.frame(&self.held, centiseconds(now - self.since))?;
+        self.held = image.clone();
+        self.since = now;
+
+        Ok(())
+    }
+
+    /// Writes the last frame and closes the file, returning its path.
+    pub fn finish(mut self, now: Instant) -> io::Result<PathBuf> {
+        let delay = centiseconds((now - self.since).max(MIN_DELAY));
+        self.encoder.frame(&self.held, delay)?;
+        self.encoder.finish()?;
+
+        Ok(self.path)
+    }
+}
+
+fn centiseconds(duration: Duration) -> u16 {
+    (duration.as_millis() / 10).min(u16::MAX as u128) as u16
+}
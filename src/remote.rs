@@ -0,0 +1,695 @@
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use cheat::{self, Cheat, Comparison, Search};
+use machine::{Machine, FRAME_RATE};
+
+/// Longest request accepted, enough for the state of a 64 KiB machine.
+const MAX_REQUEST: usize = 1 << 20;
+
+/// Most output held for a client that does not read it, before it is
+/// dropped.
+const MAX_OUTPUT: usize = 16 << 20;
+
+/// Most frames one `step` runs, a minute's worth.
+const MAX_STEP: u32 = 60 * FRAME_RATE;
+
+/// Longest stretch of real time made up for at once when running.
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// A well-formed call the machine could not carry out.
+const FAILED: i64 = -32000;
+
+/// Where the server listens: a loopback TCP address, or a Unix socket
+/// written `unix:<path>`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Address {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Address, String> {
+        if let Some(path) = text.strip_prefix("unix:") {
+            return unix_address(path);
+        }
+
+        let address = text.to_socket_addrs()
+            .map_err(|err| format!("invalid address {}: {}", text, err))?
+            .next()
+            .ok_or_else(|| format!("{} resolves to no address", text))?;
+
+        if !address.ip().is_loopback() {
+            return Err(format!("{} is not a loopback address; the remote control only listens locally", text));
+        }
+
+        Ok(Address::Tcp(address))
+    }
+}
+
+#[cfg(unix)]
+fn unix_address(path: &str) -> Result<Address, String> {
+    Ok(Address::Unix(PathBuf::from(path)))
+}
+
+#[cfg(not(unix))]
+fn unix_address(_: &str) -> Result<Address, String> {
+    Err("Unix sockets are not available on this platform".into())
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Address::Tcp(ref address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            Address::Unix(ref path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    fn bind(address: &Address) -> io::Result<Listener> {
+        let listener = match *address {
+            Address::Tcp(address) => Listener::Tcp(TcpListener::bind(address)?),
+            #[cfg(unix)]
+            Address::Unix(ref path) => {
+                // A socket left behind by a server that died is in the way,
+                // but one that still answers belongs to a running server,
+                // and anything other than a socket is not ours to remove.
+                let socket = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket());
+                if socket && UnixStream::connect(path).is_err() {
+                    fs::remove_file(path)?;
+                }
+                Listener::Unix(UnixListener::bind(path)?, path.clone())
+            }
+        };
+
+        match listener {
+            Listener::Tcp(ref listener) => listener.set_nonblocking(true)?,
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _) => listener.set_nonblocking(true)?,
+        }
+
+        Ok(listener)
+    }
+
+    fn accept(&self) -> io::Result<Stream> {
+        let stream = match *self {
+            Listener::Tcp(ref listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                Stream::Tcp(stream)
+            }
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _) => Stream::Unix(listener.accept()?.0),
+        };
+
+        match stream {
+            Stream::Tcp(ref stream) => stream.set_nonblocking(true)?,
+            #[cfg(unix)]
+            Stream::Unix(ref stream) => stream.set_nonblocking(true)?,
+        }
+
+        Ok(stream)
+    }
+
+    fn address(&self) -> io::Result<Address> {
+        match *self {
+            Listener::Tcp(ref listener) => Ok(Address::Tcp(listener.local_addr()?)),
+            #[cfg(unix)]
+            Listener::Unix(_, ref path) => Ok(Address::Unix(path.clone())),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if let Listener::Unix(_, ref path) = *self {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref mut stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+/// A connection, with what it sent that is not a whole line yet and what
+/// is still to be sent to it.
+struct Client {
+    stream: Stream,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    subscribed: bool,
+    closed: bool,
+}
+
+impl Client {
+    fn new(stream: Stream) -> Client {
+        Client {
+            stream,
+            input: Vec::new(),
+            output: Vec::new(),
+            subscribed: false,
+            closed: false,
+        }
+    }
+
+    /// Reads what has arrived, returning the complete lines.
+    fn receive(&mut self) -> Vec<String> {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(count) => {
+                    self.input.extend_from_slice(&buffer[..count]);
+                    if self.input.len() > MAX_REQUEST {
+                        break;
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.input.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.input.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim().to_string());
+        }
+        if self.input.len() > MAX_REQUEST {
+            self.closed = true;
+        }
+
+        lines
+    }
+
+    fn send(&mut self, message: &Value) {
+        if self.closed {
+            return;
+        }
+        serde_json::to_writer(&mut self.output, message).expect("JSON values serialize");
+        self.output.push(b'\n');
+
+        if self.output.len() > MAX_OUTPUT {
+            self.output.clear();
+            self.closed = true;
+        }
+    }
+
+    /// Writes as much of the pending output as the socket takes.
+    fn flush(&mut self) {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => {
+                    self.closed = true;
+                    return;
+                }
+                Ok(count) => {
+                    self.output.drain(..count);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.closed = true;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new<S: Into<String>>(code: i64, message: S) -> RpcError {
+        RpcError { code, message: message.into() }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepParams {
+    #[serde(default = "one_frame")]
+    frames: u32,
+}
+
+fn one_frame() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeysParams {
+    keys: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MemoryParams {
+    address: usize,
+    length: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StateParams {
+    state: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchParams {
+    compare: Comparison,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FreezeParams {
+    address: u16,
+    value: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddressParams {
+    address: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoParams {}
+
+/// Serves a `Machine` to any number of local clients, on one thread.
+///
+/// The protocol is JSON-RPC 2.0, one message per line. The server starts
+/// paused so that a client can step the machine frame by frame from the
+/// first one; `resume` runs it in real time. Methods:
+///
+/// - `status`: `{paused, frame, speed}`
+/// - `pause`, `resume`: as `status`, afterwards
+/// - `step {frames = 1}`: runs frames, returning `{frame}`
+/// - `reset`: restarts the program
+/// - `get_keys`, `set_keys {keys}`: pressed keys, as a list of 0 to 15
+/// - `get_display`: `{width, height, pixels}`, with a color index per pixel,
+///   row by row
+/// - `get_registers`: `{pc, i, sp, stack, v, delay_timer, sound_timer}`
+/// - `read_memory {address, length}`: `{address, bytes}`
+/// - `save_state`: `{state}`, the machine state in hex
+/// - `load_state {state}`: restores a `save_state` result
+/// - `subscribe`, `unsubscribe`: start or stop `frame` notifications of
+///   `{frame, sound}` after every frame run
+/// - `search_start`: snapshots RAM for a cheat search, returning
+///   `{count}` candidate addresses
+/// - `search {compare}`: keeps the candidates whose byte is `"equal"`,
+///   `"changed"`, `"increased"` or `"decreased"` since the last step, or
+///   now `{"value": n}`, returning `{count, addresses}`
+/// - `freeze {address, value}`, `unfreeze {address}`: hold an address at
+///   a value every frame, or let it go; `unfreeze` returns whether it was
+///   frozen
+/// - `get_cheats`: `{cheats}`, the frozen addresses as `"AAA:VV"` codes
+/// - `save_cheats`: keeps the codes for the ROM's hash, returning `{path}`;
+///   `load_cheats` freezes the kept ones, returning `{cheats}`
+pub struct Server {
+    machine: Machine,
+    listener: Listener,
+    clients: Vec<Client>,
+    paused: bool,
+    search: Option<Search>,
+    /// Where `save_cheats` and `load_cheats` keep codes.
+    cheat_dir: Option<PathBuf>,
+}
+
+impl Server {
+    /// Listens on `address`, with `machine` paused.
+    pub fn bind(address: &Address, machine: Machine) -> io::Result<Server> {
+        Ok(Server {
+            machine,
+            listener: Listener::bind(address)?,
+            clients: Vec::new(),
+            paused: true,
+            search: None,
+            cheat_dir: cheat::dir(),
+        })
+    }
+
+    /// Keeps cheats in `dir` instead of next to the default config file.
+    pub fn set_cheat_dir(&mut self, dir: PathBuf) {
+        self.cheat_dir = Some(dir);
+    }
+
+    /// The address listened on, with the actual port if 0 was asked for.
+    pub fn address(&self) -> io::Result<Address> {
+        self.listener.address()
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Accepts new clients and answers every complete request, without
+    /// blocking.
+    pub fn poll(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok(stream) => self.clients.push(Client::new(stream)),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::ConnectionAborted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        for index in 0..self.clients.len() {
+            for line in self.clients[index].receive() {
+                if let Some(response) = self.handle(&line, index) {
+                    self.clients[index].send(&response);
+                }
+            }
+        }
+
+        for client in &mut self.clients {
+            client.flush();
+        }
+        self.clients.retain(|client| !client.closed);
+
+        Ok(())
+    }
+
+    /// Serves forever, running the machine in real time while it is not
+    /// paused.
+    pub fn run(&mut self) -> io::Result<()> {
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let mut next = Instant::now();
+
+        loop {
+            self.poll()?;
+
+            let now = Instant::now();
+            if self.paused || now - next > MAX_CATCH_UP {
+                next = now;
+            }
+            while !self.paused && next <= now {
+                // The fault was sent to the subscribers.
+                let _ = self.run_frame();
+                next += frame;
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Runs a frame and tells the subscribers. A fault pauses the
+    /// machine and is sent as a `fault` notification instead.
+    fn run_frame(&mut self) -> Result<(), RpcError> {
+        let (event, result) = match self.machine.run_frame() {
+            Ok(sound) => {
+                let params = json!({ "frame": self.machine.frame(), "sound": sound });
+                (json!({ "jsonrpc": "2.0", "method": "frame", "params": params }), Ok(()))
+            }
+            Err(fault) => {
+                self.paused = true;
+                let (message, pc) = (fault.to_string(), self.machine.cpu().pc());
+                let params = json!({ "frame": self.machine.frame(), "fault": message, "pc": pc });
+                let event = json!({ "jsonrpc": "2.0", "method": "fault", "params": params });
+                (event, Err(RpcError::new(FAILED, format!("{} at {:#05x}", message, pc))))
+            }
+        };
+        for client in self.clients.iter_mut().filter(|client| client.subscribed) {
+            client.send(&event);
+        }
+
+        result
+    }
+
+    /// The response to a request line, or `None` for a notification.
+    fn handle(&mut self, line: &str, client: usize) -> Option<Value> {
+        if line.is_empty() {
+            return None;
+        }
+
+        let (id, result) = match serde_json::from_str::<Value>(line) {
+            Err(err) => (Value::Null, Err(RpcError::new(PARSE_ERROR, err.to_string()))),
+            Ok(request) => {
+                let id = request.get("id").cloned();
+                let result = match request.get("method").and_then(Value::as_str) {
+                    Some(method) => {
+                        let params = request.get("params").cloned().unwrap_or(Value::Null);
+                        self.call(method, params, client)
+                    }
+                    None => Err(RpcError::new(INVALID_REQUEST, "expected an object with a method")),
+                };
+
+                // Calls without an id want no answer, not even on failure.
+                (id?, result)
+            }
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": err.code, "message": err.message },
+            }),
+        })
+    }
+
+    fn call(&mut self, method: &str, params: Value, client: usize) -> Result<Value, RpcError> {
+        match method {
+            "status" => {
+                parse::<NoParams>(params)?;
+                Ok(self.status())
+            }
+            "pause" | "resume" => {
+                parse::<NoParams>(params)?;
+                self.paused = method == "pause";
+                Ok(self.status())
+            }
+            "step" => {
+                let params: StepParams = parse(params)?;
+                for _ in 0..params.frames.min(MAX_STEP) {
+                    self.run_frame()?;
+                }
+                Ok(json!({ "frame": self.machine.frame() }))
+            }
+            "reset" => {
+                parse::<NoParams>(params)?;
+                self.machine.reset();
+                Ok(Value::Null)
+            }
+            "get_keys" => {
+                parse::<NoParams>(params)?;
+                let keys = self.machine.keys();
+                Ok(json!({ "keys": (0..16).filter(|key| keys & 1 << key != 0).collect::<Vec<u8>>() }))
+            }
+            "set_keys" => {
+                let params: KeysParams = parse(params)?;
+                if let Some(key) = params.keys.iter().find(|&&key| key > 0xF) {
+                    return Err(RpcError::new(INVALID_PARAMS, format!("no key {}; keys go from 0 to 15", key)));
+                }
+                self.machine.set_keys(params.keys.iter().fold(0, |mask, key| mask | 1 << key));
+                Ok(Value::Null)
+            }
+            "get_display" => {
+                parse::<NoParams>(params)?;
+                let display = self.machine.display();
+                let (width, height) = (display.width(), display.height());
+                let pixels: Vec<u8> = (0..height)
+                    .flat_map(|y| (0..width).map(move |x| display.pixel(x, y)))
+                    .collect();
+                Ok(json!({ "width": width, "height": height, "pixels": pixels }))
+            }
+            "get_registers" => {
+                parse::<NoParams>(params)?;
+                Ok(serde_json::to_value(self.machine.registers()).expect("registers serialize"))
+            }
+            "read_memory" => {
+                let params: MemoryParams = parse(params)?;
+                let memory = self.machine.memory();
+                match params.address.checked_add(params.length) {
+                    Some(end) if end <= memory.len() => {
+                        Ok(json!({ "address": params.address, "bytes": &memory[params.address..end] }))
+                    }
+                    _ => Err(RpcError::new(INVALID_PARAMS, format!("memory ends at {:#x}", memory.len()))),
+                }
+            }
+            "save_state" => {
+                parse::<NoParams>(params)?;
+                Ok(json!({ "state": to_hex(&self.machine.save_state()) }))
+            }
+            "load_state" => {
+                let params: StateParams = parse(params)?;
+                let state = from_hex(&params.state)
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "state is not a hex string"))?;
+                self.machine.load_state(&state)
+                    .map_err(|err| RpcError::new(FAILED, format!("cannot load state: {}", err)))?;
+                Ok(Value::Null)
+            }
+            "subscribe" | "unsubscribe" => {
+                parse::<NoParams>(params)?;
+                self.clients[client].subscribed = method == "subscribe";
+                Ok(Value::Null)
+            }
+            "search_start" => {
+                parse::<NoParams>(params)?;
+                let search = Search::new(self.machine.memory());
+                let count = search.candidates().len();
+                self.search = Some(search);
+                Ok(json!({ "count": count }))
+            }
+            "search" => {
+                let params: SearchParams = parse(params)?;
+                let search = self.search.as_mut()
+                    .ok_or_else(|| RpcError::new(FAILED, "no search started; call search_start first"))?;
+                let count = search.narrow(self.machine.memory(), params.compare);
+                Ok(json!({ "count": count, "addresses": search.candidates() }))
+            }
+            "freeze" => {
+                let params: FreezeParams = parse(params)?;
+                self.machine.freeze(Cheat { address: params.address, value: params.value })
+                    .map_err(|err| RpcError::new(INVALID_PARAMS, format!("cannot freeze: {}", err)))?;
+                Ok(Value::Null)
+            }
+            "unfreeze" => {
+                let params: AddressParams = parse(params)?;
+                Ok(json!(self.machine.unfreeze(params.address)))
+            }
+            "get_cheats" => {
+                parse::<NoParams>(params)?;
+                Ok(json!({ "cheats": self.machine.cheats() }))
+            }
+            "save_cheats" => {
+                parse::<NoParams>(params)?;
+                let path = cheat::save_to(&self.cheat_dir()?, &self.rom_hash()?, &self.machine.cheats())
+                    .map_err(|err| RpcError::new(FAILED, format!("cannot save cheats: {}", err)))?;
+                Ok(json!({ "path": path }))
+            }
+            "load_cheats" => {
+                parse::<NoParams>(params)?;
+                let cheats = cheat::load_from(&self.cheat_dir()?, &self.rom_hash()?)
+                    .map_err(|err| RpcError::new(FAILED, format!("cannot load cheats: {}", err)))?;
+                for &cheat in &cheats {
+                    self.machine.freeze(cheat)
+                        .map_err(|err| RpcError::new(FAILED, format!("cannot freeze {}: {}", cheat, err)))?;
+                }
+                Ok(json!({ "cheats": cheats }))
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("no method {}", method))),
+        }
+    }
+
+    fn cheat_dir(&self) -> Result<PathBuf, RpcError> {
+        self.cheat_dir.clone().ok_or_else(|| RpcError::new(FAILED, "no config directory to keep cheats in"))
+    }
+
+    /// What the machine's cheats are kept under.
+    fn rom_hash(&self) -> Result<String, RpcError> {
+        self.machine.rom()
+            .map(|rom| rom.hash().to_string())
+            .ok_or_else(|| RpcError::new(FAILED, "the machine was not loaded from a ROM"))
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "paused": self.paused,
+            "frame": self.machine.frame(),
+            "speed": self.machine.speed(),
+        })
+    }
+}
+
+/// Reads named parameters, where leaving them out is the same as `{}`.
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use cpu::Cpu;
+use snapshot::Snapshot;
+
+/// What happened during one frame, enough to run it again.
+#[derive(Clone, Debug, Default)]
+struct Frame {
+    instructions: u32,
+    /// Key state changes, as the instruction they came before and the new
+    /// key mask.
+    keys: Vec<(u32, u16)>,
+}
+
+impl Frame {
+    fn cost(&self) -> usize {
+        mem::size_of::<Frame>() + self.keys.len() * mem::size_of::<(u32, u16)>()
+    }
+}
+
+/// A snapshot and the frames run after it.
+struct Segment {
+    /// The encoded snapshot: in full for the newest segment, and as a
+    /// delta against the next newer one for the others.
+    state: Vec<u8>,
+    frames: Vec<Frame>,
+    /// Bytes counted against the budget, once the segment is closed.
+    cost: usize,
+}
+
+/// A ring buffer of machine history to run backwards through.
+///
+/// A snapshot is taken every `interval` frames. Only the newest one is kept
+/// whole; each older one is stored as a delta against its successor, which
+/// is small because a frame changes little of the memory and display. The
+/// frames in between are kept as instruction counts and key changes and
+/// re-executed from the previous snapshot, which is deterministic since
+/// the random number generator is part of the snapshot. The oldest
+/// snapshots are dropped once the history outgrows its budget.
+pub struct Rewind {
+    interval: usize,
+    budget: usize,
+    segments: VecDeque<Segment>,
+    current: Frame,
+    last_keys: u16,
+    used: usize,
+}
+
+impl Rewind {
+    /// Keeps a snapshot every `interval` frames in about `budget` bytes.
+    pub fn new(interval: u32, budget: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1) as usize,
+            budget,
+            segments: VecDeque::new(),
+            current: Frame::default(),
+            last_keys: 0,
+            used: 0,
+        }
+    }
+
+    /// Forgets all history, as after loading another program.
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.current = Frame::default();
+        self.used = 0;
+    }
+
+    /// Bytes of history held, not counting the newest snapshot.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Frames that can be rewound.
+    pub fn frames(&self) -> usize {
+        self.segments.iter().map(|segment| segment.frames.len()).sum()
+    }
+
+    /// Records the key state before `cpu` runs its next instruction.
+    pub fn record_instruction(&mut self, cpu: &Cpu) {
+        if self.segments.is_empty() {
+            self.start_segment(cpu);
+        }
+
+        let keys = cpu.keys();
+        if keys != self.last_keys {
+            self.current.keys.push((self.current.instructions, keys));
+            self.last_keys = keys;
+        }
+        self.current.instructions += 1;
+    }
+
+    /// Forgets the instruction recorded last, which faulted and so did not
+    /// run.
+    pub fn discard_instruction(&mut self) {
+        self.current.instructions -= 1;
+        if self.current.keys.last().is_some_and(|&(at, _)| at == self.current.instructions) {
+            self.current.keys.pop();
+        }
+    }
+
+    /// Closes the frame `cpu` just finished with a timer tick.
+    pub fn end_frame(&mut self, cpu: &Cpu) {
+        let frame = mem::take(&mut self.current);
+
+        let full = match self.segments.back_mut() {
+            Some(segment) => {
+                segment.frames.push(frame);
+                segment.frames.len() >= self.interval
+            }
+            None => return,
+        };
+
+        if full {
+            self.start_segment(cpu);
+        }
+    }
+
+    /// Takes `cpu` back by one frame. Returns false when there is no more
+    /// history, leaving `cpu` at the oldest state held.
+    pub fn step_back(&mut self, cpu: &mut Cpu) -> bool {
+        let live_keys = cpu.keys();
+        self.current = Frame::default();
+
+        if self.segments.back().is_some_and(|segment| segment.frames.is_empty()) && self.segments.len() > 1 {
+            let newest = self.segments.pop_back().unwrap();
+            let previous = self.segments.back_mut().unwrap();
+
+            previous.state = patch(&newest.state, &previous.state);
+            self.used -= previous.cost;
+            previous.cost = 0;
+        }
+
+        let stepped = match self.segments.back_mut() {
+            Some(segment) => segment.frames.pop().is_some(),
+            None => return false,
+        };
+
+        let segment = self.segments.back().unwrap();
+        replay(cpu, &segment.state, &segment.frames);
+
+        self.last_keys = cpu.keys();
+        cpu.set_keys(live_keys);
+
+        stepped
+    }
+
+    fn start_segment(&mut self, cpu: &Cpu) {
+        let state = cpu.snapshot().to_bytes();
+
+        if let Some(newest) = self.segments.back_mut() {
+            newest.state = diff(&state, &newest.state);
+            newest.cost = newest.state.len() + newest.frames.iter().map(Frame::cost).sum::<usize>();
+            self.used += newest.cost;
+        }
+
+        self.segments.push_back(Segment { state, frames: Vec::new(), cost: 0 });
+        self.last_keys = cpu.keys();
+
+        while self.used > self.budget && self.segments.len() > 1 {
+            let oldest = self.segments.pop_front().unwrap();
+            self.used -= oldest.cost;
+        }
+    }
+}
+
+/// Restores `state` into `cpu` and runs `frames` again.
+fn replay(cpu: &mut Cpu, state: &[u8], frames: &[Frame]) {
+    let snapshot = Snapshot::from_bytes(state).expect("rewind snapshots are complete");
+    cpu.restore(&snapshot).expect("rewind history is cleared when the machine changes");
+
+    for frame in frames {
+        let mut keys = frame.keys.iter().peekable();
+
+        for index in 0..frame.instructions {
+            if let Some(&&(_, mask)) = keys.peek().filter(|&&&(at, _)| at == index) {
+                cpu.set_keys(mask);
+                keys.next();
+            }
+            cpu.run_next_instruction().expect("recorded instructions ran without faults");
+        }
+        cpu.tick_timers();
+    }
+}
+
+/// Encodes `target` against `base` of the same length, as runs of
+/// unchanged bytes alternating with runs of replacement bytes.
+fn diff(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut at = 0;
+
+    while at < target.len() {
+        let start = at;
+        while at < target.len() && base[at] == target[at] {
+            at += 1;
+        }
+        let same = at - start;
+
+        let start = at;
+        while at < target.len() && base[at] != target[at] {
+            at += 1;
+        }
+
+        write_length(&mut delta, same);
+        write_length(&mut delta, at - start);
+        delta.extend_from_slice(&target[start..at]);
+    }
+
+    delta
+}
+
+/// Rebuilds the target of `diff` from its base.
+fn patch(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut target = base.to_vec();
+    let mut delta = delta;
+    let mut at = 0;
+
+    while !delta.is_empty() {
+        at += read_length(&mut delta);
+        let changed = read_length(&mut delta);
+
+        target[at..at + changed].copy_from_slice(&delta[..changed]);
+        delta = &delta[changed..];
+        at += changed;
+    }
+
+    target
+}
+
+/// LEB128, since most runs fit in a byte.
+fn write_length(out: &mut Vec<u8>, mut length: usize) {
+    while length >= 0x80 {
+        out.push(length as u8 | 0x80);
+        length >>= 7;
+    }
+    out.push(length as u8);
+}
+
+fn read_length(input: &mut &[u8]) -> usize {
+    let mut length = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = input[0];
+        *input = &input[1..];
+
+        length |= ((byte & 0x7f) as usize) << shift;
+        if byte < 0x80 {
+            return length;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use machine::Machine;
+    use rom::Rom;
+
+    #[test]
+    fn patches_diffs_back_to_their_target() {
+        let base: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut target = base.clone();
+        target[0] = 0xFF;
+        target[3..7].copy_from_slice(&[1, 2, 3, 4]);
+        // Runs longer than a byte of LEB128.
+        target[300..600].iter_mut().for_each(|byte| *byte = !*byte);
+        target[999] = 0;
+
+        let delta = diff(&base, &target);
+        assert!(delta.len() < 320);
+        assert_eq!(patch(&base, &delta), target);
+        // And the other way round.
+        assert_eq!(patch(&target, &diff(&target, &base)), base);
+    }
+
+    #[test]
+    fn encodes_nothing_for_equal_states() {
+        let state = [7; 300];
+
+        assert!(diff(&state, &state).len() <= 3);
+        assert_eq!(patch(&state, &diff(&state, &state)), state.to_vec());
+        assert_eq!(patch(&state, &[]), state.to_vec());
+    }
+
+    #[test]
+    fn round_trips_lengths() {
+        for &length in &[0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, 1 << 20] {
+            let mut out = Vec::new();
+            write_length(&mut out, length);
+
+            let mut input = &out[..];
+            assert_eq!(read_length(&mut input), length);
+            assert!(input.is_empty());
+        }
+    }
+
+    #[test]
+    fn steps_back_through_the_frames_run() {
+        let program = [
+            0x70, 0x01, // 0x200: ADD V0, 1
+            0xC1, 0xFF, // 0x202: RND V1, 0xFF
+            0xE2, 0x9E, // 0x204: SKP V2
+            0x12, 0x00, // 0x206: JP 0x200
+            0x73, 0x01, // 0x208: ADD V3, 1
+            0x12, 0x00, // 0x20A: JP 0x200
+        ];
+        let rom = Rom::from_bytes(&program, None).unwrap();
+        let mut machine = Machine::new(rom, &Config::default()).unwrap();
+        let cpu = machine.cpu_mut();
+        // Snapshots are taken every 3 frames, so most frames are replayed.
+        let mut rewind = Rewind::new(3, 1 << 20);
+
+        let mut states = Vec::new();
+        for frame in 0..10 {
+            states.push(cpu.snapshot().to_bytes());
+            cpu.set_keys(if frame % 4 == 1 { 1 } else { 0 });
+            for _ in 0..7 {
+                rewind.record_instruction(cpu);
+                cpu.run_next_instruction().unwrap();
+            }
+            cpu.tick_timers();
+            rewind.end_frame(cpu);
+        }
+        assert_eq!(rewind.frames(), 10);
+
+        cpu.set_keys(0x8000);
+        for state in states.iter().rev() {
+            assert!(rewind.step_back(cpu));
+            let mut now = cpu.snapshot();
+            // Keys pressed now are left as they are.
+            assert_eq!(cpu.keys(), 0x8000);
+            now.keys = Snapshot::from_bytes(state).unwrap().keys;
+            assert_eq!(&now.to_bytes(), state);
+        }
+        assert!(!rewind.step_back(cpu));
+    }
+}
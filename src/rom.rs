@@ -1,63 +1,150 @@
-use std::path::Path;
+use std::error;
+use std::fmt;
 use std::fs::File;
-use std::io::*;
+use std::io::{self, Read};
+use std::path::Path;
+
+use sha1_smol::Sha1;
+
+use archive;
+use database::{Database, RomInfo};
+use detect::{self, Detection};
+use memory;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The archive could not be unpacked.
+    Archive(String),
+    /// The archive holds several ROMs and none was chosen.
+    Ambiguous(Vec<String>),
+    /// The chosen entry is not in the archive.
+    MissingEntry(String),
+    /// The program or the fonts do not fit in memory, or they overlap.
+    Layout(memory::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Archive(ref message) => f.write_str(message),
+            Error::Ambiguous(ref names) => write!(f, "archive holds several ROMs: {}", names.join(", ")),
+            Error::MissingEntry(ref name) => write!(f, "archive has no entry named {}", name),
+            Error::Layout(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<memory::Error> for Error {
+    fn from(err: memory::Error) -> Error {
+        Error::Layout(err)
+    }
+}
 
+/// An immutable program image and what is known about it.
+#[derive(Clone)]
 pub struct Rom {
-    data: [u8; 4096],
+    program: Vec<u8>,
+    hash: String,
+    info: Option<RomInfo>,
+    detection: Option<Detection>,
 }
 
 impl Rom {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Rom> {
-        let mut file = try!(File::open(&path));
+    /// Loads a ROM file, or standard input when the path is `-`, knowing
+    /// only the bundled ROM database.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Rom, Error> {
+        Rom::open(path, None, &Database::bundled())
+    }
 
+    /// Loads a ROM file, picking `entry` if the file is an archive, and
+    /// looks it up in `database`.
+    pub fn open<P: AsRef<Path>>(path: P, entry: Option<&str>, database: &Database) -> Result<Rom, Error> {
         let mut buf = Vec::new();
 
-        try!(file.read_to_end(&mut buf));
-
-        let mut data = [0; 4096];
-
-        let sprites: [[u8; 5]; 16] = [
-            [0xF0, 0x90, 0x90, 0x90, 0xF0],
-            [0x20, 0x60, 0x20, 0x20, 0x70],
-            [0xF0, 0x10, 0xF0, 0x80, 0xF0],
-            [0xF0, 0x10, 0xF0, 0x10, 0xF0],
-            [0x90, 0x90, 0xF0, 0x10, 0x10],
-            [0xF0, 0x80, 0xF0, 0x10, 0xF0],
-            [0xF0, 0x80, 0xF0, 0x90, 0xF0],
-            [0xF0, 0x10, 0x20, 0x40, 0x40],
-            [0xF0, 0x90, 0xF0, 0x90, 0xF0],
-            [0xF0, 0x90, 0xF0, 0x10, 0xF0],
-            [0xF0, 0x90, 0xF0, 0x90, 0x90],
-            [0xE0, 0x90, 0xE0, 0x90, 0xE0],
-            [0xF0, 0x80, 0x80, 0x80, 0xF0],
-            [0xE0, 0x90, 0x90, 0x90, 0xE0],
-            [0xF0, 0x80, 0xF0, 0x80, 0xF0],
-            [0xF0, 0x80, 0xF0, 0x80, 0x80],
-        ];
-
-        let mut i = 0;
-        for sprite in &sprites {
-            for pos in sprite {
-                data[i] = *pos;
-                i += 1;
-            }
+        if path.as_ref() == Path::new("-") {
+            io::stdin().read_to_end(&mut buf)?;
+        } else {
+            File::open(&path)?.read_to_end(&mut buf)?;
         }
 
-        let mut j = 0x200;
+        Rom::load(&buf, entry, database)
+    }
+
+    /// Loads a ROM image already in memory, which may also be an archive,
+    /// knowing only the bundled ROM database.
+    pub fn from_bytes(bytes: &[u8], entry: Option<&str>) -> Result<Rom, Error> {
+        Rom::load(bytes, entry, &Database::bundled())
+    }
+
+    /// Loads a ROM image already in memory and looks it up in `database`.
+    pub fn load(bytes: &[u8], entry: Option<&str>, database: &Database) -> Result<Rom, Error> {
+        let extracted = archive::extract(bytes, entry)?;
+        let program = extracted.as_ref().map_or(bytes, Vec::as_slice);
+
+        let hash = Sha1::from(program).digest().to_string();
 
-        for i in buf.iter() {
-            data[j] = *i;
-            j += 1;
+        let mut info = database.lookup(&hash).cloned();
+        let mut detection = None;
+
+        // The configured load address is not known yet, so detection
+        // assumes the usual one; `Config::load` redoes it for another.
+        if info.is_none() {
+            let guess = detect::detect(program, memory::DEFAULT_LOAD_ADDRESS);
+            info = Some(guess.info());
+            detection = Some(guess);
         }
 
-        Ok(Rom { data })
+        Ok(Rom {
+            program: program.to_vec(),
+            hash,
+            info,
+            detection,
+        })
+    }
+
+    /// The program bytes, exactly as loaded.
+    pub fn program(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// Lowercase hex SHA-1 of the program, after unpacking any archive. Used
+    /// to look up per-ROM settings.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// The ROM database entry for this ROM, or the profile guessed by
+    /// platform detection when the ROM is unknown.
+    pub fn info(&self) -> Option<&RomInfo> {
+        self.info.as_ref()
     }
 
-    pub fn load(&self, address: u16) -> u8 {
-        self.data[address as usize]
+    /// How the platform was guessed, if the ROM is not in the database.
+    pub fn detection(&self) -> Option<&Detection> {
+        self.detection.as_ref()
     }
 
-    pub fn store(&mut self, address: u16, value: u8) {
-        self.data[address as usize] = value;
+    /// This ROM with the platform guessed again for a program loaded at
+    /// `load_address`, if it was guessed for another address.
+    pub fn with_load_address(&self, load_address: u16) -> Rom {
+        let mut rom = self.clone();
+
+        if self.detection.as_ref().is_some_and(|detection| detection.load_address != load_address) {
+            let guess = detect::detect(&self.program, load_address);
+            rom.info = Some(guess.info());
+            rom.detection = Some(guess);
+        }
+
+        rom
     }
 }
@@ -0,0 +1,124 @@
+use chip8::config::{Rgb, Scaling, VideoConfig};
+use chip8::display::Framebuffer;
+use chip8::filter::Filter;
+use chip8::video::{self, Compositor, Image};
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+
+/// Bytes per texel of the streaming texture.
+const TEXEL: usize = 3;
+
+/// The display as a streaming texture, one texel per pixel of the filtered
+/// image, which the renderer scales to the window with nearest-neighbor
+/// sampling and letterboxes.
+///
+/// The letterbox is sized from the display resolution, so switching to
+/// SUPER-CHIP's 128x64 keeps the picture the same size on screen.
+pub struct Screen<'a> {
+    creator: &'a TextureCreator<WindowContext>,
+    texture: Texture<'a>,
+    compositor: Compositor,
+    filter: Filter,
+    scaling: Scaling,
+    /// The display in color, and after the filter.
+    composed: Image,
+    image: Image,
+}
+
+impl<'a> Screen<'a> {
+    /// Call after setting `SDL_RENDER_SCALE_QUALITY`, which textures pick
+    /// up when they are created.
+    pub fn new(creator: &'a TextureCreator<WindowContext>, colors: [Rgb; 4], video: &VideoConfig) -> Screen<'a> {
+        let texture = creator.create_texture_streaming(PixelFormatEnum::RGB24, 1, 1).unwrap();
+
+        Screen {
+            creator,
+            texture,
+            compositor: Compositor::new(colors, video),
+            filter: video.filter,
+            scaling: video.scaling,
+            composed: Image::default(),
+            image: Image::default(),
+        }
+    }
+
+    pub fn set_colors(&mut self, colors: [Rgb; 4]) {
+        self.compositor.set_colors(colors);
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Switches filter, re-filtering the current picture.
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+        self.refresh();
+    }
+
+    /// Whether the picture changes on its own, as pixels fade out.
+    pub fn animating(&self) -> bool {
+        self.compositor.animating()
+    }
+
+    /// The picture as shown, for screenshots and recordings.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Composes and uploads `display`.
+    pub fn update(&mut self, display: &Framebuffer) {
+        self.compositor.compose(display, &mut self.composed);
+        self.refresh();
+    }
+
+    /// Filters and uploads the composed picture, making a new texture when
+    /// its size changed.
+    fn refresh(&mut self) {
+        if self.composed.pixels.is_empty() {
+            return;
+        }
+
+        let size = (self.image.width, self.image.height);
+        self.image = self.filter.apply(&self.composed);
+
+        let image = &self.image;
+        if (image.width, image.height) != size {
+            self.texture = self.creator
+                .create_texture_streaming(PixelFormatEnum::RGB24, image.width as u32, image.height as u32)
+                .unwrap();
+        }
+
+        let _ = self.texture.with_lock(None, |texels: &mut [u8], pitch: usize| {
+            for (y, row) in image.pixels.chunks(image.width).enumerate() {
+                let line = &mut texels[y * pitch..y * pitch + image.width * TEXEL];
+
+                for (texel, &Rgb(r, g, b)) in line.chunks_mut(TEXEL).zip(row) {
+                    texel.copy_from_slice(&[r, g, b]);
+                }
+            }
+        });
+    }
+
+    /// Where the display goes on the canvas, once there is one.
+    pub fn viewport(&self, canvas: &WindowCanvas) -> Option<Rect> {
+        let (width, height) = canvas.output_size().ok()?;
+        let (columns, rows) = (self.composed.width as u32, self.composed.height as u32);
+
+        let (x, y, w, h) = video::letterbox(width, height, columns, rows, self.scaling)?;
+        Some(Rect::new(x as i32, y as i32, w, h))
+    }
+
+    /// Draws the display over a black letterbox.
+    pub fn draw(&self, canvas: &mut WindowCanvas) {
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+
+        if let Some(viewport) = self.viewport(canvas) {
+            let _ = canvas.copy(&self.texture, None, viewport);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::error;
+use std::fmt;
+
+use display::Framebuffer;
+
+/// Why a snapshot cannot be restored into a machine.
+#[derive(Debug)]
+pub enum Error {
+    /// Taken from a machine with a different amount of RAM.
+    MemorySize { expected: usize, found: usize },
+    /// Not a complete encoded snapshot.
+    Truncated,
+    /// Holds a stack pointer past the end of the stack.
+    StackPointer(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MemorySize { expected, found } => {
+                write!(f, "snapshot has {} bytes of memory, the machine has {}", found, expected)
+            }
+            Error::Truncated => write!(f, "snapshot is truncated"),
+            Error::StackPointer(sp) => write!(f, "snapshot has stack pointer {}, past the 16-level stack", sp),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// The complete state of a running machine: registers, timers, keys,
+/// display and RAM.
+///
+/// Everything else a `Cpu` holds comes from its configuration, so
+/// restoring a snapshot into a machine built from the same ROM and config
+/// resumes exactly where it was taken.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub pc: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub i: u32,
+    pub v: [u8; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Pressed keys, one bit per key.
+    pub keys: u16,
+    /// XO-CHIP's audio pattern and its pitch.
+    pub pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+    pub rng: u64,
+    pub display: Framebuffer,
+    pub memory: Vec<u8>,
+}
+
+/// Bytes taken by everything but the RAM in `Snapshot::to_bytes`.
+const HEADER: usize = 2 + 1 + 32 + 4 + 16 + 1 + 1 + 2 + 1 + 16 + 1 + 8 + Framebuffer::ENCODED_SIZE;
+
+impl Snapshot {
+    /// A flat little-endian encoding, of the same length for every
+    /// snapshot of a given machine.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER + self.memory.len());
+
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.push(self.sp);
+        for entry in &self.stack {
+            bytes.extend_from_slice(&entry.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.i.to_le_bytes());
+        bytes.extend_from_slice(&self.v);
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        bytes.extend_from_slice(&self.keys.to_le_bytes());
+        bytes.push(self.pattern.is_some() as u8);
+        bytes.extend_from_slice(&self.pattern.unwrap_or([0; 16]));
+        bytes.push(self.pitch);
+        bytes.extend_from_slice(&self.rng.to_le_bytes());
+        bytes.extend_from_slice(&self.display.to_bytes());
+        bytes.extend_from_slice(&self.memory);
+
+        bytes
+    }
+
+    /// Decodes `to_bytes` output.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, Error> {
+        if bytes.len() < HEADER {
+            return Err(Error::Truncated);
+        }
+
+        let mut reader = Reader { bytes, at: 0 };
+
+        let pc = u16::from_le_bytes(reader.array());
+        let sp = reader.array::<1>()[0];
+        let mut stack = [0; 16];
+        if sp as usize >= stack.len() {
+            return Err(Error::StackPointer(sp));
+        }
+        for entry in &mut stack {
+            *entry = u16::from_le_bytes(reader.array());
+        }
+        let i = u32::from_le_bytes(reader.array());
+        let v = reader.array();
+        let delay_timer = reader.array::<1>()[0];
+        let sound_timer = reader.array::<1>()[0];
+        let keys = u16::from_le_bytes(reader.array());
+        let has_pattern = reader.array::<1>()[0] != 0;
+        let pattern = Some(reader.array()).filter(|_| has_pattern);
+        let pitch = reader.array::<1>()[0];
+        let rng = u64::from_le_bytes(reader.array());
+        let display = Framebuffer::from_bytes(&bytes[reader.at..]).ok_or(Error::Truncated)?;
+        let memory = bytes[reader.at + Framebuffer::ENCODED_SIZE..].to_vec();
+
+        Ok(Snapshot { pc, sp, stack, i, v, delay_timer, sound_timer, keys, pattern, pitch, rng, display, memory })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn array<const N: usize>(&mut self) -> [u8; N] {
+        let mut array = [0; N];
+        array.copy_from_slice(&self.bytes[self.at..self.at + N]);
+        self.at += N;
+        array
+    }
+}
@@ -0,0 +1,177 @@
+use config::{Persistence, Rgb, Scaling, VideoConfig};
+use display::Framebuffer;
+
+/// An RGB picture of the display, one entry per pixel, row by row.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![Rgb(0, 0, 0); width * height],
+        }
+    }
+
+    /// The pixels as packed RGB bytes.
+    pub fn to_rgb24(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|&Rgb(r, g, b)| [r, g, b]).collect()
+    }
+}
+
+/// Turns the framebuffer into colors, applying the palette and the
+/// persistence effect.
+///
+/// Persistence only changes what is shown: the framebuffer the program
+/// sees is left alone.
+pub struct Compositor {
+    colors: [Rgb; 4],
+    persistence: Persistence,
+    decay: u8,
+    /// The frame composed before, for `Persistence::Merge`.
+    previous: Option<Framebuffer>,
+    /// Whether the last merged frame differs from the framebuffer, so one
+    /// more is needed to drop the older frame.
+    merged: bool,
+    /// Color and remaining brightness of each fading pixel, for
+    /// `Persistence::Phosphor`.
+    glow: Vec<(u8, u8)>,
+}
+
+impl Compositor {
+    pub fn new(colors: [Rgb; 4], video: &VideoConfig) -> Compositor {
+        Compositor {
+            colors,
+            persistence: video.persistence,
+            decay: video.decay,
+            previous: None,
+            merged: false,
+            glow: Vec::new(),
+        }
+    }
+
+    pub fn set_colors(&mut self, colors: [Rgb; 4]) {
+        self.colors = colors;
+    }
+
+    /// Whether pixels are still fading, so the image changes even when the
+    /// framebuffer does not.
+    pub fn animating(&self) -> bool {
+        self.merged || self.glow.iter().any(|&(_, level)| level > 0 && level < self.decay)
+    }
+
+    /// Draws `display` into `image`, resizing it to the display.
+    pub fn compose(&mut self, display: &Framebuffer, image: &mut Image) {
+        let (width, height) = (display.width(), display.height());
+
+        if image.width != width || image.height != height {
+            *image = Image::new(width, height);
+        }
+        if self.glow.len() != width * height {
+            self.glow = vec![(0, 0); width * height];
+        }
+
+        let previous = self.previous.as_ref().filter(|previous| previous.width() == width);
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = y * width + x;
+                let value = display.pixel(x, y);
+
+                image.pixels[index] = match self.persistence {
+                    Persistence::None => self.colors[value as usize],
+                    Persistence::Merge => {
+                        let before = previous.map_or(0, |previous| previous.pixel(x, y));
+                        self.colors[(value | before) as usize]
+                    }
+                    Persistence::Phosphor => {
+                        let glow = &mut self.glow[index];
+                        if value != 0 {
+                            *glow = (value, self.decay);
+                            self.colors[value as usize]
+                        } else if glow.1 > 1 {
+                            glow.1 -= 1;
+                            blend(self.colors[0], self.colors[glow.0 as usize], glow.1, self.decay)
+                        } else {
+                            *glow = (0, 0);
+                            self.colors[0]
+                        }
+                    }
+                };
+            }
+        }
+
+        if self.persistence == Persistence::Merge {
+            self.merged = previous.is_some_and(|previous| previous != display);
+            self.previous = Some(display.clone());
+        }
+    }
+}
+
+/// Where a picture of `columns` by `rows` goes in a window of `width` by
+/// `height`, as x, y, width and height, centered with black borders.
+/// `None` while there is no picture.
+pub fn letterbox(width: u32, height: u32, columns: u32, rows: u32, scaling: Scaling) -> Option<(u32, u32, u32, u32)> {
+    if columns == 0 || rows == 0 {
+        return None;
+    }
+
+    let (w, h) = match scaling {
+        Scaling::Integer if width >= columns && height >= rows => {
+            let scale = (width / columns).min(height / rows);
+            (columns * scale, rows * scale)
+        }
+        // Also used when the window is smaller than one pixel per pixel.
+        _ if width * rows <= height * columns => (width, rows * width / columns),
+        _ => (columns * height / rows, height),
+    };
+
+    Some(((width - w) / 2, (height - h) / 2, w.max(1), h.max(1)))
+}
+
+/// `level / levels` of the way from `from` to `to`.
+fn blend(from: Rgb, to: Rgb, level: u8, levels: u8) -> Rgb {
+    let mix = |a: u8, b: u8| {
+        (a as u32 * (levels - level) as u32 + b as u32 * level as u32) / levels as u32
+    };
+
+    Rgb(mix(from.0, to.0) as u8, mix(from.1, to.1) as u8, mix(from.2, to.2) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_by_whole_multiples_and_centers() {
+        // 64x32 fits 12 times in 800x600, leaving 32 and 108 pixels.
+        assert_eq!(letterbox(800, 600, 64, 32, Scaling::Integer), Some((16, 108, 768, 384)));
+        assert_eq!(letterbox(640, 320, 64, 32, Scaling::Integer), Some((0, 0, 640, 320)));
+        // SUPER-CHIP's resolution keeps the same size on screen.
+        assert_eq!(letterbox(800, 600, 128, 64, Scaling::Integer), Some((16, 108, 768, 384)));
+    }
+
+    #[test]
+    fn fits_keeping_the_aspect_ratio() {
+        // Limited by the width, with borders above and below.
+        assert_eq!(letterbox(800, 600, 64, 32, Scaling::Fit), Some((0, 100, 800, 400)));
+        // Limited by the height, with borders at the sides.
+        assert_eq!(letterbox(1000, 300, 64, 32, Scaling::Fit), Some((200, 0, 600, 300)));
+    }
+
+    #[test]
+    fn fits_windows_too_small_for_whole_multiples() {
+        assert_eq!(letterbox(40, 40, 64, 32, Scaling::Integer), Some((0, 10, 40, 20)));
+        assert_eq!(letterbox(1, 1, 64, 32, Scaling::Integer), Some((0, 0, 1, 1)));
+    }
+
+    #[test]
+    fn places_nothing_without_a_picture() {
+        assert_eq!(letterbox(800, 600, 0, 0, Scaling::Fit), None);
+    }
+}
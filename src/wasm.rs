@@ -0,0 +1,227 @@
+use std::sync::{Mutex, MutexGuard};
+
+use config::{Config, Sources};
+use machine::Machine;
+use rom::Rom;
+
+/// Status codes returned by the exports.
+pub const OK: i32 = 0;
+pub const NO_ROM: i32 = 1;
+pub const INVALID_ROM: i32 = 2;
+/// The program did something no platform defines, such as an unknown
+/// instruction. It stays stopped until a reset or another ROM.
+pub const FAULT: i32 = 3;
+pub const INVALID_CONFIG: i32 = 4;
+
+/// The machine of this WebAssembly instance, and the buffers shared with
+/// JavaScript through linear memory.
+///
+/// The exports take and return only numbers, so they need no generated
+/// glue: byte buffers are passed as a pointer and a length into the
+/// instance's memory, valid until the next call. `web/chip8.js` wraps them
+/// in a class.
+struct Instance {
+    machine: Option<Machine>,
+    /// Where JavaScript writes the ROM for `load_rom`.
+    rom: Vec<u8>,
+    /// Where JavaScript writes the config for `load_rom_with_config`.
+    config: Vec<u8>,
+    /// The display, one color index per pixel.
+    framebuffer: Vec<u8>,
+    sound: bool,
+    error: String,
+}
+
+impl Instance {
+    fn fail(&mut self, status: i32, message: String) -> i32 {
+        self.error = message;
+        status
+    }
+}
+
+static INSTANCE: Mutex<Instance> = Mutex::new(Instance {
+    machine: None,
+    rom: Vec::new(),
+    config: Vec::new(),
+    framebuffer: Vec::new(),
+    sound: false,
+    error: String::new(),
+});
+
+fn instance() -> MutexGuard<'static, Instance> {
+    INSTANCE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A buffer of `size` bytes to copy a ROM into before `load_rom`.
+#[no_mangle]
+pub extern "C" fn rom_buffer(size: usize) -> *mut u8 {
+    let mut instance = instance();
+    instance.rom.resize(size, 0);
+
+    instance.rom.as_mut_ptr()
+}
+
+/// A buffer of `size` bytes to copy a config into before
+/// `load_rom_with_config`.
+#[no_mangle]
+pub extern "C" fn config_buffer(size: usize) -> *mut u8 {
+    let mut instance = instance();
+    instance.config.resize(size, 0);
+
+    instance.config.as_mut_ptr()
+}
+
+/// Loads the first `size` bytes of the ROM buffer, configured from the
+/// bundled ROM database or detection on top of the built-in defaults.
+/// The random number generator starts from seed 0.
+#[no_mangle]
+pub extern "C" fn load_rom(size: usize) -> i32 {
+    load(size, None)
+}
+
+/// Loads a ROM like `load_rom`, then applies the first `config_size`
+/// bytes of the config buffer, a config file as UTF-8 TOML, on top.
+#[no_mangle]
+pub extern "C" fn load_rom_with_config(size: usize, config_size: usize) -> i32 {
+    load(size, Some(config_size))
+}
+
+fn load(size: usize, config_size: Option<usize>) -> i32 {
+    let mut instance = instance();
+    let size = size.min(instance.rom.len());
+
+    let text = match config_size {
+        Some(config_size) => {
+            let config_size = config_size.min(instance.config.len());
+            match String::from_utf8(instance.config[..config_size].to_vec()) {
+                Ok(text) => Some(text),
+                Err(err) => return instance.fail(INVALID_CONFIG, format!("config is not UTF-8: {}", err)),
+            }
+        }
+        None => None,
+    };
+    let rom = match Rom::from_bytes(&instance.rom[..size], None) {
+        Ok(rom) => rom,
+        Err(err) => return instance.fail(INVALID_ROM, err.to_string()),
+    };
+    let sources = Sources { text, ignore_default_file: true, ..Sources::default() };
+    let config = match Config::load(&sources, Some(&rom)) {
+        Ok(config) => config,
+        Err(err) => return instance.fail(INVALID_CONFIG, err.to_string()),
+    };
+
+    match Machine::new(rom, &config) {
+        Ok(mut machine) => {
+            machine.cpu_mut().seed(0);
+            instance.machine = Some(machine);
+            instance.sound = false;
+            OK
+        }
+        Err(err) => instance.fail(INVALID_ROM, err.to_string()),
+    }
+}
+
+/// Restarts the program from its pristine image.
+#[no_mangle]
+pub extern "C" fn reset() -> i32 {
+    let mut instance = instance();
+
+    match instance.machine {
+        Some(ref mut machine) => {
+            machine.reset();
+            OK
+        }
+        None => instance.fail(NO_ROM, "no ROM is loaded".into()),
+    }
+}
+
+/// Reseeds the random number generator behind CXNN.
+#[no_mangle]
+pub extern "C" fn seed(seed: u32) -> i32 {
+    let mut instance = instance();
+
+    match instance.machine {
+        Some(ref mut machine) => {
+            machine.cpu_mut().seed(seed as u64);
+            OK
+        }
+        None => instance.fail(NO_ROM, "no ROM is loaded".into()),
+    }
+}
+
+/// Sets the pressed keys, bit N for key N.
+#[no_mangle]
+pub extern "C" fn set_keys(keys: u32) {
+    if let Some(ref mut machine) = instance().machine {
+        machine.set_keys(keys as u16);
+    }
+}
+
+/// Runs one frame, a sixtieth of a second of emulated time.
+#[no_mangle]
+pub extern "C" fn run_frame() -> i32 {
+    let mut instance = instance();
+
+    let result = match instance.machine {
+        Some(ref mut machine) => machine.run_frame().map_err(|fault| format!("{} at {:#05x}", fault, machine.cpu().pc())),
+        None => return instance.fail(NO_ROM, "no ROM is loaded".into()),
+    };
+    match result {
+        Ok(sound) => {
+            instance.sound = sound;
+            OK
+        }
+        Err(message) => {
+            instance.sound = false;
+            instance.fail(FAULT, message)
+        }
+    }
+}
+
+/// 1 when the buzzer sounded during the last frame, otherwise 0.
+#[no_mangle]
+pub extern "C" fn buzzer() -> i32 {
+    instance().sound as i32
+}
+
+/// The display, `framebuffer_width() * framebuffer_height()` color
+/// indices row by row, or null before a ROM is loaded.
+#[no_mangle]
+pub extern "C" fn framebuffer() -> *const u8 {
+    let mut instance = instance();
+    let instance = &mut *instance;
+
+    let display = match instance.machine {
+        Some(ref machine) => machine.display(),
+        None => return std::ptr::null(),
+    };
+    instance.framebuffer.clear();
+    for y in 0..display.height() {
+        instance.framebuffer.extend((0..display.width()).map(|x| display.pixel(x, y)));
+    }
+
+    instance.framebuffer.as_ptr()
+}
+
+/// 64, or 128 in SUPER-CHIP's high resolution.
+#[no_mangle]
+pub extern "C" fn framebuffer_width() -> u32 {
+    instance().machine.as_ref().map_or(0, |machine| machine.display().width() as u32)
+}
+
+/// 32, or 64 in SUPER-CHIP's high resolution.
+#[no_mangle]
+pub extern "C" fn framebuffer_height() -> u32 {
+    instance().machine.as_ref().map_or(0, |machine| machine.display().height() as u32)
+}
+
+/// The last error in UTF-8, `error_length()` bytes long.
+#[no_mangle]
+pub extern "C" fn error_message() -> *const u8 {
+    instance().error.as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn error_length() -> usize {
+    instance().error.len()
+}
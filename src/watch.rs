@@ -0,0 +1,51 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls a file for changes to its modification time or size.
+pub struct Watcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Watcher {
+        let path = path.into();
+        let stamp = stamp(&path);
+
+        Watcher {
+            path,
+            stamp,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Whether the file changed since the last call that returned true.
+    ///
+    /// Checks at most every `POLL_INTERVAL`, and ignores the file while it
+    /// is missing or empty, as it is when an assembler is rewriting it.
+    pub fn changed(&mut self, now: Instant) -> bool {
+        if now - self.last_poll < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = now;
+
+        match stamp(&self.path) {
+            Some(stamp) if stamp.1 > 0 && Some(stamp) != self.stamp => {
+                self.stamp = Some(stamp);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
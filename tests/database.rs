@@ -0,0 +1,79 @@
+extern crate chip8;
+
+use chip8::config::{Config, Rgb, Sources};
+use chip8::database::Database;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+use chip8::rom::Rom;
+
+/// The IBM logo demo, whose hash the bundled database knows.
+const IBM_LOGO: &str = "\
+00e0a22a600c6108d01f7009a239d01fa2487008d01f7004a257d01f7008a266d01f7008a275d01f1228\
+ff00ff003c003c003c003c00ff00ffff00ff0038003f003f003800ff00ff8000e000e00080008000e000\
+e00080f800fc003e003f003b003900f800f8030007000f00bf00fb00f300e30043e000e0008000800080\
+008000e000e0";
+
+fn ibm_logo() -> Vec<u8> {
+    (0..IBM_LOGO.len()).step_by(2).map(|at| u8::from_str_radix(&IBM_LOGO[at..at + 2], 16).unwrap()).collect()
+}
+
+#[test]
+fn knows_bundled_roms_by_hash() {
+    let rom = Rom::from_bytes(&ibm_logo(), None).unwrap();
+    assert_eq!(rom.hash(), "1ba58656810b67fd131eb9af3e3987863bf26c90");
+
+    let database = Database::bundled();
+    let info = database.lookup(rom.hash()).unwrap();
+    assert_eq!(info.title.as_deref(), Some("IBM Logo"));
+    assert_eq!(info.platform, Some(Platform::Vip));
+
+    // Hashes match whatever their case.
+    assert!(database.lookup(&rom.hash().to_uppercase()).is_some());
+}
+
+#[test]
+fn attaches_the_entry_to_loaded_roms() {
+    let rom = Rom::from_bytes(&ibm_logo(), None).unwrap();
+
+    assert_eq!(rom.info().and_then(|info| info.title.as_deref()), Some("IBM Logo"));
+    assert!(rom.detection().is_none());
+}
+
+#[test]
+fn loads_roms_despite_a_broken_local_database() {
+    let path = std::env::temp_dir().join(format!("chip8-roms-test-{}.toml", std::process::id()));
+    std::fs::write(&path, "not [toml").unwrap();
+
+    assert!(Database::open_at(&path).is_err());
+    let rom = Rom::from_bytes(&ibm_logo(), None).unwrap();
+    assert_eq!(rom.info().and_then(|info| info.title.as_deref()), Some("IBM Logo"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn applies_the_entry_to_roms_opened_from_files() {
+    let path = std::env::temp_dir().join(format!("chip8-ibm-logo-test-{}.ch8", std::process::id()));
+    std::fs::write(&path, ibm_logo()).unwrap();
+    let rom = Rom::new(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let sources = Sources { ignore_default_file: true, ..Sources::default() };
+    let config = Config::load(&sources, Some(&rom)).unwrap();
+
+    assert_eq!(rom.info().and_then(|info| info.author.as_deref()), Some("Unknown"));
+    assert_eq!(config.cpu.platform, Some(Platform::Vip));
+    assert_eq!(config.cpu.speed, 500);
+    assert_eq!(config.quirks, Quirks {
+        shift: false,
+        load_store: true,
+        jump: false,
+        vf_reset: true,
+        clipping: true,
+        large_sprites: false,
+    });
+    assert_eq!(config.keymap.keys[0], "Space");
+    assert_eq!(config.keymap.keys[1], "1");
+    assert_eq!(config.palette.colors()[0], Rgb(0xff, 0xff, 0xff));
+    assert_eq!(config.palette.colors()[1], Rgb(0x1f, 0x70, 0xc1));
+}
@@ -0,0 +1,72 @@
+extern crate chip8;
+
+use chip8::config::Config;
+use chip8::debugger::Debugger;
+use chip8::machine::Machine;
+use chip8::rom::Rom;
+
+/// Counts up the byte at 0x300 forever.
+const COUNTER: [u8; 10] = [
+    0xA3, 0x00, // LD I, 0x300
+    0xF0, 0x65, // LD V0, [I]
+    0x70, 0x01, // ADD V0, 1
+    0xF0, 0x55, // LD [I], V0
+    0x12, 0x00, // JP 0x200
+];
+
+fn debugger(program: &[u8]) -> Debugger {
+    let rom = Rom::from_bytes(program, None).unwrap();
+    Debugger::new(Machine::new(rom, &Config::default()).unwrap())
+}
+
+#[test]
+fn finds_and_freezes_a_counter() {
+    // Where saved cheats go, away from the user's own.
+    let dir = std::env::temp_dir().join(format!("chip8-debugger-cheats-{}", std::process::id()));
+    let mut debugger = debugger(&COUNTER);
+    debugger.set_cheat_dir(dir.clone());
+
+    assert_eq!(debugger.execute("search changed").unwrap_err(), "no search started; run `search start` first");
+    assert_eq!(debugger.execute("search start").unwrap(), "4096 candidates");
+    debugger.execute("step").unwrap();
+    debugger.execute("search changed").unwrap();
+    debugger.execute("step").unwrap();
+    assert_eq!(debugger.execute("search increased").unwrap(), "1 candidates: 300");
+
+    // The frozen value shows at once, and the program's stores leave it.
+    debugger.execute("freeze 300:42").unwrap();
+    assert_eq!(debugger.execute("mem 300 2").unwrap(), "300: 42 00");
+    assert_eq!(debugger.execute("step 2").unwrap(), "frame 4");
+    assert_eq!(debugger.execute("mem 300 1").unwrap(), "300: 42");
+    assert!(debugger.execute("regs").unwrap().contains("V0-V7 43 00"));
+    assert_eq!(debugger.execute("cheats").unwrap(), "300:42");
+
+    assert!(debugger.execute("save-cheats").unwrap().starts_with(&format!("saved to {}", dir.display())));
+    debugger.execute("unfreeze 300").unwrap();
+    assert_eq!(debugger.execute("unfreeze 300").unwrap_err(), "300 is not frozen");
+    assert_eq!(debugger.execute("cheats").unwrap(), "no cheats");
+
+    // A new machine for the same ROM picks the saved codes up.
+    let mut debugger = self::debugger(&COUNTER);
+    debugger.set_cheat_dir(dir.clone());
+    assert_eq!(debugger.execute("load-cheats").unwrap(), "300:42");
+    debugger.execute("step").unwrap();
+    assert_eq!(debugger.execute("mem 300 1").unwrap(), "300: 42");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn runs_commands_from_a_reader_until_quit() {
+    let mut debugger = debugger(&COUNTER);
+    let mut output = Vec::new();
+
+    debugger.run("step 3\nmem 1000\nfly\n\nquit\nstep\n".as_bytes(), &mut output).unwrap();
+
+    assert_eq!(String::from_utf8(output).unwrap(), "\
+(chip8) frame 3
+(chip8) error: memory ends at 1000
+(chip8) error: unknown command or arguments for `fly`; `help` lists them
+(chip8) (chip8) ");
+    assert_eq!(debugger.machine().frame(), 3);
+}
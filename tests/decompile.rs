@@ -0,0 +1,151 @@
+extern crate chip8;
+
+use chip8::decompile::{Listing, Sprite};
+use chip8::instruction::Instruction;
+use chip8::quirks::Quirks;
+
+const PROGRAM: [u8; 41] = [
+    0x00, 0xE0, // 0x200: CLS
+    0x60, 0x00, // 0x202: LD V0, 0
+    0xA2, 0x20, // 0x204: LD I, 0x220
+    0xD0, 0x15, // 0x206: DRW V0, V1, 5
+    0x70, 0x01, // 0x208: ADD V0, 1
+    0x30, 0x10, // 0x20A: SE V0, 16
+    0x12, 0x06, // 0x20C: JP 0x206
+    0x41, 0x00, // 0x20E: SNE V1, 0
+    0x22, 0x14, // 0x210: CALL 0x214
+    0x12, 0x12, // 0x212: JP 0x212
+    0xA2, 0x26, // 0x214: LD I, 0x226
+    0xF0, 0x33, // 0x216: LD B, V0
+    0xF2, 0x65, // 0x218: LD V2, [I]
+    0xF0, 0x29, // 0x21A: LD F, V0
+    0xD1, 0x25, // 0x21C: DRW V1, V2, 5
+    0x00, 0xEE, // 0x21E: RET
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0x220: the digit 0
+    0x00,
+    0x00, 0x00, 0x00, // 0x226: where the digits go
+];
+
+fn pseudo_code(program: &[u8]) -> String {
+    Listing::new(program, 0x200, 0x200, Quirks::default()).to_pseudo_code()
+}
+
+#[test]
+fn decodes_instructions() {
+    assert_eq!(Instruction::decode(0xD015), Some(Instruction::Draw(0, 1, 5)));
+    assert_eq!(Instruction::decode(0xF000).map(Instruction::width), Some(4));
+    assert_eq!(Instruction::decode(0x5121), None);
+    assert_eq!(Instruction::decode(0x800F), None);
+    assert_eq!(Instruction::decode(0xA220).unwrap().to_string(), "LD I, 0x220");
+    assert_eq!(Instruction::decode(0xF265).unwrap().to_string(), "LD V2, [I]");
+}
+
+#[test]
+fn turns_skips_and_jumps_into_loops_and_ifs() {
+    let code = pseudo_code(&PROGRAM);
+
+    assert!(code.contains("\
+void main() {
+    clear();
+    v0 = 0;
+    i = sprite_220;
+    loop {
+        vf = draw(v0, v1, 5);
+        v0 += 1;
+        if (v0 == 16) {
+            break;
+        }
+    }
+    if (v1 == 0) {
+        score_214();
+    }
+    loop {
+    }
+}
+"));
+}
+
+#[test]
+fn recovers_if_else() {
+    let program = [
+        0x30, 0x00, // 0x200: SE V0, 0
+        0x12, 0x08, // 0x202: JP 0x208
+        0x61, 0x01, // 0x204: LD V1, 1
+        0x12, 0x0A, // 0x206: JP 0x20A
+        0x61, 0x02, // 0x208: LD V1, 2
+        0x12, 0x0A, // 0x20A: JP 0x20A
+    ];
+
+    assert!(pseudo_code(&program).contains("\
+    if (v0 == 0) {
+        v1 = 1;
+    } else {
+        v1 = 2;
+    }
+"));
+}
+
+#[test]
+fn falls_back_to_goto() {
+    // The second skip is itself skipped over, which no `if` nests.
+    let program = [0x30, 0x01, 0x31, 0x02, 0x00, 0xE0, 0x12, 0x06];
+
+    assert!(pseudo_code(&program).contains("\
+    if (v0 == 1) {
+        goto L_204;
+    }
+    if (v1 == 2) {
+        goto L_206;
+    }
+L_204:
+    clear();
+"));
+}
+
+#[test]
+fn finds_sprites_and_score_routines() {
+    let listing = Listing::new(&PROGRAM, 0x200, 0x200, Quirks::default());
+
+    assert_eq!(listing.sprites, vec![Sprite { address: 0x220, width: 8, height: 5 }]);
+    assert_eq!(listing.label(0x214), Some("score_214"));
+    assert_eq!(listing.label(0x226), Some("data_226"));
+
+    let code = listing.to_pseudo_code();
+    assert!(code.contains("// Converts to decimal with FX33, as score routines do.\nvoid score_214() {"));
+    assert!(code.contains("//     ####....\n//     #..#....\n"));
+    assert!(code.contains("byte sprite_220[6] = { 0xF0, 0x90, 0x90, 0x90, 0xF0, 0x00 };"));
+}
+
+#[test]
+fn writes_assembler_with_labels() {
+    let program = [
+        0x22, 0x0C, // 0x200: CALL 0x20C
+        0x30, 0x05, // 0x202: SE V0, 5
+        0x12, 0x08, // 0x204: JP 0x208
+        0x00, 0xE0, // 0x206: CLS
+        0xB2, 0x14, // 0x208: JP V0, 0x214
+        0x00, 0xE0, // 0x20A: CLS, which nothing reaches
+        0xA2, 0x10, // 0x20C: LD I, 0x210
+        0x00, 0xEE, // 0x20E: RET
+        0xFF, 0xFF, // 0x210: data
+    ];
+    let listing = Listing::new(&program, 0x200, 0x200, Quirks::default());
+
+    assert_eq!(listing.to_assembly(), "\
+; 18 bytes loaded at 0x200, entered at 0x200.
+
+main:
+    CALL sub_20C
+    SE V0, 0x05
+    JP L_208
+    CLS
+L_208:
+    JP V0, 0x214            ; computed, not followed
+    DB 0x00, 0xE0           ; unreachable: CLS
+sub_20C:
+    LD I, data_210
+    RET
+data_210:
+    DB 0xFF, 0xFF
+");
+}
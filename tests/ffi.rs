@@ -0,0 +1,141 @@
+#![cfg(target_os = "linux")]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+/// Builds the library and compiles the C example against it, once for
+/// all tests, and returns a command running the example.
+fn example() -> Command {
+    static EXAMPLE: OnceLock<PathBuf> = OnceLock::new();
+
+    let example = EXAMPLE.get_or_init(|| {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        // A target directory of its own, apart from the one running the
+        // tests, which `cargo test` does not put the cdylib in.
+        let exe = env::current_exe().unwrap();
+        let target = exe.parent().unwrap().parent().unwrap().join("ffi");
+
+        let status = Command::new(option_env!("CARGO").unwrap_or("cargo"))
+            .args(["build", "--lib"])
+            .arg("--manifest-path").arg(root.join("Cargo.toml"))
+            .arg("--target-dir").arg(&target)
+            .status()
+            .expect("cannot run cargo");
+        assert!(status.success(), "the library does not build");
+
+        let lib = target.join("debug");
+        let output = lib.join("chip8-c-example");
+
+        let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".into()))
+            .args(["-std=c99", "-Wall", "-Werror"])
+            .arg("-I").arg(root.join("include"))
+            .arg(root.join("examples/c/run.c"))
+            .arg("-L").arg(&lib)
+            // An RPATH rather than a RUNPATH, which LD_LIBRARY_PATH from
+            // cargo would override with a stale library in its target.
+            .arg(format!("-Wl,-rpath,{}", lib.display()))
+            .arg("-Wl,--disable-new-dtags")
+            .args(["-lchip8", "-o"])
+            .arg(&output)
+            .status()
+            .expect("cannot run the C compiler");
+        assert!(status.success(), "the C example does not compile");
+
+        output
+    });
+
+    let mut command = Command::new(example);
+    command.env("LD_LIBRARY_PATH", example.parent().unwrap());
+    command
+}
+
+fn run(name: &str, rom: &[u8], frames: u32) -> Output {
+    run_with_config(name, rom, frames, None)
+}
+
+fn run_with_config(name: &str, rom: &[u8], frames: u32, config: Option<&str>) -> Output {
+    let path = env::temp_dir().join(format!("chip8-ffi-{}-{}.ch8", name, std::process::id()));
+    fs::write(&path, rom).unwrap();
+    let config_path = path.with_extension("toml");
+
+    let mut command = example();
+    command.arg(&path).arg(frames.to_string());
+    if let Some(config) = config {
+        fs::write(&config_path, config).unwrap();
+        command.arg(&config_path);
+    }
+    let output = command.output().unwrap();
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(&config_path);
+
+    output
+}
+
+#[test]
+fn runs_a_rom_from_c() {
+    let rom = [
+        0xA2, 0x06, // LD I, 0x206
+        0xD0, 0x05, // DRW V0, V0, 5
+        0x12, 0x04, // JP 0x204
+        0xF0, 0x90, 0x90, 0x90, 0xF0,
+    ];
+
+    let output = run("draw", &rom, 10);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 33);
+    assert!(lines[0].starts_with("####...."));
+    assert!(lines[1].starts_with("#..#...."));
+    assert!(lines[5].chars().all(|pixel| pixel == '.'));
+    assert_eq!(lines[1].len(), 64);
+    assert_eq!(lines[32], "10 frames, 0 with the buzzer on");
+}
+
+#[test]
+fn reports_faults_as_status_codes() {
+    let output = run("underflow", &[0x00, 0xEE], 1);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "run: stack underflow: return with an empty stack at 0x200\n");
+
+    let output = run("unknown", &[0xFF, 0xFF], 1);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "run: unknown instruction: unknown instruction FFFF at 0x200\n");
+}
+
+#[test]
+fn ignores_the_users_config_file() {
+    let dir = env::temp_dir().join(format!("chip8-ffi-home-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), "[cpu]\nload_address = 0x300\n").unwrap();
+
+    let path = dir.join("underflow.ch8");
+    fs::write(&path, [0x00, 0xEE]).unwrap();
+    let output = example()
+        .arg(&path)
+        .arg("1")
+        .env("CHIP8_CONFIG", dir.join("config.toml"))
+        .env("XDG_CONFIG_HOME", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "run: stack underflow: return with an empty stack at 0x200\n");
+}
+
+#[test]
+fn applies_config_text() {
+    let output = run_with_config("config", &[0x00, 0xEE], 1, Some("[cpu]\nload_address = 0x300\n"));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "run: stack underflow: return with an empty stack at 0x300\n");
+
+    let output = run_with_config("bad-config", &[0x00, 0xEE], 1, Some("[cpu]\nspeed = 0\n"));
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "run: invalid configuration: invalid config: cpu.speed must be greater than zero\n",
+    );
+}
@@ -0,0 +1,98 @@
+extern crate chip8;
+extern crate serde_json;
+
+use chip8::flow::{Edge, EdgeKind, Graph, Region, RegionKind, Terminator, Unresolved};
+
+use serde_json::Value;
+
+const PROGRAM: [u8; 18] = [
+    0x22, 0x0C, // 0x200: CALL 0x20C
+    0x30, 0x05, // 0x202: SE V0, 5
+    0x12, 0x08, // 0x204: JP 0x208
+    0x00, 0xE0, // 0x206: CLS
+    0xB2, 0x14, // 0x208: JP V0, 0x214
+    0x00, 0xE0, // 0x20A: CLS, which nothing reaches
+    0xA2, 0x10, // 0x20C: LD I, 0x210
+    0x00, 0xEE, // 0x20E: RET
+    0xFF, 0xFF, // 0x210: data
+];
+
+fn starts(graph: &Graph) -> Vec<u16> {
+    graph.blocks.iter().map(|block| block.start).collect()
+}
+
+#[test]
+fn splits_blocks_at_branches_and_their_targets() {
+    let graph = Graph::build(&PROGRAM, 0x200, 0x200);
+
+    assert_eq!(starts(&graph), vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20C]);
+
+    let block = |start: u16| graph.blocks.iter().find(|block| block.start == start).unwrap();
+    assert_eq!(block(0x200).successors, vec![
+        Edge { target: 0x20C, kind: EdgeKind::Call },
+        Edge { target: 0x202, kind: EdgeKind::Next },
+    ]);
+    assert_eq!(block(0x202).successors, vec![
+        Edge { target: 0x204, kind: EdgeKind::Next },
+        Edge { target: 0x206, kind: EdgeKind::Skip },
+    ]);
+    assert_eq!(block(0x206).terminator, Terminator::Next);
+    assert_eq!(block(0x208).terminator, Terminator::Computed);
+    assert!(block(0x208).successors.is_empty());
+    assert_eq!((block(0x20C).opcodes.clone(), block(0x20C).terminator), (vec![0xA210, 0x00EE], Terminator::Return));
+
+    assert_eq!(graph.unresolved, vec![Unresolved { address: 0x208, base: 0x214 }]);
+}
+
+#[test]
+fn names_subroutines_and_finds_their_calls() {
+    let graph = Graph::build(&PROGRAM, 0x200, 0x200);
+
+    let names: Vec<&str> = graph.subroutines.iter().map(|subroutine| subroutine.name.as_str()).collect();
+    assert_eq!(names, vec!["main", "sub_20C"]);
+    assert_eq!(graph.subroutines[0].blocks, vec![0x200, 0x202, 0x204, 0x206, 0x208]);
+    assert_eq!(graph.subroutines[0].calls, vec![0x20C]);
+    assert_eq!(graph.subroutines[1].blocks, vec![0x20C]);
+    assert!(graph.subroutines[1].calls.is_empty());
+}
+
+#[test]
+fn tells_data_from_unreachable_code() {
+    let graph = Graph::build(&PROGRAM, 0x200, 0x200);
+
+    assert_eq!(graph.regions, vec![
+        Region { start: 0x20A, end: 0x20C, kind: RegionKind::Unreachable },
+        Region { start: 0x210, end: 0x212, kind: RegionKind::Data },
+    ]);
+}
+
+#[test]
+fn starts_at_the_entry_point() {
+    // Loaded at 0x600 and entered past a table, as on the ETI 660.
+    let program = [0x01, 0x02, 0x16, 0x02];
+    let graph = Graph::build(&program, 0x600, 0x602);
+
+    assert_eq!(starts(&graph), vec![0x602]);
+    assert_eq!(graph.blocks[0].successors, vec![Edge { target: 0x602, kind: EdgeKind::Jump }]);
+    assert_eq!(graph.regions, vec![Region { start: 0x600, end: 0x602, kind: RegionKind::Data }]);
+}
+
+#[test]
+fn exports_dot_and_json() {
+    let graph = Graph::build(&PROGRAM, 0x200, 0x200);
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph chip8 {"));
+    assert!(dot.contains("subgraph \"cluster_sub_20C\""));
+    assert!(dot.contains("\"0x200\" -> \"0x20c\" [label=\"sub_20C\", style=dashed];"));
+    assert!(dot.contains("\"0x202\" -> \"0x206\" [label=\"skip\"];"));
+    assert!(dot.contains("unresolved BNNN"));
+    assert!(dot.contains("0x210-0x211\\ndata"));
+
+    let json: Value = serde_json::from_str(&graph.to_json()).unwrap();
+    assert_eq!(json["entry"], 0x200);
+    assert_eq!(json["blocks"][0]["successors"][0], serde_json::json!({ "target": 0x20C, "kind": "call" }));
+    assert_eq!(json["subroutines"][1]["name"], "sub_20C");
+    assert_eq!(json["regions"][0]["kind"], "unreachable");
+    assert_eq!(json["unresolved"][0]["base"], 0x214);
+}
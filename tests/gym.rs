@@ -0,0 +1,119 @@
+extern crate chip8;
+
+use chip8::config::Config;
+use chip8::gym::{self, Environment, Observation, Options, Script};
+use chip8::rom::Rom;
+
+/// Counts presses of key 5 into a BCD score at 0x300, one per press.
+const PROGRAM: [u8; 22] = [
+    0x60, 0x00, // LD V0, 0
+    0xA3, 0x00, // LD I, 0x300
+    0xF0, 0x33, // LD B, V0
+    0x61, 0x05, // LD V1, 5
+    0xE1, 0x9E, // SKP V1
+    0x12, 0x08, // JP 0x208
+    0x70, 0x01, // ADD V0, 1
+    0xF0, 0x33, // LD B, V0
+    0xE1, 0xA1, // SKNP V1
+    0x12, 0x10, // JP 0x210
+    0x12, 0x08, // JP 0x208
+];
+
+const SCRIPT: &str = r#"
+actions = [[], [5]]
+
+[[reward]]
+address = 0x300
+format = "bcd"
+length = 3
+
+[[done]]
+address = 0x300
+format = "bcd"
+length = 3
+above = 2
+"#;
+
+fn environment(options: Options) -> Environment {
+    let rom = Rom::from_bytes(&PROGRAM, None).unwrap();
+    Environment::new(rom, &Config::default(), SCRIPT.parse().unwrap(), options).unwrap()
+}
+
+fn single_frames() -> Options {
+    Options { frame_skip: 1, ..Options::default() }
+}
+
+#[test]
+fn rewards_score_changes_until_done() {
+    let mut environment = environment(single_frames());
+    assert_eq!(environment.action_count(), 2);
+
+    let rewards: Vec<(f64, bool)> = [0, 1, 1, 0, 1, 0, 1]
+        .iter()
+        .map(|&action| {
+            let (_, reward, done) = environment.step(action);
+            (reward, done)
+        })
+        .collect();
+
+    assert_eq!(rewards, vec![
+        (0.0, false),
+        (1.0, false),
+        (0.0, false),
+        (0.0, false),
+        (1.0, false),
+        (0.0, false),
+        (1.0, true),
+    ]);
+
+    environment.reset(1);
+    assert_eq!(environment.machine().memory()[0x300..0x303], [0, 0, 0]);
+}
+
+#[test]
+fn holds_actions_for_skipped_frames() {
+    let mut environment = environment(Options::default());
+
+    let (_, reward, _) = environment.step(1);
+    assert_eq!(reward, 1.0);
+    assert_eq!(environment.machine().frame(), 4);
+}
+
+#[test]
+fn sticky_actions_can_keep_the_last_keys() {
+    let mut environment = environment(Options { sticky: 1.0, ..single_frames() });
+
+    for _ in 0..10 {
+        assert_eq!(environment.step(1).1, 0.0);
+    }
+}
+
+#[test]
+fn observes_pixels_or_ram() {
+    let mut pixels = environment(single_frames());
+    assert_eq!(pixels.reset(0).len(), 128 * 64);
+
+    let mut ram = environment(Options { observation: Observation::Ram, ..single_frames() });
+    ram.step(1);
+    let (observation, _, _) = ram.step(1);
+    assert_eq!(observation.len(), ram.observation_size());
+    assert_eq!(observation[0x300..0x303], [0, 0, 1]);
+}
+
+#[test]
+fn steps_environments_in_parallel() {
+    let mut environments: Vec<Environment> = (0..8).map(|_| environment(single_frames())).collect();
+    let actions: Vec<usize> = (0..8).map(|index| index % 2).collect();
+
+    let rewards: Vec<f64> = gym::step_all(&mut environments, &actions).iter().map(|step| step.1).collect();
+
+    assert_eq!(rewards, vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0]);
+}
+
+#[test]
+fn rejects_scripts_for_other_roms() {
+    let rom = Rom::from_bytes(&PROGRAM, None).unwrap();
+    let script: Script = "sha1 = \"0000000000000000000000000000000000000000\"".parse().unwrap();
+
+    assert!(Environment::new(rom, &Config::default(), script, Options::default()).is_err());
+}
@@ -0,0 +1,208 @@
+#![cfg(target_os = "linux")]
+
+// A minimal libretro frontend, driving the core through the shared library
+// as RetroArch would.
+
+extern crate chip8;
+
+use std::env;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use chip8::libretro::{GameInfo, SystemInfo, Variable};
+
+#[link(name = "dl")]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+const RTLD_NOW: c_int = 2;
+
+/// Draws a "0" at the top left, then sounds the buzzer once key 5 is
+/// pressed.
+const PROGRAM: [u8; 23] = [
+    0xA2, 0x12, // LD I, 0x212
+    0xD0, 0x05, // DRW V0, V0, 5
+    0x62, 0x05, // LD V2, 5
+    0xE2, 0x9E, // SKP V2
+    0x12, 0x06, // JP 0x206
+    0x63, 0x0A, // LD V3, 10
+    0xF3, 0x18, // LD ST, V3
+    0x12, 0x0E, // JP 0x20E
+    0x00, 0x00,
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+];
+
+static PIXEL_FORMAT: Mutex<Option<c_uint>> = Mutex::new(None);
+static VARIABLES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// Width, height, pitch and pixels of the last frame.
+type Frame = (c_uint, c_uint, usize, Vec<u32>);
+
+static FRAME: Mutex<Option<Frame>> = Mutex::new(None);
+static AUDIO: Mutex<Vec<i16>> = Mutex::new(Vec::new());
+static PRESS_A: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" fn environment(command: c_uint, data: *mut c_void) -> bool {
+    match command {
+        // SET_MESSAGE, SET_INPUT_DESCRIPTORS
+        6 | 11 => true,
+        // SET_PIXEL_FORMAT
+        10 => {
+            *PIXEL_FORMAT.lock().unwrap() = Some(*(data as *const c_uint));
+            true
+        }
+        // GET_VARIABLE
+        15 => {
+            let variable = &mut *(data as *mut Variable);
+            if CStr::from_ptr(variable.key).to_str() == Ok("chip8_speed") {
+                variable.value = b"1200\0".as_ptr() as *const c_char;
+                return true;
+            }
+            false
+        }
+        // SET_VARIABLES
+        16 => {
+            let mut variable = data as *const Variable;
+            while !(*variable).key.is_null() {
+                VARIABLES.lock().unwrap().push(CStr::from_ptr((*variable).key).to_string_lossy().into_owned());
+                variable = variable.add(1);
+            }
+            true
+        }
+        // GET_VARIABLE_UPDATE
+        17 => {
+            *(data as *mut bool) = false;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+    let pixels = std::slice::from_raw_parts(data as *const u32, pitch / 4 * height as usize).to_vec();
+    *FRAME.lock().unwrap() = Some((width, height, pitch, pixels));
+}
+
+unsafe extern "C" fn audio_sample(_: i16, _: i16) {}
+
+unsafe extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+    AUDIO.lock().unwrap().extend_from_slice(std::slice::from_raw_parts(data, frames * 2));
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(port: c_uint, device: c_uint, _: c_uint, id: c_uint) -> i16 {
+    // RetroPad A on the first port.
+    (port == 0 && device == 1 && id == 8 && PRESS_A.load(Ordering::SeqCst)) as i16
+}
+
+struct Core(*mut c_void);
+
+impl Core {
+    /// Builds the shared library and opens it.
+    fn open() -> Core {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        // A target directory of its own, apart from the one running the
+        // tests, which `cargo test` does not put the cdylib in.
+        let exe = env::current_exe().unwrap();
+        let target = exe.parent().unwrap().parent().unwrap().join("libretro");
+
+        let status = Command::new(option_env!("CARGO").unwrap_or("cargo"))
+            .args(["build", "--lib"])
+            .arg("--manifest-path").arg(root.join("Cargo.toml"))
+            .arg("--target-dir").arg(&target)
+            .status()
+            .expect("cannot run cargo");
+        assert!(status.success(), "the core does not build");
+
+        let path = target.join("debug/libchip8.so");
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+
+        let handle = unsafe { dlopen(path.as_ptr(), RTLD_NOW) };
+        assert!(!handle.is_null(), "cannot open {:?}", path);
+        Core(handle)
+    }
+
+    /// The entry point called `name`, as a `T` function pointer.
+    unsafe fn get<T: Copy>(&self, name: &str) -> T {
+        let name = CString::new(name).unwrap();
+        let symbol = dlsym(self.0, name.as_ptr());
+        assert!(!symbol.is_null(), "core lacks {:?}", name);
+        mem::transmute_copy(&symbol)
+    }
+}
+
+#[test]
+fn runs_a_game_like_a_frontend() {
+    let core = Core::open();
+
+    unsafe {
+        assert_eq!(core.get::<unsafe extern "C" fn() -> c_uint>("retro_api_version")(), 1);
+
+        core.get::<unsafe extern "C" fn(unsafe extern "C" fn(c_uint, *mut c_void) -> bool)>("retro_set_environment")(environment);
+        core.get::<unsafe extern "C" fn(unsafe extern "C" fn(*const c_void, c_uint, c_uint, usize))>("retro_set_video_refresh")(video_refresh);
+        core.get::<unsafe extern "C" fn(unsafe extern "C" fn(i16, i16))>("retro_set_audio_sample")(audio_sample);
+        core.get::<unsafe extern "C" fn(unsafe extern "C" fn(*const i16, usize) -> usize)>("retro_set_audio_sample_batch")(audio_sample_batch);
+        core.get::<unsafe extern "C" fn(unsafe extern "C" fn())>("retro_set_input_poll")(input_poll);
+        core.get::<unsafe extern "C" fn(unsafe extern "C" fn(c_uint, c_uint, c_uint, c_uint) -> i16)>("retro_set_input_state")(input_state);
+        core.get::<unsafe extern "C" fn()>("retro_init")();
+
+        let variables = VARIABLES.lock().unwrap().clone();
+        for key in &["chip8_speed", "chip8_palette", "chip8_keypad", "chip8_quirk_clipping"] {
+            assert!(variables.iter().any(|variable| variable == key), "{} is not a core option", key);
+        }
+
+        let mut info: SystemInfo = mem::zeroed();
+        core.get::<unsafe extern "C" fn(*mut SystemInfo)>("retro_get_system_info")(&mut info);
+        assert_eq!(CStr::from_ptr(info.library_name).to_str(), Ok("chip8"));
+        assert!(!info.need_fullpath);
+
+        let game = GameInfo { path: ptr::null(), data: PROGRAM.as_ptr() as *const c_void, size: PROGRAM.len(), meta: ptr::null() };
+        assert!(core.get::<unsafe extern "C" fn(*const GameInfo) -> bool>("retro_load_game")(&game));
+        assert_eq!(*PIXEL_FORMAT.lock().unwrap(), Some(1));
+
+        let run = core.get::<unsafe extern "C" fn()>("retro_run");
+        run();
+
+        let (width, height, pitch, pixels) = FRAME.lock().unwrap().clone().unwrap();
+        assert_eq!((width, height, pitch), (64, 32, 256));
+        assert_ne!(pixels[0], pixels[5], "the sprite's top left pixel is lit and its right neighbor is not");
+        {
+            let mut audio = AUDIO.lock().unwrap();
+            assert_eq!(audio.len(), 2 * 44_100 / 60);
+            assert!(audio.iter().all(|&sample| sample == 0));
+            audio.clear();
+        }
+
+        PRESS_A.store(true, Ordering::SeqCst);
+        run();
+        run();
+        assert!(AUDIO.lock().unwrap().iter().any(|&sample| sample != 0), "pressing A beeps");
+
+        let memory_size = core.get::<unsafe extern "C" fn(c_uint) -> usize>("retro_get_memory_size")(2);
+        let memory = core.get::<unsafe extern "C" fn(c_uint) -> *mut c_void>("retro_get_memory_data")(2) as *mut u8;
+        assert_eq!(memory_size, 4096);
+        assert!(!memory.is_null());
+
+        let size = core.get::<unsafe extern "C" fn() -> usize>("retro_serialize_size")();
+        let mut state = vec![0u8; size];
+        assert!(core.get::<unsafe extern "C" fn(*mut c_void, usize) -> bool>("retro_serialize")(state.as_mut_ptr() as *mut c_void, size));
+
+        *memory.add(0x300) = 0xAB;
+        assert!(core.get::<unsafe extern "C" fn(*const c_void, usize) -> bool>("retro_unserialize")(state.as_ptr() as *const c_void, size));
+        assert_eq!(*memory.add(0x300), 0);
+
+        core.get::<unsafe extern "C" fn()>("retro_reset")();
+        run();
+        core.get::<unsafe extern "C" fn()>("retro_unload_game")();
+        core.get::<unsafe extern "C" fn()>("retro_deinit")();
+    }
+}
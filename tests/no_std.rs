@@ -0,0 +1,89 @@
+extern crate chip8;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use chip8::bus::{Bus, Policies};
+use chip8::cpu::Cpu;
+use chip8::font;
+use chip8::memory::{self, Layout, Memory};
+use chip8::quirks::Quirks;
+use chip8::random::Rng;
+
+/// Builds the library with only the core, where it is `#![no_std]` and
+/// links neither `std` nor `alloc`.
+#[test]
+fn core_builds_without_std() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A target directory of its own, apart from the one running the tests.
+    let exe = env::current_exe().unwrap();
+    let target = exe.parent().unwrap().parent().unwrap().join("no-std");
+
+    // Only the rlib: the cdylib would need a panic handler.
+    let status = Command::new(option_env!("CARGO").unwrap_or("cargo"))
+        .args(["rustc", "--lib", "--no-default-features", "--crate-type", "rlib"])
+        .arg("--manifest-path").arg(root.join("Cargo.toml"))
+        .arg("--target-dir").arg(&target)
+        .status()
+        .expect("cannot run cargo");
+    assert!(status.success(), "the core does not build without std");
+}
+
+/// Always the same byte, to tell CXNN's numbers apart.
+struct Fixed(u8);
+
+impl Rng for Fixed {
+    fn next_u8(&mut self) -> u8 {
+        self.0
+    }
+}
+
+fn layout() -> Layout<&'static [u8]> {
+    Layout {
+        memory_size: 4096,
+        load_address: 0x200,
+        font: font::small("vip").unwrap(),
+        font_address: 0x000,
+        large_font: font::large("schip").unwrap(),
+        large_font_address: 0x050,
+    }
+}
+
+#[test]
+fn runs_from_an_array_with_an_injected_rng() {
+    let program = [
+        0xC0, 0xFF, // RND V0, 0xFF
+        0xF0, 0x15, // LD DT, V0
+        0xF1, 0x29, // LD F, V1
+        0x12, 0x06, // JP 0x206
+    ];
+
+    let memory = Memory::with_storage([0; 4096], &layout(), &program).unwrap();
+    assert_eq!(memory.load(0x000), font::small("vip").unwrap()[0]);
+    assert_eq!(memory.load(0x203), 0x15);
+
+    let bus = Bus::with_memory(memory, &layout(), Policies::default());
+    let mut cpu = Cpu::with_rng(bus, Quirks::default(), 0x200, Fixed(0x2A));
+
+    for _ in 0..4 {
+        cpu.run_next_instruction().unwrap();
+    }
+    assert_eq!(cpu.v()[0], 0x2A);
+    assert_eq!(cpu.pc(), 0x206);
+
+    // The timers only move when ticked.
+    assert_eq!(cpu.delay_timer(), 0x2A);
+    cpu.tick_timers();
+    cpu.tick_timers();
+    assert_eq!(cpu.delay_timer(), 0x28);
+}
+
+#[test]
+fn rejects_storage_of_the_wrong_size() {
+    let err = Memory::with_storage([0; 2048], &layout(), &[0x12, 0x00]).err().unwrap();
+    assert_eq!(err, memory::Error::StorageSize { expected: 4096, found: 2048 });
+
+    let err = Memory::with_storage([0; 4096], &layout(), &[0; 4000]).err().unwrap();
+    assert_eq!(err.to_string(), "ROM is 4000 bytes but only 3584 fit between 0x200 and the end of the 4 KiB memory");
+}
@@ -0,0 +1,278 @@
+extern crate chip8;
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+
+use chip8::config::Config;
+use chip8::machine::Machine;
+use chip8::remote::{Address, Server};
+use chip8::rom::Rom;
+
+use serde_json::{json, Value};
+
+/// Clears the screen, draws a "0" at (1, 2) and loops.
+const PROGRAM: [u8; 17] = [
+    0x00, 0xE0, // CLS
+    0xA2, 0x0C, // LD I, 0x20C
+    0x60, 0x01, // LD V0, 1
+    0x61, 0x02, // LD V1, 2
+    0xD0, 0x15, // DRW V0, V1, 5
+    0x12, 0x0A, // JP 0x20A
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+];
+
+/// Counts up the byte at 0x300 forever.
+const COUNTER: [u8; 10] = [
+    0xA3, 0x00, // LD I, 0x300
+    0xF0, 0x65, // LD V0, [I]
+    0x70, 0x01, // ADD V0, 1
+    0xF0, 0x55, // LD [I], V0
+    0x12, 0x00, // JP 0x200
+];
+
+fn serve(address: &str) -> Address {
+    serve_program(address, &PROGRAM)
+}
+
+fn serve_program(address: &str, program: &[u8]) -> Address {
+    spawn(bind(address, program))
+}
+
+fn bind(address: &str, program: &[u8]) -> Server {
+    let rom = Rom::from_bytes(program, None).unwrap();
+    let machine = Machine::new(rom, &Config::default()).unwrap();
+    Server::bind(&address.parse().unwrap(), machine).unwrap()
+}
+
+fn spawn(mut server: Server) -> Address {
+    let address = server.address().unwrap();
+
+    thread::spawn(move || server.run());
+
+    address
+}
+
+struct Client<S: Write> {
+    writer: S,
+    reader: BufReader<S>,
+    next_id: u64,
+    /// Notifications received while waiting for responses.
+    notifications: Vec<Value>,
+}
+
+impl<S: Write> Client<S> where BufReader<S>: BufRead {
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params });
+        writeln!(self.writer, "{}", request).unwrap();
+
+        self.receive()
+    }
+
+    fn receive(&mut self) -> Value {
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            let message: Value = serde_json::from_str(&line).unwrap();
+
+            if message.get("id").is_some() {
+                return message;
+            }
+            self.notifications.push(message);
+        }
+    }
+
+    fn result(&mut self, method: &str, params: Value) -> Value {
+        let response = self.call(method, params);
+        assert!(response.get("error").is_none(), "{} failed: {}", method, response);
+        response["result"].clone()
+    }
+}
+
+fn connect_tcp(address: &Address) -> Client<TcpStream> {
+    let stream = match *address {
+        Address::Tcp(address) => TcpStream::connect(address).unwrap(),
+        #[allow(unreachable_patterns)]
+        _ => unreachable!(),
+    };
+
+    Client {
+        writer: stream.try_clone().unwrap(),
+        reader: BufReader::new(stream),
+        next_id: 0,
+        notifications: Vec::new(),
+    }
+}
+
+#[test]
+fn steps_frames_and_reads_the_machine() {
+    let mut client = connect_tcp(&serve("127.0.0.1:0"));
+
+    let status = client.result("status", Value::Null);
+    assert_eq!(status["paused"], true);
+    assert_eq!(status["frame"], 0);
+
+    assert_eq!(client.result("step", json!({ "frames": 3 }))["frame"], 3);
+
+    let registers = client.result("get_registers", Value::Null);
+    assert_eq!(registers["pc"], 0x20A);
+    assert_eq!(registers["i"], 0x20C);
+    assert_eq!(registers["v"][1], 2);
+
+    let display = client.result("get_display", Value::Null);
+    assert_eq!(display["width"], 64);
+    let pixel = |x: usize, y: usize| display["pixels"][y * 64 + x].as_u64().unwrap();
+    assert_eq!((pixel(1, 2), pixel(4, 2), pixel(1, 3), pixel(2, 3), pixel(0, 2)), (1, 1, 1, 0, 0));
+
+    let memory = client.result("read_memory", json!({ "address": 0x20C, "length": 2 }));
+    assert_eq!(memory["bytes"], json!([0xF0, 0x90]));
+}
+
+#[test]
+fn saves_and_loads_states() {
+    let mut client = connect_tcp(&serve("127.0.0.1:0"));
+
+    client.result("set_keys", json!({ "keys": [1, 15] }));
+    client.result("step", json!({ "frames": 2 }));
+    let state = client.result("save_state", Value::Null)["state"].clone();
+
+    client.result("reset", Value::Null);
+    assert_eq!(client.result("get_registers", Value::Null)["pc"], 0x200);
+    assert_eq!(client.result("get_keys", Value::Null)["keys"], json!([]));
+
+    client.result("load_state", json!({ "state": state }));
+    assert_eq!(client.result("get_registers", Value::Null)["pc"], 0x20A);
+    assert_eq!(client.result("get_keys", Value::Null)["keys"], json!([1, 15]));
+}
+
+#[test]
+fn notifies_subscribers_of_frames() {
+    let mut client = connect_tcp(&serve("127.0.0.1:0"));
+
+    client.result("subscribe", Value::Null);
+    client.result("step", json!({ "frames": 2 }));
+
+    let frames: Vec<Value> = client.notifications.iter().map(|event| event["params"]["frame"].clone()).collect();
+    assert_eq!(frames, vec![json!(1), json!(2)]);
+    assert!(client.notifications.iter().all(|event| event["method"] == "frame"));
+
+    client.result("unsubscribe", Value::Null);
+    client.result("step", Value::Null);
+    assert_eq!(client.notifications.len(), 2);
+}
+
+#[test]
+fn reports_errors() {
+    let mut client = connect_tcp(&serve("127.0.0.1:0"));
+
+    assert_eq!(client.call("fly", Value::Null)["error"]["code"], -32601);
+    assert_eq!(client.call("step", json!({ "frames": -1 }))["error"]["code"], -32602);
+    assert_eq!(client.call("set_keys", json!({ "keys": [16] }))["error"]["code"], -32602);
+    assert_eq!(client.call("read_memory", json!({ "address": 0xFFF, "length": 2 }))["error"]["code"], -32602);
+    assert_eq!(client.call("load_state", json!({ "state": "00" }))["error"]["code"], -32000);
+
+    writeln!(client.writer, "{{not json").unwrap();
+    assert_eq!(client.receive()["error"]["code"], -32700);
+
+    // The connection survives all of that.
+    assert_eq!(client.result("status", Value::Null)["frame"], 0);
+}
+
+#[test]
+fn finds_and_freezes_a_counter() {
+    // Where saved cheats go, away from the user's own.
+    let config = std::env::temp_dir().join(format!("chip8-remote-cheats-{}", std::process::id()));
+    let serve_counter = || {
+        let mut server = bind("127.0.0.1:0", &COUNTER);
+        server.set_cheat_dir(config.clone());
+        spawn(server)
+    };
+
+    let mut client = connect_tcp(&serve_counter());
+    assert_eq!(client.call("search", json!({ "compare": "equal" }))["error"]["code"], -32000);
+
+    assert_eq!(client.result("search_start", Value::Null)["count"], 4096);
+    client.result("step", Value::Null);
+    client.result("search", json!({ "compare": "changed" }));
+    client.result("step", Value::Null);
+    let found = client.result("search", json!({ "compare": "increased" }));
+    assert_eq!(found["addresses"], json!([0x300]));
+
+    let counter = client.result("read_memory", json!({ "address": 0x300, "length": 1 }))["bytes"][0].clone();
+    assert_eq!(client.result("search", json!({ "compare": { "value": counter } }))["count"], 1);
+
+    // The frozen value shows at once, and the program's stores leave it.
+    client.result("freeze", json!({ "address": 0x300, "value": 0x42 }));
+    assert_eq!(client.result("read_memory", json!({ "address": 0x300, "length": 1 }))["bytes"], json!([0x42]));
+    client.result("step", json!({ "frames": 2 }));
+    assert_eq!(client.result("read_memory", json!({ "address": 0x300, "length": 1 }))["bytes"], json!([0x42]));
+    assert_eq!(client.result("get_registers", Value::Null)["v"][0], 0x43);
+    assert_eq!(client.result("get_cheats", Value::Null)["cheats"], json!(["300:42"]));
+
+    let saved = client.result("save_cheats", Value::Null);
+    assert!(saved["path"].as_str().unwrap().starts_with(config.to_str().unwrap()));
+
+    assert_eq!(client.result("unfreeze", json!({ "address": 0x300 })), true);
+    client.result("step", Value::Null);
+    assert_ne!(client.result("read_memory", json!({ "address": 0x300, "length": 1 }))["bytes"], json!([0x42]));
+
+    // A new machine for the same ROM picks the saved codes up.
+    let mut client = connect_tcp(&serve_counter());
+    assert_eq!(client.result("load_cheats", Value::Null)["cheats"], json!(["300:42"]));
+    client.result("step", Value::Null);
+    assert_eq!(client.result("read_memory", json!({ "address": 0x300, "length": 1 }))["bytes"], json!([0x42]));
+
+    assert_eq!(client.call("freeze", json!({ "address": 0x1000, "value": 0 }))["error"]["code"], -32602);
+
+    std::fs::remove_dir_all(&config).unwrap();
+}
+
+#[test]
+fn only_listens_on_loopback() {
+    assert!("0.0.0.0:0".parse::<Address>().is_err());
+    assert!("127.0.0.1:0".parse::<Address>().is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn serves_on_a_unix_socket() {
+    use std::os::unix::net::UnixStream;
+
+    let path = std::env::temp_dir().join(format!("chip8-remote-test-{}.sock", std::process::id()));
+    let address = serve(&format!("unix:{}", path.display()));
+    assert_eq!(address, Address::Unix(path.clone()));
+
+    let stream = UnixStream::connect(&path).unwrap();
+    let mut client = Client {
+        writer: stream.try_clone().unwrap(),
+        reader: BufReader::new(stream),
+        next_id: 0,
+        notifications: Vec::new(),
+    };
+
+    assert_eq!(client.result("step", Value::Null)["frame"], 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn leaves_other_files_at_the_socket_path_alone() {
+    let path = std::env::temp_dir().join(format!("chip8-remote-test-{}.txt", std::process::id()));
+    std::fs::write(&path, "keep me").unwrap();
+
+    let rom = Rom::from_bytes(&PROGRAM, None).unwrap();
+    let machine = Machine::new(rom, &Config::default()).unwrap();
+    assert!(Server::bind(&format!("unix:{}", path.display()).parse().unwrap(), machine).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn caps_the_frames_of_a_step() {
+    let address = serve("127.0.0.1:0");
+    let mut client = connect_tcp(&address);
+
+    assert_eq!(client.result("step", json!({ "frames": u32::MAX }))["frame"], 3600);
+}
@@ -0,0 +1,52 @@
+// Drives the WebAssembly build through web/chip8.js; run by tests/wasm.rs
+// with the path of the module.
+
+import assert from "node:assert/strict";
+import { readFile } from "node:fs/promises";
+
+import { Chip8 } from "../web/chip8.js";
+
+const bytes = await readFile(process.argv[2]);
+const module = await WebAssembly.compile(bytes);
+
+assert.deepEqual(WebAssembly.Module.imports(module), []);
+const exported = WebAssembly.Module.exports(module).map((entry) => entry.name);
+for (const name of ["memory", "rom_buffer", "config_buffer", "load_rom", "load_rom_with_config",
+                    "reset", "seed", "set_keys", "run_frame", "buzzer",
+                    "framebuffer", "framebuffer_width", "framebuffer_height", "error_message", "error_length"]) {
+  assert.ok(exported.includes(name), `${name} is not exported`);
+}
+
+const chip8 = await Chip8.load(bytes);
+assert.throws(() => chip8.runFrame(), /no ROM is loaded/);
+
+// Draws a "0" at the top left, then sounds the buzzer once key 5 is
+// pressed.
+chip8.loadRom(Uint8Array.of(
+  0xA2, 0x12, 0xD0, 0x05, 0x62, 0x05, 0xE2, 0x9E, 0x12, 0x06, 0x63, 0x0A, 0xF3, 0x18, 0x12, 0x0E,
+  0x00, 0x00, 0xF0, 0x90, 0x90, 0x90, 0xF0,
+));
+assert.equal(chip8.runFrame(), false);
+
+const { width, height, pixels } = chip8.framebuffer();
+assert.equal(width, 64);
+assert.equal(height, 32);
+assert.deepEqual([...pixels.subarray(0, 5)], [1, 1, 1, 1, 0]);
+assert.deepEqual([...pixels.subarray(64, 69)], [1, 0, 0, 1, 0]);
+
+chip8.setKeys(1 << 5);
+assert.equal(chip8.runFrame(), true);
+
+chip8.reset();
+assert.equal(chip8.runFrame(), false);
+
+assert.throws(() => chip8.loadRom(new Uint8Array(65536)), /only 65024 fit/);
+
+chip8.loadRom(Uint8Array.of(0x00, 0xEE));
+assert.throws(() => chip8.runFrame(), /return with an empty stack at 0x200/);
+
+chip8.loadRom(Uint8Array.of(0x00, 0xEE), 0, "[cpu]\nload_address = 0x300\n");
+assert.throws(() => chip8.runFrame(), /return with an empty stack at 0x300/);
+assert.throws(() => chip8.loadRom(Uint8Array.of(0x00, 0xEE), 0, "[cpu]\nspeed = 0\n"), /cpu.speed must be greater than zero/);
+
+console.log("ok");
@@ -0,0 +1,30 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// Builds the core for WebAssembly and runs `tests/wasm.mjs` against it
+/// under Node.
+#[test]
+fn runs_in_a_wasm_runtime() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A target directory of its own, apart from the one running the tests.
+    let exe = env::current_exe().unwrap();
+    let target = exe.parent().unwrap().parent().unwrap().join("wasm");
+
+    let status = Command::new(option_env!("CARGO").unwrap_or("cargo"))
+        .args(["build", "--lib", "--target", "wasm32-unknown-unknown"])
+        .arg("--manifest-path").arg(root.join("Cargo.toml"))
+        .arg("--target-dir").arg(&target)
+        .status()
+        .expect("cannot run cargo");
+    assert!(status.success(), "the core does not build for wasm32-unknown-unknown; is the target installed?");
+
+    let module = target.join("wasm32-unknown-unknown/debug/chip8.wasm");
+    let output = Command::new("node")
+        .arg(root.join("tests/wasm.mjs"))
+        .arg(&module)
+        .output()
+        .expect("cannot run node");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
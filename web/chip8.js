@@ -0,0 +1,82 @@
+// Runs CHIP-8 ROMs in web pages and Node on the WebAssembly build of the
+// core, made with
+//
+//     cargo build --release --lib --target wasm32-unknown-unknown
+//
+// as target/wasm32-unknown-unknown/release/chip8.wasm. Each Chip8 is a
+// separate instance of the module with its own machine.
+
+const OK = 0;
+
+export class Chip8 {
+  // Instantiates the core from its bytes or from a fetch() response.
+  static async load(source) {
+    const { instance } = typeof Response !== "undefined" && source instanceof Response
+      ? await WebAssembly.instantiateStreaming(source)
+      : await WebAssembly.instantiate(source);
+
+    return new Chip8(instance.exports);
+  }
+
+  constructor(exports) {
+    this.exports = exports;
+  }
+
+  // Loads a ROM from a Uint8Array, with the settings of a config file
+  // given as TOML text, if any, over the built-in defaults. Throws when
+  // the ROM or the config is invalid.
+  loadRom(rom, seed = 0, config = undefined) {
+    // Views of memory go stale when it grows, so take one only after
+    // allocating.
+    const pointer = this.exports.rom_buffer(rom.length);
+    this.bytes(pointer, rom.length).set(rom);
+
+    if (config === undefined) {
+      this.check(this.exports.load_rom(rom.length));
+    } else {
+      const text = new TextEncoder().encode(config);
+      const configPointer = this.exports.config_buffer(text.length);
+      this.bytes(configPointer, text.length).set(text);
+      this.check(this.exports.load_rom_with_config(rom.length, text.length));
+    }
+    this.check(this.exports.seed(seed));
+  }
+
+  reset() {
+    this.check(this.exports.reset());
+  }
+
+  // Sets the pressed keys, bit N for key N.
+  setKeys(keys) {
+    this.exports.set_keys(keys);
+  }
+
+  // Runs a sixtieth of a second, returning whether the buzzer sounded.
+  // Throws when the program faults.
+  runFrame() {
+    this.check(this.exports.run_frame());
+
+    return this.exports.buzzer() !== 0;
+  }
+
+  // The display as { width, height, pixels }, with one color index from 0
+  // to 3 per pixel, row by row.
+  framebuffer() {
+    const pointer = this.exports.framebuffer();
+    const width = this.exports.framebuffer_width();
+    const height = this.exports.framebuffer_height();
+
+    return { width, height, pixels: this.bytes(pointer, width * height).slice() };
+  }
+
+  bytes(pointer, length) {
+    return new Uint8Array(this.exports.memory.buffer, pointer, length);
+  }
+
+  check(status) {
+    if (status !== OK) {
+      const message = this.bytes(this.exports.error_message(), this.exports.error_length());
+      throw new Error(new TextDecoder().decode(message));
+    }
+  }
+}